@@ -1,7 +1,12 @@
-use std::hint::black_box;
+use std::{hint::black_box, sync::Arc, thread};
 
-use criterion::{criterion_group, criterion_main, Criterion};
-use lock_freedom::map::Map;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use lock_freedom::map::{Map, MapBuilder};
+
+// Enough keys for the sub-tables to fill up, which is where compressed tables,
+// copied on every write, fall behind full ones.
+const BIG: u64 = 100_000;
+const THREADS: u64 = 8;
 
 fn random_read_write(n: u64) {
     let map = Map::new();
@@ -13,10 +18,89 @@ fn random_read_write(n: u64) {
     }
 }
 
+fn filled(compressed: bool) -> Map<u64, u64> {
+    let map = MapBuilder::new().compressed(compressed).build();
+    for i in 0..BIG {
+        map.insert(i, i);
+    }
+    map
+}
+
+fn filled_by_threads(compressed: bool) -> Arc<Map<u64, u64>> {
+    let map = Arc::new(MapBuilder::new().compressed(compressed).build());
+    let threads = (0..THREADS)
+        .map(|t| {
+            let map = map.clone();
+            thread::spawn(move || {
+                for i in (t..BIG).step_by(THREADS as usize) {
+                    map.insert(i, i);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    map
+}
+
+// Compares maps with and without compressed tables. The routines return the
+// maps so that dropping them is not measured. See `criterion_benchmark` for
+// the lint.
+#[allow(clippy::incompatible_msrv)]
+fn big_map(c: &mut Criterion, name: &str, compressed: bool) {
+    let mut group = c.benchmark_group(name);
+    group.sample_size(10);
+
+    group.bench_function("insert", |b| b.iter(|| filled(compressed)));
+    group.bench_function("insert threads", |b| {
+        b.iter(|| filled_by_threads(compressed))
+    });
+    group.bench_function("overwrite", |b| {
+        b.iter_batched(
+            || filled(compressed),
+            |map| {
+                for i in 0..BIG {
+                    map.insert(i, i + 1);
+                }
+                map
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("get", |b| {
+        let map = filled(compressed);
+        b.iter(|| {
+            for i in 0..BIG {
+                black_box(map.get(&i).is_some());
+            }
+        })
+    });
+    group.bench_function("remove", |b| {
+        b.iter_batched(
+            || filled(compressed),
+            |map| {
+                for i in 0..BIG {
+                    map.remove(&i);
+                }
+                map
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+// Benchmarks need criterion, whose MSRV is far above the crate's, so
+// `black_box` being newer than the crate's MSRV does not matter here.
+#[allow(clippy::incompatible_msrv)]
 fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("read write 20", |b| {
         b.iter(|| random_read_write(black_box(20)))
     });
+    big_map(c, "big map", false);
+    big_map(c, "big compressed map", true);
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -0,0 +1,475 @@
+use crate::map::{Iter as MapIter, Map, MapBuilder, ReadGuard, Removed};
+use alloc::vec::Vec;
+use core::{
+    borrow::Borrow,
+    fmt,
+    hash::{BuildHasher, Hash},
+    ops::Deref,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering::*},
+};
+use std::collections::hash_map::RandomState;
+
+/// A lock-free cache with bounded capacity. This is implemented on top of
+/// [`Map`], whose values are wrapped in [`Cached`].
+///
+/// Every entry has a weight, which is `1` unless a weigher is given, and the
+/// cache keeps the sum of the weights of its entries below a maximum weight.
+/// Inserting past the maximum evicts entries using the CLOCK policy: each
+/// entry has an access bit, set whenever the entry is read. A clock hand
+/// sweeps the entries in the order of their hashes, clearing the access bits
+/// it finds set, and evicts the first entry whose bit is already clear. So,
+/// entries read since the last sweep get a second chance. Evicted entries are
+/// handed back as [`Removed`], so they can be reused.
+///
+/// # Example
+/// ```rust
+/// use lock_freedom::cache::Cache;
+///
+/// let cache = Cache::new(2);
+/// cache.insert("a", 1);
+/// cache.insert("b", 2);
+/// assert_eq!(*cache.get("a").unwrap().val().val(), 1);
+///
+/// let evicted = cache.insert("c", 3).evicted;
+/// assert_eq!(evicted.len(), 1);
+/// assert!(cache.get(*evicted[0].key()).is_none());
+/// assert_eq!(cache.weight(), 2);
+/// assert_eq!((cache.hits(), cache.misses()), (1, 1));
+/// ```
+pub struct Cache<K, V, H = RandomState> {
+    map: Map<K, Cached<V>, H>,
+    weigher: fn(&K, &V) -> usize,
+    max_weight: usize,
+    weight: AtomicUsize,
+    // The slot of the map's top table from which the next sweep starts.
+    hand: AtomicUsize,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl<K, V> Cache<K, V> {
+    /// Creates a [`Cache`] holding at most `max_entries` entries, with the
+    /// default hasher builder.
+    pub fn new(max_entries: usize) -> Self {
+        Self::with_builder(max_entries, MapBuilder::new())
+    }
+
+    /// Creates a [`Cache`] whose entries' weights, given by `weigher`, sum at
+    /// most `max_weight`, with the default hasher builder.
+    pub fn with_weigher(max_weight: usize, weigher: fn(&K, &V) -> usize) -> Self {
+        Self::with_builder_and_weigher(max_weight, weigher, MapBuilder::new())
+    }
+}
+
+impl<K, V, H> Cache<K, V, H> {
+    /// The maximum sum of the weights of the entries.
+    pub fn max_weight(&self) -> usize {
+        self.max_weight
+    }
+
+    /// The approximate sum of the weights of the entries. It may transiently
+    /// exceed [`max_weight`](Cache::max_weight) while insertions are still
+    /// evicting entries.
+    pub fn weight(&self) -> usize {
+        self.weight.load(Relaxed)
+    }
+
+    /// How many times [`get`](Cache::get) found an entry.
+    pub fn hits(&self) -> usize {
+        self.hits.load(Relaxed)
+    }
+
+    /// How many times [`get`](Cache::get) did not find an entry.
+    pub fn misses(&self) -> usize {
+        self.misses.load(Relaxed)
+    }
+
+    /// Tests if the cache has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Creates an iterator over guarded references to the entries. Iterating
+    /// does not set the access bits of the entries.
+    pub fn iter(&self) -> MapIter<'_, K, Cached<V>> {
+        self.map.iter()
+    }
+}
+
+impl<K, V, H> Cache<K, V, H>
+where
+    H: BuildHasher,
+{
+    /// Creates a [`Cache`] holding at most `max_entries` entries, using the
+    /// configuration of the given [`MapBuilder`].
+    pub fn with_builder(max_entries: usize, builder: MapBuilder<H>) -> Self {
+        Self::with_builder_and_weigher(max_entries, |_, _| 1, builder)
+    }
+
+    /// Creates a [`Cache`] whose entries' weights, given by `weigher`, sum at
+    /// most `max_weight`, using the configuration of the given
+    /// [`MapBuilder`].
+    pub fn with_builder_and_weigher(
+        max_weight: usize,
+        weigher: fn(&K, &V) -> usize,
+        builder: MapBuilder<H>,
+    ) -> Self {
+        Self {
+            map: Map::with_builder(builder),
+            weigher,
+            max_weight,
+            weight: AtomicUsize::new(0),
+            hand: AtomicUsize::new(0),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// Searches for the entry identified by the given key and sets its access
+    /// bit. Counts a hit if it is found, a miss otherwise. The method accepts
+    /// a type resulted from borrowing the stored key, just like [`Map::get`].
+    pub fn get<'cache, Q>(&'cache self, key: &Q) -> Option<ReadGuard<'cache, K, Cached<V>>>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        match self.map.get(key) {
+            Some(guard) => {
+                guard.val().access();
+                self.hits.fetch_add(1, Relaxed);
+                Some(guard)
+            }
+
+            None => {
+                self.misses.fetch_add(1, Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Inserts unconditionally the given key and value, then evicts entries
+    /// until the weight of the cache is within the maximum. The new entry
+    /// starts with its access bit set, but it is evicted as well if it alone
+    /// weighs more than the maximum.
+    pub fn insert(&self, key: K, val: V) -> Insertion<K, V>
+    where
+        K: Hash + Eq,
+    {
+        let weight = (self.weigher)(&key, &val);
+        // The weight is added before the entry is published, so removing the
+        // entry never makes the sum of the weights underflow.
+        self.weight.fetch_add(weight, Relaxed);
+        let replaced = self.map.insert(key, Cached::new(val, weight));
+        if let Some(removed) = &replaced {
+            self.weight.fetch_sub(removed.val().weight, Relaxed);
+        }
+
+        let mut evicted = Vec::new();
+        while self.weight.load(Relaxed) > self.max_weight {
+            match self.evict() {
+                Some(removed) => evicted.push(removed),
+                None => break,
+            }
+        }
+
+        Insertion { replaced, evicted }
+    }
+
+    /// Removes the entry identified by the given key, without evicting it.
+    /// The method accepts a type resulted from borrowing the stored key, just
+    /// like [`Map::remove`].
+    pub fn invalidate<Q>(&self, key: &Q) -> Option<Removed<K, Cached<V>>>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let removed = self.map.remove(key)?;
+        self.weight.fetch_sub(removed.val().weight, Relaxed);
+        Some(removed)
+    }
+
+    /// Moves the clock hand until it finds an entry whose access bit is clear,
+    /// and evicts it. Returns `None` if the cache is empty.
+    pub fn evict(&self) -> Option<Removed<K, Cached<V>>>
+    where
+        K: Hash + Eq,
+    {
+        let mask = (1 << self.map.bits()) - 1;
+        let mut slot = self.hand.load(Relaxed);
+        // Number of times the hand went back to the first slot.
+        let mut laps = 0;
+
+        loop {
+            let mut found_any = false;
+
+            for guard in self.map.iter_from(slot) {
+                found_any = true;
+                let (key, cached) = &*guard;
+
+                // After a whole lap, every access bit was cleared at least
+                // once, so we stop giving second chances, which would never
+                // end if the entries kept being read in the meantime.
+                if laps < 2 && cached.accessed.swap(false, Relaxed) {
+                    continue;
+                }
+
+                // Only evict the very entry we inspected, and not another
+                // one inserted meanwhile with the same key.
+                let removed = self
+                    .map
+                    .remove_with(key, |(_, stored)| ptr::eq(stored, cached));
+
+                if let Some(removed) = removed {
+                    // Entries in the same slot might have been skipped, so
+                    // the next sweep starts again at this one.
+                    let hand = self.map.hash_of(removed.key()) as usize & mask;
+                    self.hand.store(hand, Relaxed);
+                    self.weight.fetch_sub(removed.val().weight, Relaxed);
+                    return Some(removed);
+                }
+            }
+
+            if slot == 0 && !found_any {
+                return None;
+            }
+
+            slot = 0;
+            laps += 1;
+        }
+    }
+}
+
+impl<K, V, H> fmt::Debug for Cache<K, V, H>
+where
+    H: fmt::Debug,
+{
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Cache {{ map: {:?}, max_weight: {:?}, weight: {:?}, hand: {:?}, hits: {:?}, \
+             misses: {:?} }}",
+            self.map, self.max_weight, self.weight, self.hand, self.hits, self.misses
+        )
+    }
+}
+
+/// A value stored in a [`Cache`], together with its weight and access bit.
+/// Dereferences to the value.
+pub struct Cached<V> {
+    val: V,
+    weight: usize,
+    accessed: AtomicBool,
+}
+
+impl<V> Cached<V> {
+    fn new(val: V, weight: usize) -> Self {
+        Self {
+            val,
+            weight,
+            accessed: AtomicBool::new(true),
+        }
+    }
+
+    fn access(&self) {
+        // Avoids writing to the cache line when the bit is already set.
+        if !self.accessed.load(Relaxed) {
+            self.accessed.store(true, Relaxed);
+        }
+    }
+
+    /// The cached value.
+    pub fn val(&self) -> &V {
+        &self.val
+    }
+
+    /// The weight of the entry, as given by the cache's weigher when the
+    /// entry was inserted.
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+
+    /// Tests if the entry was read since the clock hand last passed by it.
+    pub fn accessed(&self) -> bool {
+        self.accessed.load(Relaxed)
+    }
+
+    /// Takes the value out.
+    pub fn into_inner(self) -> V {
+        self.val
+    }
+}
+
+impl<V> Deref for Cached<V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        &self.val
+    }
+}
+
+impl<V> fmt::Debug for Cached<V>
+where
+    V: fmt::Debug,
+{
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Cached {{ val: {:?}, weight: {:?}, accessed: {:?} }}",
+            self.val, self.weight, self.accessed
+        )
+    }
+}
+
+/// The result of [`Cache::insert`].
+#[derive(Debug)]
+pub struct Insertion<K, V> {
+    /// The entry with the same key which the insertion replaced, if any.
+    pub replaced: Option<Removed<K, Cached<V>>>,
+    /// The entries evicted to keep the weight of the cache within its
+    /// maximum. Might include the inserted entry itself.
+    pub evicted: Vec<Removed<K, Cached<V>>>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{borrow::ToOwned, string::String, sync::Arc};
+    use core::hash::{BuildHasherDefault, Hasher};
+    use std::thread;
+
+    #[derive(Default)]
+    struct Identity(u64);
+
+    impl Hasher for Identity {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, _bytes: &[u8]) {
+            unreachable!()
+        }
+
+        fn write_u64(&mut self, int: u64) {
+            self.0 = int;
+        }
+    }
+
+    #[test]
+    fn clock() {
+        // Small keys hash to the slot of the same number, so the hand sweeps
+        // them in order.
+        let builder = MapBuilder::new().hasher(BuildHasherDefault::<Identity>::default());
+        let cache = Cache::with_builder(3, builder);
+        let evicted_by = |key: u64| {
+            cache
+                .insert(key, key)
+                .evicted
+                .iter()
+                .map(|removed| *removed.key())
+                .collect::<Vec<_>>()
+        };
+
+        for i in 0..3 {
+            assert_eq!(evicted_by(i), []);
+        }
+        // The first lap only clears the bits set on insertion.
+        assert_eq!(evicted_by(3), [0]);
+        // A read gives the entry a second chance.
+        assert!(cache.get(&1).is_some());
+        assert_eq!(evicted_by(4), [2]);
+        assert_eq!(evicted_by(5), [3]);
+        assert_eq!(evicted_by(6), [1]);
+        assert_eq!(cache.weight(), 3);
+    }
+
+    #[test]
+    fn counts_hits_and_misses() {
+        let cache = Cache::new(4);
+        cache.insert("a", 1);
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+        assert_eq!(cache.hits(), 2);
+        assert_eq!(cache.misses(), 1);
+    }
+
+    #[test]
+    fn weights() {
+        let cache = Cache::with_weigher(10, |_: &u32, val: &String| val.len());
+        assert!(cache.insert(1, "abcd".to_owned()).evicted.is_empty());
+        assert!(cache.insert(2, "efgh".to_owned()).evicted.is_empty());
+        assert_eq!(cache.weight(), 8);
+
+        let insertion = cache.insert(1, "ij".to_owned());
+        assert_eq!(insertion.replaced.unwrap().val().weight(), 4);
+        assert!(insertion.evicted.is_empty());
+        assert_eq!(cache.weight(), 6);
+
+        let evicted = cache.insert(3, "klmnop".to_owned()).evicted;
+        let evicted_weight = evicted
+            .iter()
+            .map(|removed| removed.val().weight())
+            .sum::<usize>();
+        assert!(evicted_weight >= 2);
+        assert_eq!(cache.weight(), 12 - evicted_weight);
+
+        // An entry heavier than the whole cache is evicted as well.
+        let evicted = cache.insert(4, "qrstuvwxyz!".to_owned()).evicted;
+        assert!(evicted.iter().any(|removed| *removed.key() == 4));
+        assert!(cache.get(&4).is_none());
+        assert!(cache.weight() <= 10);
+    }
+
+    #[test]
+    fn invalidate_and_reuse() {
+        let cache = Cache::new(2);
+        cache.insert(1, "one".to_owned());
+        cache.insert(2, "two".to_owned());
+        let removed = cache.invalidate(&1).unwrap();
+        assert_eq!(cache.weight(), 1);
+        assert!(cache.invalidate(&1).is_none());
+
+        let (key, cached) = Removed::try_into(removed).unwrap();
+        assert_eq!((key, cached.into_inner()), (1, "one".to_owned()));
+
+        assert!(cache.evict().is_some());
+        assert!(cache.evict().is_none());
+        assert_eq!(cache.weight(), 0);
+    }
+
+    #[test]
+    fn multithreaded() {
+        const THREADS: usize = 8;
+        const ROUNDS: usize = 512;
+        const CAPACITY: usize = 64;
+
+        let cache = Arc::new(Cache::new(CAPACITY));
+        let mut threads = Vec::with_capacity(THREADS);
+        for i in 0..THREADS {
+            let cache = cache.clone();
+            threads.push(thread::spawn(move || {
+                for j in 0..ROUNDS {
+                    cache.insert((i, j), j);
+                    if let Some(guard) = cache.get(&(i, j / 2)) {
+                        assert_eq!(**guard.val(), j / 2);
+                    }
+                    if j % 7 == 0 {
+                        cache.invalidate(&(i, j / 3));
+                    }
+                    if j % 64 == 0 {
+                        thread::yield_now();
+                    }
+                }
+            }));
+        }
+        for thread in threads {
+            thread.join().expect("thread failed");
+        }
+
+        let entries = cache.iter().count();
+        assert!(entries <= CAPACITY);
+        assert_eq!(cache.weight(), entries);
+        assert_eq!(cache.hits() + cache.misses(), THREADS * ROUNDS);
+    }
+}
@@ -0,0 +1,373 @@
+use super::{
+    mpmc,
+    signal::{Registration, Signal},
+    NoRecv, Receive, RecvErr, SendErr,
+};
+use core::{
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures_core::Stream;
+use futures_sink::Sink;
+
+/// A [`Stream`] of the messages of a channel. Yields `None` once all senders
+/// disconnected and the channel is empty. A pending stream is woken up by the
+/// next `send`, with no busy polling.
+pub struct RecvStream<R>
+where
+    R: Receive,
+{
+    receiver: R,
+    registration: Registration,
+}
+
+impl<R> RecvStream<R>
+where
+    R: Receive,
+{
+    /// Adapts the given receiver into a stream.
+    pub fn new(receiver: R) -> Self {
+        Self {
+            receiver,
+            registration: Registration::default(),
+        }
+    }
+
+    /// A reference to the adapted receiver.
+    pub fn get_ref(&self) -> &R {
+        &self.receiver
+    }
+
+    /// Gives the adapted receiver back.
+    pub fn into_inner(mut self) -> R {
+        self.receiver.signal().cancel(&mut self.registration);
+        // Moving the receiver out is safe because we forget the stream right
+        // after, and the registration was already cancelled.
+        unsafe {
+            let receiver = (&self.receiver as *const R).read();
+            core::ptr::drop_in_place(&mut self.registration);
+            core::mem::forget(self);
+            receiver
+        }
+    }
+}
+
+impl<R> From<R> for RecvStream<R>
+where
+    R: Receive,
+{
+    fn from(receiver: R) -> Self {
+        Self::new(receiver)
+    }
+}
+
+impl<R> Stream for RecvStream<R>
+where
+    R: Receive,
+{
+    type Item = R::Message;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let signal = this.receiver.signal() as *const Signal;
+        let receiver = &mut this.receiver;
+        // This dereferral is safe because the signal lives in the channel's
+        // shared state, which the receiver keeps alive. Receiving never
+        // moves or drops it.
+        unsafe { &*signal }.poll_until(&mut this.registration, cx, || match receiver.try_recv() {
+            Ok(message) => Some(Some(message)),
+            Err(RecvErr::NoSender) => Some(None),
+            Err(RecvErr::NoMessage) => None,
+        })
+    }
+}
+
+impl<R> Unpin for RecvStream<R> where R: Receive {}
+
+impl<R> Drop for RecvStream<R>
+where
+    R: Receive,
+{
+    fn drop(&mut self) {
+        self.receiver.signal().cancel(&mut self.registration);
+    }
+}
+
+impl<R> fmt::Debug for RecvStream<R>
+where
+    R: Receive + fmt::Debug,
+{
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "RecvStream {{ receiver: {:?} }}", self.receiver)
+    }
+}
+
+/// A [`Sink`] over the sender of a bounded MPMC channel. While the channel is
+/// full, the sink is not ready, and it is woken up as soon as some receiver
+/// takes a message. Fails with [`NoRecv`] if all receivers disconnected.
+pub struct SendSink<T> {
+    sender: mpmc::BoundedSender<T>,
+    pending: Option<T>,
+    registration: Registration,
+}
+
+impl<T> SendSink<T> {
+    /// Adapts the given sender into a sink.
+    pub fn new(sender: mpmc::BoundedSender<T>) -> Self {
+        Self {
+            sender,
+            pending: None,
+            registration: Registration::default(),
+        }
+    }
+
+    /// A reference to the adapted sender.
+    pub fn get_ref(&self) -> &mpmc::BoundedSender<T> {
+        &self.sender
+    }
+
+    fn poll_pending(&mut self, cx: &mut Context) -> Poll<Result<(), NoRecv<T>>> {
+        if self.pending.is_none() {
+            return Poll::Ready(Ok(()));
+        }
+
+        let sender = &self.sender;
+        let pending = &mut self.pending;
+        sender
+            .not_full()
+            .poll_until(&mut self.registration, cx, || {
+                // The message is only taken out in this closure, and it is put
+                // back whenever this closure returns `None`.
+                let message = pending.take()?;
+                match sender.send(message) {
+                    Ok(()) => Some(Ok(())),
+                    Err(SendErr::Full(message)) => {
+                        *pending = Some(message);
+                        None
+                    }
+                    Err(SendErr::NoRecv(message)) => Some(Err(NoRecv { message })),
+                }
+            })
+    }
+}
+
+impl<T> From<mpmc::BoundedSender<T>> for SendSink<T> {
+    fn from(sender: mpmc::BoundedSender<T>) -> Self {
+        Self::new(sender)
+    }
+}
+
+impl<T> Sink<T> for SendSink<T> {
+    type Error = NoRecv<T>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_pending(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        debug_assert!(
+            this.pending.is_none(),
+            "start_send called before poll_ready"
+        );
+        this.pending = Some(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_pending(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_pending(cx)
+    }
+}
+
+impl<T> Unpin for SendSink<T> {}
+
+impl<T> Drop for SendSink<T> {
+    fn drop(&mut self) {
+        self.sender.not_full().cancel(&mut self.registration);
+    }
+}
+
+impl<T> fmt::Debug for SendSink<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "SendSink {{ sender: {:?} }}", self.sender)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::channel::spsc;
+    use alloc::{sync::Arc, vec::Vec};
+    use core::sync::atomic::{AtomicBool, Ordering::*};
+    use std::{
+        task::{Wake, Waker},
+        thread::{self, Thread},
+    };
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    // A waker which only records whether it was woken.
+    #[derive(Default)]
+    struct FlagWaker(AtomicBool);
+
+    impl Wake for FlagWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Release);
+        }
+    }
+
+    // A minimal executor: polls until ready, parking the thread meanwhile.
+    fn block_on<F, R>(mut poll: F) -> R
+    where
+        F: FnMut(&mut Context) -> Poll<R>,
+    {
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match poll(&mut cx) {
+                Poll::Ready(res) => break res,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    fn next<S>(stream: &mut S) -> Option<S::Item>
+    where
+        S: Stream + Unpin,
+    {
+        block_on(|cx| Pin::new(&mut *stream).poll_next(cx))
+    }
+
+    fn feed<S, T>(sink: &mut S, item: T) -> Result<(), S::Error>
+    where
+        S: Sink<T> + Unpin,
+    {
+        block_on(|cx| Pin::new(&mut *sink).poll_ready(cx))?;
+        Pin::new(&mut *sink).start_send(item)?;
+        block_on(|cx| Pin::new(&mut *sink).poll_flush(cx))
+    }
+
+    #[test]
+    fn stream_wakes_on_send() {
+        const MSGS: usize = 256;
+
+        let (mut sender, receiver) = spsc::create::<usize>();
+        let mut stream = RecvStream::new(receiver);
+        let thread = thread::spawn(move || {
+            for i in 0..MSGS {
+                sender.send(i).unwrap();
+                if i % 16 == 0 {
+                    thread::yield_now();
+                }
+            }
+        });
+
+        let received = (0..MSGS)
+            .filter_map(|_| next(&mut stream))
+            .collect::<Vec<_>>();
+        assert_eq!(received, (0..MSGS).collect::<Vec<_>>());
+        thread.join().unwrap();
+        assert_eq!(next(&mut stream), None);
+    }
+
+    #[test]
+    fn stream_of_shared_receivers() {
+        const THREADS: usize = 4;
+        const MSGS: usize = 256;
+
+        let (sender, receiver) = mpmc::create::<usize>();
+        let mut threads = Vec::with_capacity(THREADS);
+        for _ in 0..THREADS {
+            let mut stream = RecvStream::new(receiver.clone());
+            threads.push(thread::spawn(move || {
+                let mut sum = 0;
+                while let Some(i) = next(&mut stream) {
+                    sum += i;
+                }
+                sum
+            }));
+        }
+        drop(receiver);
+
+        for i in 0..MSGS {
+            sender.send(i).unwrap();
+        }
+        drop(sender);
+
+        let sum = threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .sum::<usize>();
+        assert_eq!(sum, (0..MSGS).sum());
+    }
+
+    #[test]
+    fn dropped_stream_passes_wakeup_on() {
+        let (sender, receiver) = mpmc::create::<usize>();
+        let mut first = RecvStream::new(receiver.clone());
+        let mut second = RecvStream::new(receiver);
+        let first_flag = Arc::new(FlagWaker::default());
+        let second_flag = Arc::new(FlagWaker::default());
+        let first_waker = Waker::from(first_flag.clone());
+        let second_waker = Waker::from(second_flag.clone());
+        let mut first_cx = Context::from_waker(&first_waker);
+        let mut second_cx = Context::from_waker(&second_waker);
+
+        assert!(Pin::new(&mut first).poll_next(&mut first_cx).is_pending());
+        assert!(Pin::new(&mut second).poll_next(&mut second_cx).is_pending());
+
+        sender.send(5).unwrap();
+        assert!(first_flag.0.load(Acquire));
+        assert!(!second_flag.0.load(Acquire));
+
+        drop(first);
+        assert!(second_flag.0.load(Acquire));
+        assert_eq!(
+            Pin::new(&mut second).poll_next(&mut second_cx),
+            Poll::Ready(Some(5))
+        );
+    }
+
+    #[test]
+    fn sink_backpressure() {
+        const MSGS: usize = 256;
+
+        let (sender, receiver) = mpmc::bounded::<usize>(4);
+        let mut sink = SendSink::new(sender);
+        let thread = thread::spawn(move || {
+            let mut stream = RecvStream::new(receiver);
+            let mut received = Vec::new();
+            while let Some(i) = next(&mut stream) {
+                received.push(i);
+            }
+            received
+        });
+
+        for i in 0..MSGS {
+            feed(&mut sink, i).unwrap();
+        }
+        drop(sink);
+
+        assert_eq!(thread.join().unwrap(), (0..MSGS).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn sink_no_receiver() {
+        let (sender, receiver) = mpmc::bounded::<usize>(1);
+        let mut sink = SendSink::new(sender);
+        feed(&mut sink, 0).unwrap();
+        drop(receiver);
+        assert_eq!(feed(&mut sink, 1).unwrap_err().message, 1);
+    }
+}
@@ -0,0 +1,891 @@
+use super::signal::{deadline_of, recv_until, Signal};
+pub use super::{
+    NoRecv,
+    RecvErr::{self, *},
+};
+use crate::{
+    incin::Incinerator,
+    owned_alloc::OwnedAlloc,
+    ptr::{bypass_null, check_null_align},
+};
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::{
+    fmt, mem,
+    ptr::{null_mut, NonNull},
+    sync::atomic::{AtomicPtr, AtomicUsize, Ordering::*},
+};
+use std::time::{Duration, Instant};
+
+/// Creates an asynchronous lock-free broadcast channel. Every [`Receiver`]
+/// sees every message sent after it subscribed, so messages must be
+/// [`Clone`] to be received. Both [`Sender`] and [`Receiver`] are clonable.
+/// A cloned receiver starts at the same point as the original one, while
+/// [`Sender::subscribe`] creates a receiver which only sees new messages.
+///
+/// Messages are kept until every live receiver has passed them. A receiver
+/// which never receives keeps every message sent after it from being
+/// reclaimed. See [`bounded`] for a channel which drops old messages instead.
+pub fn create<T>() -> (Sender<T>, Receiver<T>) {
+    with_incin(SharedIncin::new())
+}
+
+/// Same as [`create`], but use a passed incinerator instead of creating a new
+/// one.
+pub fn with_incin<T>(incin: SharedIncin<T>) -> (Sender<T>, Receiver<T>) {
+    check_null_align::<Node<T>>();
+
+    // A single node without a message, referenced by both the back and the
+    // receiver.
+    let alloc = OwnedAlloc::new(Node {
+        message: None,
+        refs: AtomicUsize::new(2),
+        next: AtomicPtr::new(null_mut()),
+    });
+    let single_node = alloc.into_raw();
+
+    let shared = Arc::new(Shared {
+        back: AtomicPtr::new(single_node.as_ptr()),
+        senders: AtomicUsize::new(1),
+        receivers: AtomicUsize::new(1),
+        incin,
+        signal: Signal::new(),
+    });
+
+    let sender = Sender {
+        shared: shared.clone(),
+    };
+    let receiver = Receiver {
+        cursor: single_node,
+        shared,
+    };
+
+    (sender, receiver)
+}
+
+/// The [`Sender`] handle of a broadcast channel. Created by [`create`] or
+/// [`with_incin`] function. It is clonable and does not require mutability.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends a message to every connected receiver. If all receivers
+    /// disconnected, an error is returned.
+    pub fn send(&self, message: T) -> Result<(), NoRecv<T>> {
+        if self.shared.receivers.load(Acquire) == 0 {
+            return Err(NoRecv { message });
+        }
+
+        // One reference for the back, one for the link from the previous
+        // node.
+        let alloc = OwnedAlloc::new(Node {
+            message: Some(message),
+            refs: AtomicUsize::new(2),
+            next: AtomicPtr::new(null_mut()),
+        });
+        let node = alloc.into_raw();
+
+        // Swapping takes over the reference the back held to the previous
+        // node.
+        let prev = self.shared.back.swap(node.as_ptr(), AcqRel);
+
+        // This is safe because we never store null in the back, and we own
+        // the reference the back held, so the previous node is alive. We only
+        // release it after linking, so whoever frees it sees the link.
+        unsafe {
+            let prev = bypass_null(prev);
+            prev.as_ref().next.store(node.as_ptr(), Release);
+            self.shared.release(prev);
+        }
+
+        self.shared.signal.notify_all();
+        Ok(())
+    }
+
+    /// Creates a new [`Receiver`] which will see every message sent from now
+    /// on.
+    pub fn subscribe(&self) -> Receiver<T> {
+        self.shared.receivers.fetch_add(1, AcqRel);
+
+        // The back node might be swapped out and released while we try to
+        // acquire it, so we need the incinerator paused.
+        let pause = self.shared.incin.get_unchecked().pause();
+        let cursor = loop {
+            // This is safe because we never store null in the back.
+            let back = unsafe { bypass_null(self.shared.back.load(Acquire)) };
+            // This dereferral is safe because we paused the incinerator and
+            // nodes are only deleted through it.
+            let refs = unsafe { &back.as_ref().refs };
+            let mut count = refs.load(Relaxed);
+
+            // A node with no references is about to be deleted, and it is
+            // not in the back anymore. We must not bring it back to life.
+            while count != 0 {
+                match refs.compare_exchange_weak(count, count + 1, Acquire, Relaxed) {
+                    Ok(_) => break,
+                    Err(found) => count = found,
+                }
+            }
+
+            if count != 0 {
+                break back;
+            }
+        };
+        pause.resume();
+
+        Receiver {
+            cursor,
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Tests if there are any [`Receiver`]s still connected. There are no
+    /// guarantees that [`send`](Sender::send) will succeed if this method
+    /// returns `true` because the [`Receiver`]s may disconnect meanwhile.
+    pub fn is_connected(&self) -> bool {
+        self.shared.receivers.load(Relaxed) != 0
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, AcqRel);
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, AcqRel) == 1 {
+            // Blocked receivers must see we disconnected.
+            self.shared.signal.notify_all();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "broadcast::Sender {{ ptr: {:p} }}", self.shared)
+    }
+}
+
+unsafe impl<T> Send for Sender<T> where T: Send + Sync {}
+unsafe impl<T> Sync for Sender<T> where T: Send + Sync {}
+
+/// The [`Receiver`] handle of a broadcast channel. Created by [`create`] or
+/// [`with_incin`] function, or by [`Sender::subscribe`]. It is clonable, and
+/// each clone sees every message.
+pub struct Receiver<T> {
+    // The last node seen, never null. We hold a reference to it.
+    cursor: NonNull<Node<T>>,
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Receiver<T>
+where
+    T: Clone,
+{
+    /// Tries to receive a copy of the next message. If no message is
+    /// available, [`Err`]`(`[`RecvErr::NoMessage`]`)` is returned. If the
+    /// senders disconnected, [`Err`]`(`[`RecvErr::NoSender`]`)` is returned.
+    pub fn recv(&mut self) -> Result<T, RecvErr> {
+        // We must check for disconnection before looking for messages. A
+        // message sent before the disconnection is then surely visible.
+        let disconnected = self.shared.senders.load(Acquire) == 0;
+
+        // This is safe because we hold a reference to our cursor.
+        let next = unsafe { self.cursor.as_ref().next.load(Acquire) };
+
+        match NonNull::new(next) {
+            Some(next) => {
+                // This is safe because our cursor links to the next node, and
+                // keeps it alive. Only the very first node of the channel has
+                // no message, and it is nobody's successor.
+                let message = unsafe { next.as_ref().message.clone().unwrap() };
+                // This is safe for the same reason. We now also reference the
+                // next node, and we can give up our cursor.
+                unsafe {
+                    next.as_ref().refs.fetch_add(1, Relaxed);
+                    let prev = mem::replace(&mut self.cursor, next);
+                    self.shared.release(prev);
+                }
+                Ok(message)
+            }
+
+            None if disconnected => Err(NoSender),
+
+            None => Err(NoMessage),
+        }
+    }
+
+    /// Receives a message, blocking the current thread while there are no new
+    /// messages. If the senders disconnected and there are no new messages,
+    /// [`Err`]`(`[`RecvErr::NoSender`]`)` is returned.
+    pub fn recv_blocking(&mut self) -> Result<T, RecvErr> {
+        self.recv_until(None)
+    }
+
+    /// Same as [`recv_blocking`](Self::recv_blocking), but gives up after the
+    /// given timeout, returning [`Err`]`(`[`RecvErr::NoMessage`]`)`.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<T, RecvErr> {
+        self.recv_until(deadline_of(timeout))
+    }
+
+    /// Same as [`recv_blocking`](Self::recv_blocking), but gives up when the
+    /// given deadline is reached, returning
+    /// [`Err`]`(`[`RecvErr::NoMessage`]`)`.
+    pub fn recv_deadline(&mut self, deadline: Instant) -> Result<T, RecvErr> {
+        self.recv_until(Some(deadline))
+    }
+
+    fn recv_until(&mut self, deadline: Option<Instant>) -> Result<T, RecvErr> {
+        match self.recv() {
+            Err(RecvErr::NoMessage) => (),
+            res => return res,
+        }
+        let shared = self.shared.clone();
+        recv_until(&shared.signal, deadline, || self.recv())
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Tests if there are any [`Sender`]s still connected. There are no
+    /// guarantees that [`recv`](Receiver::recv) will succeed if this method
+    /// returns `true` because the [`Sender`]s may disconnect meanwhile. This
+    /// method may also return `true` if the [`Sender`]s disconnected but
+    /// there are messages this receiver did not see yet.
+    pub fn is_connected(&self) -> bool {
+        // This is safe because we hold a reference to our cursor.
+        let next = unsafe { self.cursor.as_ref().next.load(Acquire) };
+        self.shared.senders.load(Acquire) != 0 || !next.is_null()
+    }
+
+    /// Tests if there are no messages this receiver did not see yet. This is
+    /// exact at the moment it checks the node after the receiver's cursor.
+    pub fn is_empty(&self) -> bool {
+        // This is safe because we hold a reference to our cursor.
+        unsafe { self.cursor.as_ref().next.load(Acquire).is_null() }
+    }
+
+    /// The shared incinerator used by this [`Receiver`].
+    pub fn incin(&self) -> SharedIncin<T> {
+        self.shared.incin.clone()
+    }
+
+    pub(super) fn signal(&self) -> &Signal {
+        &self.shared.signal
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.shared.receivers.fetch_add(1, AcqRel);
+        // This is safe because we hold a reference to our cursor, so it is
+        // alive.
+        unsafe { self.cursor.as_ref().refs.fetch_add(1, Relaxed) };
+        Self {
+            cursor: self.cursor,
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.receivers.fetch_sub(1, AcqRel);
+        // This is safe because we hold a reference to our cursor, and we will
+        // not use it anymore.
+        unsafe { self.shared.release(self.cursor) };
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "broadcast::Receiver {{ ptr: {:p} }}", self.shared)
+    }
+}
+
+unsafe impl<T> Send for Receiver<T> where T: Send + Sync {}
+unsafe impl<T> Sync for Receiver<T> where T: Send + Sync {}
+
+/// Creates a bounded lock-free broadcast channel, which keeps at most `cap`
+/// messages. When the channel is full, sending overwrites the oldest message,
+/// so senders never wait for slow receivers. A receiver which falls behind
+/// is told how many messages it missed through
+/// [`BoundedRecvErr::Lagged`], and then continues from the oldest message
+/// still kept.
+///
+/// # Panics
+/// Panics if `cap` is zero.
+pub fn bounded<T>(cap: usize) -> (BoundedSender<T>, BoundedReceiver<T>) {
+    assert!(cap > 0, "capacity must be positive");
+
+    let mut slots = Vec::with_capacity(cap);
+    for _ in 0..cap {
+        slots.push(AtomicPtr::new(null_mut()));
+    }
+
+    let shared = Arc::new(BoundedShared {
+        slots: slots.into_boxed_slice(),
+        tail: AtomicUsize::new(0),
+        senders: AtomicUsize::new(1),
+        receivers: AtomicUsize::new(1),
+        incin: Incinerator::new(),
+        signal: Signal::new(),
+    });
+
+    let sender = BoundedSender {
+        shared: shared.clone(),
+    };
+    let receiver = BoundedReceiver { shared, next: 0 };
+
+    (sender, receiver)
+}
+
+/// The error of [`BoundedReceiver::recv`] operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundedRecvErr {
+    /// Returned when there are no new messages, but there are still senders
+    /// connected.
+    NoMessage,
+    /// Returned when all senders were disconnected.
+    NoSender,
+    /// Returned when the receiver fell behind, and the given number of
+    /// messages were overwritten before it could see them. The next receive
+    /// gets the oldest message still kept.
+    Lagged(usize),
+}
+
+impl From<RecvErr> for BoundedRecvErr {
+    fn from(err: RecvErr) -> Self {
+        match err {
+            RecvErr::NoMessage => BoundedRecvErr::NoMessage,
+            RecvErr::NoSender => BoundedRecvErr::NoSender,
+        }
+    }
+}
+
+/// The sender handle of a bounded broadcast channel. Created by [`bounded`]
+/// function. It is clonable and does not require mutability.
+pub struct BoundedSender<T> {
+    shared: Arc<BoundedShared<T>>,
+}
+
+impl<T> BoundedSender<T> {
+    /// Sends a message to every connected receiver, overwriting the oldest
+    /// message if the channel is full. If all receivers disconnected, an
+    /// error is returned.
+    pub fn send(&self, message: T) -> Result<(), NoRecv<T>> {
+        let shared = &*self.shared;
+        if shared.receivers.load(Acquire) == 0 {
+            return Err(NoRecv { message });
+        }
+
+        let seq = shared.tail.fetch_add(1, AcqRel);
+        let slot = &shared.slots[seq % shared.slots.len()];
+        let entry = OwnedAlloc::new(Entry { seq, message }).into_raw();
+
+        // Overwritten entries might still be being read.
+        let pause = shared.incin.pause();
+        let mut loaded = slot.load(Acquire);
+
+        loop {
+            // This dereferral is safe because we paused the incinerator and
+            // entries are only deleted through it.
+            if let Some(found) = unsafe { loaded.as_ref() } {
+                if found.seq > seq {
+                    // Some faster sender already overwrote the slot with a
+                    // newer message. Ours is outdated before anyone could see
+                    // it. This is safe because we never shared the entry.
+                    unsafe { OwnedAlloc::from_raw(entry) };
+                    break;
+                }
+            }
+
+            match slot.compare_exchange(loaded, entry.as_ptr(), AcqRel, Acquire) {
+                Ok(_) => {
+                    if let Some(old) = NonNull::new(loaded) {
+                        // This is safe because the old entry is not reachable
+                        // anymore, and only the incinerator deletes it.
+                        pause.add_to_incin(unsafe { OwnedAlloc::from_raw(old) });
+                    }
+                    break;
+                }
+
+                Err(found) => loaded = found,
+            }
+        }
+
+        pause.resume();
+        shared.signal.notify_all();
+        Ok(())
+    }
+
+    /// Creates a new [`BoundedReceiver`] which will see every message sent
+    /// from now on.
+    pub fn subscribe(&self) -> BoundedReceiver<T> {
+        self.shared.receivers.fetch_add(1, AcqRel);
+        BoundedReceiver {
+            shared: self.shared.clone(),
+            next: self.shared.tail.load(Acquire),
+        }
+    }
+
+    /// Tests if there are any [`BoundedReceiver`]s still connected. There are
+    /// no guarantees that [`send`](BoundedSender::send) will succeed if this
+    /// method returns `true` because the receivers may disconnect meanwhile.
+    pub fn is_connected(&self) -> bool {
+        self.shared.receivers.load(Relaxed) != 0
+    }
+}
+
+impl<T> Clone for BoundedSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, AcqRel);
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for BoundedSender<T> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, AcqRel) == 1 {
+            // Blocked receivers must see we disconnected.
+            self.shared.signal.notify_all();
+        }
+    }
+}
+
+impl<T> fmt::Debug for BoundedSender<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "broadcast::BoundedSender {{ ptr: {:p} }}",
+            self.shared
+        )
+    }
+}
+
+unsafe impl<T> Send for BoundedSender<T> where T: Send + Sync {}
+unsafe impl<T> Sync for BoundedSender<T> where T: Send + Sync {}
+
+/// The receiver handle of a bounded broadcast channel. Created by [`bounded`]
+/// function, or by [`BoundedSender::subscribe`]. It is clonable, and each
+/// clone sees every message.
+pub struct BoundedReceiver<T> {
+    shared: Arc<BoundedShared<T>>,
+    // The sequence number of the next message to be seen.
+    next: usize,
+}
+
+impl<T> BoundedReceiver<T>
+where
+    T: Clone,
+{
+    /// Tries to receive a copy of the next message. If no message is
+    /// available, [`Err`]`(`[`BoundedRecvErr::NoMessage`]`)` is returned. If
+    /// the senders disconnected, [`Err`]`(`[`BoundedRecvErr::NoSender`]`)` is
+    /// returned. If the next message was overwritten,
+    /// [`Err`]`(`[`BoundedRecvErr::Lagged`]`)` is returned.
+    pub fn recv(&mut self) -> Result<T, BoundedRecvErr> {
+        let shared = &*self.shared;
+        let cap = shared.slots.len();
+        // We must check for disconnection before looking for messages. A
+        // message sent before the disconnection is then surely visible.
+        let disconnected = shared.senders.load(Acquire) == 0;
+
+        let pause = shared.incin.pause();
+        let loaded = shared.slots[self.next % cap].load(Acquire);
+
+        // This dereferral is safe because we paused the incinerator and
+        // entries are only deleted through it.
+        let res = match unsafe { loaded.as_ref() } {
+            Some(entry) if entry.seq == self.next => {
+                self.next += 1;
+                Ok(entry.message.clone())
+            }
+
+            // The slot was overwritten by a message at least one lap ahead.
+            Some(entry) if entry.seq > self.next => {
+                let oldest = shared.tail.load(Acquire).saturating_sub(cap);
+                let oldest = oldest.max(self.next + 1);
+                let missed = oldest - self.next;
+                self.next = oldest;
+                Err(BoundedRecvErr::Lagged(missed))
+            }
+
+            // The slot is empty or holds an older message.
+            _ if disconnected => Err(BoundedRecvErr::NoSender),
+
+            _ => Err(BoundedRecvErr::NoMessage),
+        };
+
+        pause.resume();
+        res
+    }
+
+    /// Receives a message, blocking the current thread while there are no new
+    /// messages. If the senders disconnected and there are no new messages,
+    /// [`Err`]`(`[`BoundedRecvErr::NoSender`]`)` is returned. Lagging is
+    /// reported immediately.
+    pub fn recv_blocking(&mut self) -> Result<T, BoundedRecvErr> {
+        self.recv_until(None)
+    }
+
+    /// Same as [`recv_blocking`](Self::recv_blocking), but gives up after the
+    /// given timeout, returning [`Err`]`(`[`BoundedRecvErr::NoMessage`]`)`.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<T, BoundedRecvErr> {
+        self.recv_until(deadline_of(timeout))
+    }
+
+    /// Same as [`recv_blocking`](Self::recv_blocking), but gives up when the
+    /// given deadline is reached, returning
+    /// [`Err`]`(`[`BoundedRecvErr::NoMessage`]`)`.
+    pub fn recv_deadline(&mut self, deadline: Instant) -> Result<T, BoundedRecvErr> {
+        self.recv_until(Some(deadline))
+    }
+
+    fn recv_until(&mut self, deadline: Option<Instant>) -> Result<T, BoundedRecvErr> {
+        let shared = self.shared.clone();
+        shared
+            .signal
+            .wait_until(deadline, || match self.recv() {
+                Err(BoundedRecvErr::NoMessage) => None,
+                res => Some(res),
+            })
+            .unwrap_or(Err(BoundedRecvErr::NoMessage))
+    }
+}
+
+impl<T> BoundedReceiver<T> {
+    /// Tests if there are any [`BoundedSender`]s still connected. There are
+    /// no guarantees that [`recv`](BoundedReceiver::recv) will succeed if this
+    /// method returns `true` because the senders may disconnect meanwhile.
+    /// This method may also return `true` if the senders disconnected but
+    /// there are messages this receiver did not see yet.
+    pub fn is_connected(&self) -> bool {
+        self.shared.senders.load(Acquire) != 0 || self.shared.tail.load(Acquire) > self.next
+    }
+
+    /// The number of messages this receiver did not see yet, at most the
+    /// capacity. Messages being sent concurrently may already be counted.
+    pub fn len(&self) -> usize {
+        let tail = self.shared.tail.load(Acquire);
+        tail.saturating_sub(self.next).min(self.capacity())
+    }
+
+    /// Tests if there are no messages this receiver did not see yet. This is
+    /// exact at the moment it checks the slot of the next message.
+    pub fn is_empty(&self) -> bool {
+        let shared = &*self.shared;
+        let pause = shared.incin.pause();
+        let loaded = shared.slots[self.next % shared.slots.len()].load(Acquire);
+        // This dereferral is safe because we paused the incinerator and
+        // entries are only deleted through it. An entry ahead of us means we
+        // lagged, and there are messages to report.
+        let empty = unsafe { loaded.as_ref() }.map_or(true, |entry| entry.seq < self.next);
+        pause.resume();
+        empty
+    }
+
+    /// The maximum number of messages kept for slow receivers.
+    pub fn capacity(&self) -> usize {
+        self.shared.slots.len()
+    }
+}
+
+impl<T> Clone for BoundedReceiver<T> {
+    fn clone(&self) -> Self {
+        self.shared.receivers.fetch_add(1, AcqRel);
+        Self {
+            shared: self.shared.clone(),
+            next: self.next,
+        }
+    }
+}
+
+impl<T> Drop for BoundedReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.receivers.fetch_sub(1, AcqRel);
+    }
+}
+
+impl<T> fmt::Debug for BoundedReceiver<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "broadcast::BoundedReceiver {{ ptr: {:p}, next: {} }}",
+            self.shared, self.next
+        )
+    }
+}
+
+unsafe impl<T> Send for BoundedReceiver<T> where T: Send + Sync {}
+unsafe impl<T> Sync for BoundedReceiver<T> where T: Send + Sync {}
+
+struct Shared<T> {
+    // never null, holds a reference to the last node
+    back: AtomicPtr<Node<T>>,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+    incin: SharedIncin<T>,
+    signal: Signal,
+}
+
+impl<T> Shared<T> {
+    // This function is unsafe because the caller must own a reference to the
+    // node, and must not use the node after calling it. Gives up the
+    // reference, deleting the node if it was the last one. Deleting a node
+    // gives up its reference to the next one.
+    unsafe fn release(&self, mut node: NonNull<Node<T>>) {
+        loop {
+            if node.as_ref().refs.fetch_sub(1, AcqRel) != 1 {
+                break;
+            }
+
+            // A node with no references is not in the back anymore, and the
+            // sender which took it from the back linked it before releasing
+            // it. The last node only loses its references when the channel is
+            // dropped.
+            let next = node.as_ref().next.load(Acquire);
+            // Deleting only through the incinerator because a subscribing
+            // sender might be reading the node.
+            self.incin.get_unchecked().add(OwnedAlloc::from_raw(node));
+
+            match NonNull::new(next) {
+                Some(next_nnptr) => node = next_nnptr,
+                None => break,
+            }
+        }
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        // This is safe because we never store null in the back, and the back
+        // holds a reference to the last node.
+        let back = *self.back.get_mut();
+        unsafe { self.release(bypass_null(back)) };
+    }
+}
+
+#[repr(align(/* at least */ 2))]
+struct Node<T> {
+    // Only the first node of the channel has no message.
+    message: Option<T>,
+    // References from receivers, from the back, and from the previous node.
+    refs: AtomicUsize,
+    next: AtomicPtr<Node<T>>,
+}
+
+make_shared_incin! {
+    { "`broadcast::Receiver`" }
+    pub SharedIncin<T> of OwnedAlloc<Node<T>>
+}
+
+impl<T> fmt::Debug for SharedIncin<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "SharedIncin {{ inner: {:?} }}", self.inner)
+    }
+}
+
+struct BoundedShared<T> {
+    slots: Box<[AtomicPtr<Entry<T>>]>,
+    // The sequence number of the next message to be sent.
+    tail: AtomicUsize,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+    incin: Incinerator<OwnedAlloc<Entry<T>>>,
+    signal: Signal,
+}
+
+impl<T> Drop for BoundedShared<T> {
+    fn drop(&mut self) {
+        for slot in self.slots.iter_mut() {
+            if let Some(entry) = NonNull::new(*slot.get_mut()) {
+                // This is safe because nobody else has access to the slots
+                // now, and entries are only stored in one slot.
+                unsafe { OwnedAlloc::from_raw(entry) };
+            }
+        }
+    }
+}
+
+struct Entry<T> {
+    seq: usize,
+    message: T,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::channel::broadcast;
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+    use std::thread;
+
+    #[test]
+    fn every_receiver_sees_every_message() {
+        const THREADS: usize = 4;
+        const MSGS: usize = 256;
+
+        let (sender, receiver) = broadcast::create::<usize>();
+        let mut threads = Vec::with_capacity(THREADS);
+
+        for _ in 0..THREADS {
+            let mut receiver = receiver.clone();
+            threads.push(thread::spawn(move || {
+                let mut received = Vec::with_capacity(MSGS);
+                while let Ok(i) = receiver.recv_blocking() {
+                    received.push(i);
+                }
+                received
+            }));
+        }
+
+        drop(receiver);
+
+        for i in 0..MSGS {
+            sender.send(i).unwrap();
+        }
+
+        drop(sender);
+
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), (0..MSGS).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn subscribe_sees_only_new() {
+        let (sender, mut receiver) = broadcast::create::<usize>();
+        sender.send(0).unwrap();
+        let mut late = sender.subscribe();
+        sender.send(1).unwrap();
+
+        assert_eq!(receiver.recv(), Ok(0));
+        assert_eq!(receiver.recv(), Ok(1));
+        assert_eq!(late.recv(), Ok(1));
+        assert_eq!(late.recv(), Err(broadcast::NoMessage));
+
+        drop(sender);
+        assert_eq!(receiver.recv(), Err(broadcast::NoSender));
+        assert_eq!(late.recv(), Err(broadcast::NoSender));
+    }
+
+    #[test]
+    fn no_receiver() {
+        let (sender, receiver) = broadcast::create::<usize>();
+        drop(receiver);
+        assert!(!sender.is_connected());
+        assert_eq!(sender.send(5).unwrap_err().message, 5);
+    }
+
+    #[test]
+    fn reclaims_messages() {
+        let (sender, mut receiver) = broadcast::create::<Arc<()>>();
+        let mut other = receiver.clone();
+        let message = Arc::new(());
+
+        for _ in 0..16 {
+            sender.send(message.clone()).unwrap();
+        }
+        while receiver.recv().is_ok() {}
+        assert_eq!(Arc::strong_count(&message), 17);
+
+        while other.recv().is_ok() {}
+        // The last message is still referenced by both receivers.
+        assert_eq!(Arc::strong_count(&message), 2);
+
+        drop(receiver);
+        drop(other);
+        drop(sender);
+        assert_eq!(Arc::strong_count(&message), 1);
+    }
+
+    #[test]
+    fn bounded_lagged() {
+        let (sender, mut receiver) = broadcast::bounded::<usize>(4);
+        for i in 0..10 {
+            sender.send(i).unwrap();
+        }
+
+        assert_eq!(receiver.recv(), Err(broadcast::BoundedRecvErr::Lagged(6)));
+        for i in 6..10 {
+            assert_eq!(receiver.recv(), Ok(i));
+        }
+        assert_eq!(receiver.recv(), Err(broadcast::BoundedRecvErr::NoMessage));
+
+        drop(sender);
+        assert_eq!(receiver.recv(), Err(broadcast::BoundedRecvErr::NoSender));
+    }
+
+    #[test]
+    fn bounded_correct_numbers() {
+        const THREADS: usize = 4;
+        const MSGS: usize = 512;
+
+        let (sender, receiver) = broadcast::bounded::<usize>(8);
+        let mut threads = Vec::with_capacity(THREADS);
+
+        for _ in 0..THREADS {
+            let mut receiver = receiver.clone();
+            threads.push(thread::spawn(move || {
+                let mut expected = 0;
+                loop {
+                    match receiver.recv_blocking() {
+                        Ok(i) => {
+                            assert_eq!(i, expected);
+                            expected += 1;
+                        }
+                        Err(broadcast::BoundedRecvErr::Lagged(n)) => expected += n,
+                        Err(_) => break,
+                    }
+                }
+                expected
+            }));
+        }
+
+        drop(receiver);
+
+        for i in 0..MSGS {
+            sender.send(i).unwrap();
+            if i % 16 == 0 {
+                thread::yield_now();
+            }
+        }
+
+        drop(sender);
+
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), MSGS);
+        }
+    }
+
+    #[test]
+    fn len_and_is_empty() {
+        let (sender, mut receiver) = broadcast::create::<usize>();
+        assert!(receiver.is_empty());
+        sender.send(1).unwrap();
+        assert!(!receiver.is_empty());
+        assert_eq!(receiver.recv(), Ok(1));
+        assert!(receiver.is_empty());
+
+        let (sender, mut receiver) = broadcast::bounded::<usize>(2);
+        assert_eq!(receiver.capacity(), 2);
+        assert!(receiver.is_empty());
+        for i in 0..3 {
+            sender.send(i).unwrap();
+        }
+        assert_eq!(receiver.len(), 2);
+        assert!(!receiver.is_empty());
+        assert_eq!(receiver.recv(), Err(broadcast::BoundedRecvErr::Lagged(1)));
+        assert_eq!(receiver.len(), 2);
+        receiver.recv().unwrap();
+        receiver.recv().unwrap();
+        assert_eq!(receiver.len(), 0);
+        assert!(receiver.is_empty());
+    }
+}
@@ -10,6 +10,27 @@ pub mod spmc;
 /// A lock-free Multi-Producer-Multi-Consumer (MPMC) FIFO channel.
 pub mod mpmc;
 
+/// A lock-free broadcast channel, where every receiver sees every message.
+pub mod broadcast;
+
+/// A lock-free channel for a single message.
+pub mod oneshot;
+
+/// Waiting on several receivers at once, possibly of different flavours.
+pub mod select;
+
+/// Asynchronous adapters for the channels: receivers as
+/// [`Stream`](futures_core::Stream)s and bounded senders as
+/// [`Sink`](futures_sink::Sink)s. Requires the `async` feature.
+#[cfg(feature = "async")]
+pub mod r#async;
+
+mod ring;
+
+mod shared;
+
+mod signal;
+
 /// The error of `Sender::send` operation. Occurs if all receivers were
 /// disconnected.
 #[derive(Debug, Clone, Copy)]
@@ -18,6 +39,35 @@ pub struct NoRecv<T> {
     pub message: T,
 }
 
+/// The error of `send` operation of bounded channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendErr<T> {
+    /// Returned when the channel is full. The message was not sent.
+    Full(T),
+    /// Returned when all receivers were disconnected.
+    NoRecv(T),
+}
+
+impl<T> SendErr<T> {
+    /// Returns the message which was attempted to be sent.
+    pub fn into_inner(self) -> T {
+        match self {
+            SendErr::Full(message) | SendErr::NoRecv(message) => message,
+        }
+    }
+
+    /// Tests if the error was caused by a full channel.
+    pub fn is_full(&self) -> bool {
+        matches!(self, SendErr::Full(_))
+    }
+}
+
+impl<T> From<NoRecv<T>> for SendErr<T> {
+    fn from(err: NoRecv<T>) -> Self {
+        SendErr::NoRecv(err.message)
+    }
+}
+
 /// The error of `Receiver::recv` operation.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RecvErr {
@@ -27,3 +77,102 @@ pub enum RecvErr {
     /// Returned when all senders were disconnected.
     NoSender,
 }
+
+/// A receiver of any channel flavour of this crate. It allows different kinds
+/// of receivers to be handled uniformly, e.g. by [`select::Select`]. This trait
+/// is sealed: it cannot be implemented outside of this crate.
+pub trait Receive: sealed::Receiver {}
+
+impl<T> Receive for spsc::Receiver<T> {}
+impl<T> Receive for mpsc::Receiver<T> {}
+impl<T> Receive for spmc::Receiver<T> {}
+impl<T> Receive for mpmc::Receiver<T> {}
+impl<T> Receive for mpmc::BoundedReceiver<T> {}
+impl<T> Receive for broadcast::Receiver<T> where T: Clone {}
+
+mod sealed {
+    use super::{broadcast, mpmc, mpsc, signal::Signal, spmc, spsc, RecvErr};
+
+    pub trait Receiver {
+        type Message;
+
+        fn try_recv(&mut self) -> Result<Self::Message, RecvErr>;
+
+        fn signal(&self) -> &Signal;
+    }
+
+    impl<T> Receiver for spsc::Receiver<T> {
+        type Message = T;
+
+        fn try_recv(&mut self) -> Result<T, RecvErr> {
+            self.recv()
+        }
+
+        fn signal(&self) -> &Signal {
+            self.signal()
+        }
+    }
+
+    impl<T> Receiver for mpsc::Receiver<T> {
+        type Message = T;
+
+        fn try_recv(&mut self) -> Result<T, RecvErr> {
+            self.recv()
+        }
+
+        fn signal(&self) -> &Signal {
+            self.signal()
+        }
+    }
+
+    impl<T> Receiver for spmc::Receiver<T> {
+        type Message = T;
+
+        fn try_recv(&mut self) -> Result<T, RecvErr> {
+            self.recv()
+        }
+
+        fn signal(&self) -> &Signal {
+            self.signal()
+        }
+    }
+
+    impl<T> Receiver for mpmc::Receiver<T> {
+        type Message = T;
+
+        fn try_recv(&mut self) -> Result<T, RecvErr> {
+            self.recv()
+        }
+
+        fn signal(&self) -> &Signal {
+            self.signal()
+        }
+    }
+
+    impl<T> Receiver for mpmc::BoundedReceiver<T> {
+        type Message = T;
+
+        fn try_recv(&mut self) -> Result<T, RecvErr> {
+            self.recv()
+        }
+
+        fn signal(&self) -> &Signal {
+            self.signal()
+        }
+    }
+
+    impl<T> Receiver for broadcast::Receiver<T>
+    where
+        T: Clone,
+    {
+        type Message = T;
+
+        fn try_recv(&mut self) -> Result<T, RecvErr> {
+            self.recv()
+        }
+
+        fn signal(&self) -> &Signal {
+            self.signal()
+        }
+    }
+}
@@ -1,6 +1,10 @@
+use super::ring::Ring;
+use super::shared::Shared;
+use super::signal::{deadline_of, recv_until, Signal};
 pub use super::{
     NoRecv,
     RecvErr::{self, *},
+    SendErr,
 };
 use crate::owned_alloc::OwnedAlloc;
 use crate::{
@@ -8,12 +12,13 @@ use crate::{
     ptr::{bypass_null, check_null_align},
     removable::Removable,
 };
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 use core::{
     fmt,
     ptr::{null_mut, NonNull},
-    sync::atomic::{AtomicPtr, Ordering::*},
+    sync::atomic::{AtomicPtr, AtomicUsize, Ordering::*},
 };
+use std::time::{Duration, Instant};
 
 /// Creates an asynchronous lock-free Multi-Producer-Multi-Consumer (MPMC)
 /// channel. In order to allow multiple producers and multiple receivers,
@@ -40,10 +45,14 @@ pub fn with_incin<T>(incin: SharedIncin<T>) -> (Sender<T>, Receiver<T>) {
     };
     let alloc = OwnedAlloc::new(shared);
     let back = alloc.into_raw();
+    let shared = Arc::new(Shared::new());
 
     // Put the shared back in the sender.
     let sender = Sender {
-        inner: Arc::new(SenderInner { back }),
+        inner: Arc::new(SenderInner {
+            back,
+            shared: shared.clone(),
+        }),
     };
 
     // And put the shared back and the single node (again) as front in the
@@ -53,6 +62,7 @@ pub fn with_incin<T>(incin: SharedIncin<T>) -> (Sender<T>, Receiver<T>) {
             front: AtomicPtr::new(single_node.as_ptr()),
             back,
             incin,
+            shared,
         }),
     };
 
@@ -74,6 +84,7 @@ impl<T> Sender<T> {
             next: AtomicPtr::new(null_mut()),
         });
         let node = alloc.into_raw();
+        self.inner.shared.len.add(1);
 
         // Then we load the back pointer so we can check if the receivers
         // disconnected. This dereferral is safe because we only deallocate
@@ -88,6 +99,7 @@ impl<T> Sender<T> {
                 // without sharing it.
                 let mut alloc = unsafe { OwnedAlloc::from_raw(node) };
                 let message = alloc.message.replace(None).unwrap();
+                self.inner.shared.len.sub(1);
                 break Err(NoRecv { message });
             }
 
@@ -139,6 +151,84 @@ impl<T> Sender<T> {
                         }
                     }
 
+                    self.inner.shared.signal.notify_one();
+                    break Ok(());
+                }
+
+                Err(new) => loaded = new,
+            }
+        }
+    }
+
+    /// Sends all messages of the given iterator as a single batch. The nodes
+    /// are linked beforehand and spliced in with a single update of the back,
+    /// so the messages of a batch are never interleaved with messages from
+    /// other senders. If the receivers disconnected, the messages are given
+    /// back in the error.
+    pub fn send_iter<I>(&self, messages: I) -> Result<(), NoRecv<Vec<T>>>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let (first, last, count) = match make_chain(messages) {
+            Some(chain) => chain,
+            None => return Ok(()),
+        };
+        self.inner.shared.len.add(count);
+
+        // This dereferral is safe because we only deallocate the shared back
+        // when both sides disconnect.
+        let mut loaded = unsafe { self.inner.back.as_ref().ptr.load(Relaxed) };
+
+        loop {
+            // When the receiver disconnect, it will bit-mark the back.
+            if loaded as usize & 1 == 1 {
+                // Safe because we did not share the chain.
+                let message = unsafe { take_chain(first) };
+                self.inner.shared.len.sub(count);
+                break Err(NoRecv { message });
+            }
+
+            // This dereferral is safe because we only deallocate the shared
+            // back when both sides disconnect. The last node of the chain
+            // becomes the back.
+            let res = unsafe {
+                self.inner.back.as_ref().ptr.compare_exchange(
+                    loaded,
+                    last.as_ptr(),
+                    AcqRel,
+                    Relaxed,
+                )
+            };
+
+            match res {
+                Ok(_) => {
+                    // Bypassing null check is safe because we never store null
+                    // in the back.
+                    let prev = unsafe { bypass_null(loaded) };
+                    // This is safe for the same reasons as in `send`. We link
+                    // the previous back to the first node of the chain.
+                    let res = unsafe {
+                        prev.as_ref().next.compare_exchange(
+                            null_mut(),
+                            first.as_ptr(),
+                            Release,
+                            Relaxed,
+                        )
+                    };
+
+                    if res.is_err() {
+                        // Then it is (null | 1). Just like in `send`, the
+                        // previous back and the whole chain need to be
+                        // deleted. Safe because we are the only ones which can
+                        // access them.
+                        unsafe {
+                            OwnedAlloc::from_raw(prev);
+                            delete_before_last(first, None);
+                        }
+                    }
+
+                    // There might be many messages for many receivers.
+                    self.inner.shared.signal.notify_all();
                     break Ok(());
                 }
 
@@ -147,6 +237,14 @@ impl<T> Sender<T> {
         }
     }
 
+    /// The approximate number of messages sent but not yet received. Just
+    /// like [`Receiver::len`], this is only a hint. Requires the `len`
+    /// feature.
+    #[cfg(feature = "len")]
+    pub fn pending(&self) -> usize {
+        self.inner.shared.len.get()
+    }
+
     /// Tests if there are any [`Receiver`]s still connected. There are no
     /// guarantees that [`send`](Sender::send) will succeed if this method
     /// returns `true` because the [`Receiver`] may disconnect meanwhile.
@@ -206,6 +304,7 @@ impl<T> Receiver<T> {
             // delete nodes via incinerator.
             match unsafe { front_nnptr.as_ref().message.take(AcqRel) } {
                 Some(val) => {
+                    self.inner.shared.len.sub(1);
                     // Safe to call because we passed a pointer from the front
                     // which was loaded during the very same pause we are
                     // passing.
@@ -222,6 +321,71 @@ impl<T> Receiver<T> {
         }
     }
 
+    /// Receives a message, blocking the current thread while the channel is
+    /// empty. If the senders disconnected and the channel is empty,
+    /// [`Err`]`(`[`RecvErr::NoSender`]`)` is returned. The thread is only
+    /// parked when there are no messages, and sending stays lock-free.
+    pub fn recv_blocking(&self) -> Result<T, RecvErr> {
+        self.recv_until(None)
+    }
+
+    /// Same as [`recv_blocking`](Self::recv_blocking), but gives up after the
+    /// given timeout, returning [`Err`]`(`[`RecvErr::NoMessage`]`)`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvErr> {
+        self.recv_until(deadline_of(timeout))
+    }
+
+    /// Same as [`recv_blocking`](Self::recv_blocking), but gives up when the
+    /// given deadline is reached, returning
+    /// [`Err`]`(`[`RecvErr::NoMessage`]`)`.
+    pub fn recv_deadline(&self, deadline: Instant) -> Result<T, RecvErr> {
+        self.recv_until(Some(deadline))
+    }
+
+    pub(super) fn signal(&self) -> &Signal {
+        &self.inner.shared.signal
+    }
+
+    fn recv_until(&self, deadline: Option<Instant>) -> Result<T, RecvErr> {
+        recv_until(&self.inner.shared.signal, deadline, || self.recv())
+    }
+
+    /// The approximate number of messages in the channel. Concurrent sends
+    /// and receives may be partially observed, so this is only a hint.
+    /// Requires the `len` feature.
+    #[cfg(feature = "len")]
+    pub fn len(&self) -> usize {
+        self.inner.shared.len.get()
+    }
+
+    /// Tests if there are no messages in the channel. Unlike `len`, this is
+    /// exact at the moment it checks the front of the channel.
+    pub fn is_empty(&self) -> bool {
+        let pause = self.inner.incin.get_unchecked().pause();
+        let mut ptr = self.inner.front.load(Acquire);
+
+        while let Some(nnptr) = NonNull::new((ptr as usize & !1) as *mut Node<T>) {
+            // This is safe because we paused the incinerator and only delete
+            // nodes via incinerator. Nodes reachable from the front are not
+            // deleted during the pause.
+            let node = unsafe { nnptr.as_ref() };
+            if node.message.is_present(Acquire) {
+                return false;
+            }
+            ptr = node.next.load(Acquire);
+        }
+
+        pause.resume();
+        true
+    }
+
+    /// Creates an iterator over the messages currently available. The
+    /// iterator does not block: it stops as soon as the channel is empty or
+    /// disconnected.
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter { receiver: self }
+    }
+
     /// Tests if there are any [`Sender`]s still connected. There are no
     /// guarantees that [`recv`](Receiver::recv) will succeed if this method
     /// returns `true` because the [`Receiver`] may disconnect meanwhile.
@@ -305,12 +469,294 @@ impl<T> fmt::Debug for Receiver<T> {
     }
 }
 
+/// An iterator based on [`recv`](Receiver::recv) operation of the
+/// [`Receiver`]. Created by [`Receiver::try_iter`].
+pub struct TryIter<'rx, T>
+where
+    T: 'rx,
+{
+    receiver: &'rx Receiver<T>,
+}
+
+impl<'rx, T> Iterator for TryIter<'rx, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl<'rx, T> fmt::Debug for TryIter<'rx, T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "TryIter {{ receiver: {:?} }}", self.receiver)
+    }
+}
+
+/// Creates a bounded lock-free Multi-Producer-Multi-Consumer (MPMC) channel
+/// which holds at most `cap` messages. Messages are written into a ring buffer
+/// allocated upfront, so no allocation happens when sending. If the channel is
+/// full, [`BoundedSender::send`] fails with [`SendErr::Full`]. Just like the
+/// unbounded channel, [`BoundedSender`] and [`BoundedReceiver`] are clonable and
+/// do not require mutability.
+///
+/// # Panics
+/// Panics if `cap` is zero.
+pub fn bounded<T>(cap: usize) -> (BoundedSender<T>, BoundedReceiver<T>) {
+    let inner = Arc::new(BoundedInner {
+        ring: Ring::new(cap),
+        senders: AtomicUsize::new(1),
+        receivers: AtomicUsize::new(1),
+        not_empty: Signal::new(),
+        not_full: Signal::new(),
+    });
+
+    let sender = BoundedSender {
+        inner: inner.clone(),
+    };
+    let receiver = BoundedReceiver { inner };
+    (sender, receiver)
+}
+
+/// The [`BoundedSender`] handle of a bounded MPMC channel. Created by
+/// [`bounded`] function. It is clonable and does not require mutability.
+pub struct BoundedSender<T> {
+    inner: Arc<BoundedInner<T>>,
+}
+
+impl<T> BoundedSender<T> {
+    /// Sends a message. If the channel is full,
+    /// [`Err`]`(`[`SendErr::Full`]`)` is returned. If all receivers
+    /// disconnected, [`Err`]`(`[`SendErr::NoRecv`]`)` is returned.
+    pub fn send(&self, message: T) -> Result<(), SendErr<T>> {
+        if self.inner.receivers.load(Acquire) == 0 {
+            return Err(SendErr::NoRecv(message));
+        }
+
+        self.inner.ring.push(message).map_err(SendErr::Full)?;
+        self.inner.not_empty.notify_one();
+        Ok(())
+    }
+
+    /// Sends a message, blocking the current thread while the channel is full.
+    /// If all receivers disconnected, [`Err`]`(`[`SendErr::NoRecv`]`)` is
+    /// returned. The thread is only parked when the channel is full.
+    pub fn send_blocking(&self, message: T) -> Result<(), SendErr<T>> {
+        self.send_until(message, None)
+    }
+
+    /// Same as [`send_blocking`](Self::send_blocking), but gives up after the
+    /// given timeout, returning [`Err`]`(`[`SendErr::Full`]`)`.
+    pub fn send_timeout(&self, message: T, timeout: Duration) -> Result<(), SendErr<T>> {
+        self.send_until(message, deadline_of(timeout))
+    }
+
+    /// Same as [`send_blocking`](Self::send_blocking), but gives up when the
+    /// given deadline is reached, returning [`Err`]`(`[`SendErr::Full`]`)`.
+    pub fn send_deadline(&self, message: T, deadline: Instant) -> Result<(), SendErr<T>> {
+        self.send_until(message, Some(deadline))
+    }
+
+    #[cfg(feature = "async")]
+    pub(super) fn not_full(&self) -> &Signal {
+        &self.inner.not_full
+    }
+
+    fn send_until(&self, message: T, deadline: Option<Instant>) -> Result<(), SendErr<T>> {
+        let mut pending = Some(message);
+        let res = self.inner.not_full.wait_until(deadline, || {
+            // The message is only taken out in this closure, and it is put
+            // back whenever this closure returns `None`.
+            let message = pending.take()?;
+            match self.send(message) {
+                Err(SendErr::Full(message)) => {
+                    pending = Some(message);
+                    None
+                }
+                res => Some(res),
+            }
+        });
+
+        match (res, pending) {
+            (Some(res), _) => res,
+            (None, Some(message)) => Err(SendErr::Full(message)),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    /// The number of messages sent but not yet received. Unlike the unbounded
+    /// channels, this is always available, since it is read from the positions
+    /// of the ring buffer.
+    pub fn pending(&self) -> usize {
+        self.inner.ring.len()
+    }
+
+    /// The maximum number of messages the channel holds.
+    pub fn capacity(&self) -> usize {
+        self.inner.ring.cap()
+    }
+
+    /// Tests if there are any [`BoundedReceiver`]s still connected. There are
+    /// no guarantees that [`send`](BoundedSender::send) will succeed if this
+    /// method returns `true` because the receivers may disconnect meanwhile.
+    pub fn is_connected(&self) -> bool {
+        self.inner.receivers.load(Relaxed) > 0
+    }
+}
+
+impl<T> Clone for BoundedSender<T> {
+    fn clone(&self) -> Self {
+        self.inner.senders.fetch_add(1, Relaxed);
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Drop for BoundedSender<T> {
+    fn drop(&mut self) {
+        // Release, so the receivers see every message sent before they see
+        // the disconnection.
+        if self.inner.senders.fetch_sub(1, Release) == 1 {
+            // Blocked receivers must see we disconnected.
+            self.inner.not_empty.notify_all();
+        }
+    }
+}
+
+impl<T> fmt::Debug for BoundedSender<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "mpmc::BoundedSender {{ ptr: {:p} }}", self.inner)
+    }
+}
+
+/// The [`BoundedReceiver`] handle of a bounded MPMC channel. Created by
+/// [`bounded`] function. It is clonable and does not require mutability.
+pub struct BoundedReceiver<T> {
+    inner: Arc<BoundedInner<T>>,
+}
+
+impl<T> BoundedReceiver<T> {
+    /// Tries to receive a message. If no message is available,
+    /// [`Err`]`(`[`RecvErr::NoMessage`]`)` is returned. If the senders
+    /// disconnected and the channel is empty,
+    /// [`Err`]`(`[`RecvErr::NoSender`]`)` is returned.
+    pub fn recv(&self) -> Result<T, RecvErr> {
+        // The senders must be checked before the ring. Otherwise, a message
+        // sent right before the last sender disconnected could be missed.
+        let connected = self.inner.senders.load(Acquire) > 0;
+
+        match self.inner.ring.pop() {
+            Some(message) => {
+                self.inner.not_full.notify_one();
+                Ok(message)
+            }
+            None if connected => Err(RecvErr::NoMessage),
+            None => Err(RecvErr::NoSender),
+        }
+    }
+
+    /// Receives a message, blocking the current thread while the channel is
+    /// empty. If the senders disconnected and the channel is empty,
+    /// [`Err`]`(`[`RecvErr::NoSender`]`)` is returned. The thread is only
+    /// parked when there are no messages, and sending stays lock-free.
+    pub fn recv_blocking(&self) -> Result<T, RecvErr> {
+        self.recv_until(None)
+    }
+
+    /// Same as [`recv_blocking`](Self::recv_blocking), but gives up after the
+    /// given timeout, returning [`Err`]`(`[`RecvErr::NoMessage`]`)`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvErr> {
+        self.recv_until(deadline_of(timeout))
+    }
+
+    /// Same as [`recv_blocking`](Self::recv_blocking), but gives up when the
+    /// given deadline is reached, returning
+    /// [`Err`]`(`[`RecvErr::NoMessage`]`)`.
+    pub fn recv_deadline(&self, deadline: Instant) -> Result<T, RecvErr> {
+        self.recv_until(Some(deadline))
+    }
+
+    pub(super) fn signal(&self) -> &Signal {
+        &self.inner.not_empty
+    }
+
+    fn recv_until(&self, deadline: Option<Instant>) -> Result<T, RecvErr> {
+        recv_until(&self.inner.not_empty, deadline, || self.recv())
+    }
+
+    /// The number of messages in the channel. Concurrent sends and receives
+    /// may change it right away, but it never exceeds the capacity.
+    pub fn len(&self) -> usize {
+        self.inner.ring.len()
+    }
+
+    /// Tests if there are no messages in the channel.
+    pub fn is_empty(&self) -> bool {
+        self.inner.ring.is_empty()
+    }
+
+    /// The maximum number of messages the channel holds.
+    pub fn capacity(&self) -> usize {
+        self.inner.ring.cap()
+    }
+
+    /// Tests if there are any [`BoundedSender`]s still connected. There are no
+    /// guarantees that [`recv`](BoundedReceiver::recv) will succeed if this
+    /// method returns `true` because the senders may disconnect meanwhile.
+    /// This method may also return `true` if the senders disconnected but
+    /// there are messages pending in the buffer.
+    pub fn is_connected(&self) -> bool {
+        self.inner.senders.load(Acquire) > 0 || !self.inner.ring.is_empty()
+    }
+}
+
+impl<T> Clone for BoundedReceiver<T> {
+    fn clone(&self) -> Self {
+        self.inner.receivers.fetch_add(1, Relaxed);
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Drop for BoundedReceiver<T> {
+    fn drop(&mut self) {
+        if self.inner.receivers.fetch_sub(1, Release) == 1 {
+            // Blocked senders must see we disconnected.
+            self.inner.not_full.notify_all();
+        }
+    }
+}
+
+impl<T> fmt::Debug for BoundedReceiver<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "mpmc::BoundedReceiver {{ ptr: {:p} }}", self.inner)
+    }
+}
+
+struct BoundedInner<T> {
+    ring: Ring<T>,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+    not_empty: Signal,
+    not_full: Signal,
+}
+
 struct SenderInner<T> {
     back: NonNull<SharedBack<T>>,
+    shared: Arc<Shared>,
 }
 
 impl<T> Drop for SenderInner<T> {
     fn drop(&mut self) {
+        self.disconnect();
+        // Blocked receivers must see we disconnected.
+        self.shared.signal.notify_all();
+    }
+}
+
+impl<T> SenderInner<T> {
+    fn disconnect(&mut self) {
         // This is safe because we only store nodes allocated via
         // `OwnedAlloc`. Also, the shared back is only deallocated when both
         // sides disconnected.
@@ -358,6 +804,7 @@ struct ReceiverInner<T> {
     front: AtomicPtr<Node<T>>,
     back: NonNull<SharedBack<T>>,
     incin: SharedIncin<T>,
+    shared: Arc<Shared>,
 }
 
 impl<T> ReceiverInner<T> {
@@ -449,6 +896,56 @@ struct Node<T> {
     next: AtomicPtr<Node<T>>,
 }
 
+// The first and the last nodes of a chain of nodes, and how many nodes it has.
+type Chain<T> = (NonNull<Node<T>>, NonNull<Node<T>>, usize);
+
+// Links the messages into a chain of fresh nodes, or returns `None` if there
+// are no messages.
+fn make_chain<I, T>(messages: I) -> Option<Chain<T>>
+where
+    I: IntoIterator<Item = T>,
+{
+    let mut messages = messages.into_iter();
+    let first = OwnedAlloc::new(Node {
+        message: Removable::new(messages.next()?),
+        next: AtomicPtr::new(null_mut()),
+    })
+    .into_raw();
+
+    let mut last = first;
+    let mut count = 1;
+    for message in messages {
+        let node = OwnedAlloc::new(Node {
+            message: Removable::new(message),
+            next: AtomicPtr::new(null_mut()),
+        })
+        .into_raw();
+        // This is safe because we only link nodes we just allocated, and the
+        // chain is not shared yet.
+        unsafe { last.as_ref().next.store(node.as_ptr(), Relaxed) };
+        last = node;
+        count += 1;
+    }
+
+    Some((first, last, count))
+}
+
+// This function is unsafe because the chain must have been created by
+// `make_chain` and must have never been shared. Takes the messages out of the
+// chain and deallocates its nodes.
+unsafe fn take_chain<T>(first: NonNull<Node<T>>) -> Vec<T> {
+    let mut messages = Vec::new();
+    let mut node_ptr = Some(first);
+
+    while let Some(node) = node_ptr {
+        let mut alloc = OwnedAlloc::from_raw(node);
+        node_ptr = NonNull::new(*alloc.next.get_mut());
+        messages.extend(alloc.message.replace(None));
+    }
+
+    messages
+}
+
 make_shared_incin! {
     { "`mpmc::Receiver`" }
     pub SharedIncin<T> of OwnedAlloc<Node<T>>
@@ -540,4 +1037,261 @@ mod test {
             assert!(status.load(Relaxed));
         }
     }
+
+    #[test]
+    fn bounded_full_and_disconnect() {
+        let (sender, receiver) = mpmc::bounded::<usize>(3);
+        for i in 0..3 {
+            sender.send(i).unwrap();
+        }
+        assert_eq!(sender.send(3), Err(mpmc::SendErr::Full(3)));
+        assert_eq!(receiver.recv(), Ok(0));
+        sender.send(3).unwrap();
+
+        drop(sender);
+        assert!(receiver.is_connected());
+        for i in 1..4 {
+            assert_eq!(receiver.recv(), Ok(i));
+        }
+        assert_eq!(receiver.recv(), Err(mpmc::NoSender));
+
+        let (sender, receiver) = mpmc::bounded::<usize>(1);
+        drop(receiver);
+        assert_eq!(sender.send(0), Err(mpmc::SendErr::NoRecv(0)));
+    }
+
+    #[test]
+    fn bounded_drops_pending() {
+        let counter = Arc::new(());
+        let (sender, receiver) = mpmc::bounded(8);
+        for _ in 0..5 {
+            sender.send(counter.clone()).unwrap();
+        }
+        receiver.recv().unwrap();
+        drop(sender);
+        drop(receiver);
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn blocking_correct_numbers() {
+        const THREADS: usize = 8;
+        const MSGS_PER_THREAD: usize = 64;
+        const MSGS: usize = THREADS * MSGS_PER_THREAD;
+
+        let mut done = Vec::with_capacity(MSGS);
+        for _ in 0..MSGS {
+            done.push(AtomicBool::new(false));
+        }
+        let done = Arc::<[AtomicBool]>::from(done);
+        let (sender, receiver) = mpmc::create::<usize>();
+        let mut threads = Vec::with_capacity(THREADS);
+
+        for i in 0..THREADS {
+            let sender = sender.clone();
+            threads.push(thread::spawn(move || {
+                let start = i * MSGS_PER_THREAD;
+                for j in start..start + MSGS_PER_THREAD {
+                    sender.send(j).unwrap();
+                }
+            }));
+
+            let receiver = receiver.clone();
+            let done = done.clone();
+            threads.push(thread::spawn(move || {
+                while let Ok(i) = receiver.recv_blocking() {
+                    assert!(!done[i].swap(true, AcqRel));
+                }
+            }));
+        }
+
+        drop(sender);
+        drop(receiver);
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        for status in done.iter() {
+            assert!(status.load(Relaxed));
+        }
+    }
+
+    #[test]
+    fn bounded_blocking() {
+        use std::time::Duration;
+
+        let (sender, receiver) = mpmc::bounded::<usize>(2);
+        sender.send(0).unwrap();
+        sender.send(1).unwrap();
+        assert_eq!(
+            sender.send_timeout(2, Duration::from_millis(10)),
+            Err(mpmc::SendErr::Full(2))
+        );
+
+        let thread = thread::spawn(move || {
+            for i in 0..64 {
+                assert_eq!(receiver.recv_blocking(), Ok(i));
+            }
+            assert_eq!(receiver.recv_blocking(), Err(mpmc::NoSender));
+        });
+
+        for i in 2..64 {
+            sender.send_blocking(i).unwrap();
+        }
+        drop(sender);
+        thread.join().unwrap();
+
+        let (sender, receiver) = mpmc::bounded::<usize>(1);
+        sender.send(0).unwrap();
+        let thread = thread::spawn(move || sender.send_blocking(1));
+        thread::sleep(Duration::from_millis(10));
+        drop(receiver);
+        assert_eq!(thread.join().unwrap(), Err(mpmc::SendErr::NoRecv(1)));
+    }
+
+    #[test]
+    fn bounded_correct_numbers() {
+        const THREADS: usize = 8;
+        const MSGS_PER_THREAD: usize = 128;
+        const MSGS: usize = THREADS * MSGS_PER_THREAD;
+
+        let mut done = Vec::with_capacity(MSGS);
+        for _ in 0..MSGS {
+            done.push(AtomicBool::new(false));
+        }
+        let done = Arc::<[AtomicBool]>::from(done);
+        let (sender, receiver) = mpmc::bounded::<usize>(5);
+        let mut threads = Vec::with_capacity(THREADS);
+
+        for i in 0..THREADS {
+            let sender = sender.clone();
+            threads.push(thread::spawn(move || {
+                let start = i * MSGS_PER_THREAD;
+                for j in start..start + MSGS_PER_THREAD {
+                    let mut message = j;
+                    loop {
+                        match sender.send(message) {
+                            Ok(()) => break,
+                            Err(mpmc::SendErr::Full(back)) => {
+                                message = back;
+                                thread::yield_now();
+                            }
+                            Err(mpmc::SendErr::NoRecv(_)) => unreachable!(),
+                        }
+                    }
+                }
+            }));
+
+            let receiver = receiver.clone();
+            let done = done.clone();
+            threads.push(thread::spawn(move || loop {
+                match receiver.recv() {
+                    Ok(i) => assert!(!done[i].swap(true, AcqRel)),
+
+                    Err(mpmc::NoMessage) => (),
+
+                    Err(mpmc::NoSender) => break,
+                }
+            }));
+        }
+
+        drop(sender);
+        drop(receiver);
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        for status in done.iter() {
+            assert!(status.load(Relaxed));
+        }
+    }
+
+    #[test]
+    fn batch_correct_numbers() {
+        const THREADS: usize = 4;
+        const BATCHES: usize = 16;
+        const BATCH: usize = 8;
+        const MSGS: usize = THREADS * BATCHES * BATCH;
+
+        let mut done = Vec::with_capacity(MSGS);
+        for _ in 0..MSGS {
+            done.push(AtomicBool::new(false));
+        }
+        let done = Arc::<[AtomicBool]>::from(done);
+        let (sender, receiver) = mpmc::create::<usize>();
+        let mut threads = Vec::with_capacity(THREADS * 2);
+
+        for i in 0..THREADS {
+            let sender = sender.clone();
+            threads.push(thread::spawn(move || {
+                for j in 0..BATCHES {
+                    let start = (i * BATCHES + j) * BATCH;
+                    sender.send_iter(start..start + BATCH).unwrap();
+                }
+            }));
+
+            let receiver = receiver.clone();
+            let done = done.clone();
+            threads.push(thread::spawn(move || {
+                while let Ok(i) = receiver.recv_blocking() {
+                    assert!(!done[i].swap(true, AcqRel));
+                    for i in receiver.try_iter() {
+                        assert!(!done[i].swap(true, AcqRel));
+                    }
+                }
+            }));
+        }
+
+        drop(sender);
+        drop(receiver);
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        for status in done.iter() {
+            assert!(status.load(Relaxed));
+        }
+    }
+
+    #[test]
+    fn batch_no_receiver() {
+        let (sender, receiver) = mpmc::create::<usize>();
+        drop(receiver);
+        let err = sender.send_iter(0..4).unwrap_err();
+        assert_eq!(err.message, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn len_and_is_empty() {
+        let (sender, receiver) = mpmc::create::<usize>();
+        assert!(receiver.is_empty());
+        sender.send(0).unwrap();
+        sender.send_iter(1..3).unwrap();
+        #[cfg(feature = "len")]
+        assert_eq!(receiver.len(), 3);
+        assert_eq!(receiver.recv(), Ok(0));
+        assert!(!receiver.is_empty());
+        #[cfg(feature = "len")]
+        assert_eq!(sender.pending(), 2);
+        assert_eq!(receiver.try_iter().count(), 2);
+        assert!(receiver.is_empty());
+
+        let (sender, receiver) = mpmc::bounded::<usize>(3);
+        assert_eq!(receiver.capacity(), 3);
+        assert!(receiver.is_empty());
+        for i in 0..3 {
+            sender.send(i).unwrap();
+        }
+        assert_eq!(receiver.len(), 3);
+        assert_eq!(sender.pending(), 3);
+        receiver.recv().unwrap();
+        sender.send(3).unwrap();
+        assert_eq!(receiver.len(), 3);
+        while receiver.recv().is_ok() {}
+        assert_eq!(sender.pending(), 0);
+        assert!(receiver.is_empty());
+    }
 }
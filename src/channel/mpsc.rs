@@ -1,15 +1,18 @@
+use super::shared::Shared;
+use super::signal::{deadline_of, recv_until, Signal};
 pub use super::{
     NoRecv,
     RecvErr::{self, *},
 };
 use crate::owned_alloc::OwnedAlloc;
 use crate::ptr::{bypass_null, check_null_align};
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 use core::{
     fmt,
     ptr::{null_mut, NonNull},
     sync::atomic::{AtomicPtr, Ordering::*},
 };
+use std::time::{Duration, Instant};
 
 /// Creates an asynchronous lock-free Multi-Producer-Single-Consumer (MPSC)
 /// channel. In order to allow multiple producers, [`Sender`] is clonable and
@@ -31,14 +34,19 @@ pub fn create<T>() -> (Sender<T>, Receiver<T>) {
     };
     let alloc = OwnedAlloc::new(shared);
     let back = alloc.into_raw();
+    let shared = Arc::new(Shared::new());
 
     // Sender with an Arc because it is shared.
     let sender = Sender {
-        inner: Arc::new(SenderInner { back }),
+        inner: Arc::new(SenderInner {
+            back,
+            shared: shared.clone(),
+        }),
     };
     let receiver = Receiver {
         back,
         front: single_node,
+        shared,
     };
 
     (sender, receiver)
@@ -59,6 +67,7 @@ impl<T> Sender<T> {
             next: AtomicPtr::new(null_mut()),
         });
         let node = alloc.into_raw();
+        self.inner.shared.len.add(1);
 
         // We first load the back because we need to check it. This is safe
         // because we only store nodes allocated via `OwnedAlloc`. Also, the
@@ -73,6 +82,7 @@ impl<T> Sender<T> {
                 // node.
                 let mut alloc = unsafe { OwnedAlloc::from_raw(node) };
                 let message = alloc.message.take().unwrap();
+                self.inner.shared.len.sub(1);
                 break Err(NoRecv { message });
             }
 
@@ -126,6 +136,7 @@ impl<T> Sender<T> {
                         }
                     }
 
+                    self.inner.shared.signal.notify_one();
                     break Ok(());
                 }
 
@@ -134,6 +145,85 @@ impl<T> Sender<T> {
         }
     }
 
+    /// Sends all messages of the given iterator as a single batch. The nodes
+    /// are linked beforehand and spliced in with a single update of the back,
+    /// so the messages of a batch are never interleaved with messages from
+    /// other senders. If the receiver disconnected, the messages are given
+    /// back in the error.
+    pub fn send_iter<I>(&self, messages: I) -> Result<(), NoRecv<Vec<T>>>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let (first, last, count) = match make_chain(messages) {
+            Some(chain) => chain,
+            None => return Ok(()),
+        };
+        self.inner.shared.len.add(count);
+
+        // This is safe because we only store nodes allocated via
+        // `OwnedAlloc`. Also, the shared back is only deallocated when both
+        // sides disconnected.
+        let mut loaded = unsafe { self.inner.back.as_ref().ptr.load(Relaxed) };
+
+        loop {
+            // If the lower bit is marked, it means the receiver disconnected.
+            if loaded as usize & 1 == 1 {
+                // This is safe because we did not share the chain.
+                let message = unsafe { take_chain(first) };
+                self.inner.shared.len.sub(count);
+                break Err(NoRecv { message });
+            }
+
+            // This is safe because we only store nodes allocated via
+            // `OwnedAlloc`. Also, the node is only deallocated when both sides
+            // disconnected. The last node of the chain becomes the back.
+            let res = unsafe {
+                self.inner.back.as_ref().ptr.compare_exchange(
+                    loaded,
+                    last.as_ptr(),
+                    AcqRel,
+                    Relaxed,
+                )
+            };
+
+            match res {
+                Ok(_) => {
+                    // This is safe because we never store null on the back.
+                    let prev = unsafe { bypass_null(loaded) };
+                    // This is safe for the same reasons as in `send`. We link
+                    // the previous back to the first node of the chain.
+                    let res = unsafe { prev.as_ref().next.swap(first.as_ptr(), Release) };
+
+                    // The receiver disconnected, so, just like in `send`, the
+                    // previous back and the whole chain (except for its last
+                    // node) need to be deleted.
+                    if !res.is_null() {
+                        // This is safe because the receiver will not access
+                        // these nodes anymore, and the chain was never seen by
+                        // other senders.
+                        unsafe {
+                            OwnedAlloc::from_raw(prev);
+                            delete_before_last(first, None);
+                        }
+                    }
+
+                    self.inner.shared.signal.notify_one();
+                    break Ok(());
+                }
+
+                Err(new) => loaded = new,
+            }
+        }
+    }
+
+    /// The approximate number of messages sent but not yet received. Just
+    /// like [`Receiver::len`], this is only a hint. Requires the `len`
+    /// feature.
+    #[cfg(feature = "len")]
+    pub fn pending(&self) -> usize {
+        self.inner.shared.len.get()
+    }
+
     /// Tests if the [`Receiver`] is still connected. There are no guarantees
     /// that [`send`](Sender::send) will succeed if this method returns `true`
     /// because the [`Receiver`] may disconnect meanwhile.
@@ -168,6 +258,7 @@ impl<T> fmt::Debug for Sender<T> {
 pub struct Receiver<T> {
     back: NonNull<SharedBack<T>>,
     front: NonNull<Node<T>>,
+    shared: Arc<Shared>,
 }
 
 impl<T> Receiver<T> {
@@ -187,6 +278,7 @@ impl<T> Receiver<T> {
             // Then we remove logicaly.
             match node.message.take() {
                 Some(message) => {
+                    self.shared.len.sub(1);
                     // No need to clear the lower bit since the receiver is the
                     // only one that marks next field.
                     if let Some(nnptr) = NonNull::new(next) {
@@ -251,6 +343,65 @@ impl<T> Receiver<T> {
         }
     }
 
+    /// Receives a message, blocking the current thread while the channel is
+    /// empty. If the senders disconnected and the channel is empty,
+    /// [`Err`]`(`[`RecvErr::NoSender`]`)` is returned. The thread is only
+    /// parked when there are no messages, and sending stays lock-free.
+    pub fn recv_blocking(&mut self) -> Result<T, RecvErr> {
+        self.recv_until(None)
+    }
+
+    /// Same as [`recv_blocking`](Self::recv_blocking), but gives up after the
+    /// given timeout, returning [`Err`]`(`[`RecvErr::NoMessage`]`)`.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<T, RecvErr> {
+        self.recv_until(deadline_of(timeout))
+    }
+
+    /// Same as [`recv_blocking`](Self::recv_blocking), but gives up when the
+    /// given deadline is reached, returning
+    /// [`Err`]`(`[`RecvErr::NoMessage`]`)`.
+    pub fn recv_deadline(&mut self, deadline: Instant) -> Result<T, RecvErr> {
+        self.recv_until(Some(deadline))
+    }
+
+    pub(super) fn signal(&self) -> &Signal {
+        &self.shared.signal
+    }
+
+    fn recv_until(&mut self, deadline: Option<Instant>) -> Result<T, RecvErr> {
+        match self.recv() {
+            Err(RecvErr::NoMessage) => (),
+            res => return res,
+        }
+        let shared = self.shared.clone();
+        recv_until(&shared.signal, deadline, || self.recv())
+    }
+
+    /// The approximate number of messages in the channel. Concurrent sends
+    /// and receives may be partially observed, so this is only a hint.
+    /// Requires the `len` feature.
+    #[cfg(feature = "len")]
+    pub fn len(&self) -> usize {
+        self.shared.len.get()
+    }
+
+    /// Tests if there are no messages in the channel. Unlike `len`, this is
+    /// exact at the moment it checks the front of the channel.
+    pub fn is_empty(&self) -> bool {
+        // Safe because we always have at least one node, which is only dropped
+        // in the last side to disconnect's drop. Nodes after the front always
+        // hold a message until we receive it.
+        let front = unsafe { self.front.as_ref() };
+        front.message.is_none() && front.next.load(Acquire) as usize & !1 == 0
+    }
+
+    /// Creates an iterator over the messages currently available. The
+    /// iterator does not block: it stops as soon as the channel is empty or
+    /// disconnected.
+    pub fn try_iter(&mut self) -> TryIter<'_, T> {
+        TryIter { receiver: self }
+    }
+
     /// Tests if there any [`Sender`]s still connected. There are no guarantees
     /// that [`recv`](Receiver::recv) will succeed if this method returns `true`
     /// because the [`Receiver`] may disconnect meanwhile. This method may
@@ -347,12 +498,44 @@ impl<T> fmt::Debug for Receiver<T> {
     }
 }
 
+/// An iterator based on [`recv`](Receiver::recv) operation of the
+/// [`Receiver`]. Created by [`Receiver::try_iter`].
+pub struct TryIter<'rx, T>
+where
+    T: 'rx,
+{
+    receiver: &'rx mut Receiver<T>,
+}
+
+impl<'rx, T> Iterator for TryIter<'rx, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl<'rx, T> fmt::Debug for TryIter<'rx, T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "TryIter {{ receiver: {:?} }}", self.receiver)
+    }
+}
+
 struct SenderInner<T> {
     back: NonNull<SharedBack<T>>,
+    shared: Arc<Shared>,
 }
 
 impl<T> Drop for SenderInner<T> {
     fn drop(&mut self) {
+        self.disconnect();
+        // A blocked receiver must see we disconnected.
+        self.shared.signal.notify_all();
+    }
+}
+
+impl<T> SenderInner<T> {
+    fn disconnect(&mut self) {
         // This is safe because we only store nodes allocated via
         // `OwnedAlloc`. Also, the shared back is only deallocated when both
         // sides disconnected.
@@ -410,6 +593,56 @@ struct Node<T> {
     next: AtomicPtr<Node<T>>,
 }
 
+// The first and the last nodes of a chain of nodes, and how many nodes it has.
+type Chain<T> = (NonNull<Node<T>>, NonNull<Node<T>>, usize);
+
+// Links the messages into a chain of fresh nodes, or returns `None` if there
+// are no messages.
+fn make_chain<I, T>(messages: I) -> Option<Chain<T>>
+where
+    I: IntoIterator<Item = T>,
+{
+    let mut messages = messages.into_iter();
+    let first = OwnedAlloc::new(Node {
+        message: Some(messages.next()?),
+        next: AtomicPtr::new(null_mut()),
+    })
+    .into_raw();
+
+    let mut last = first;
+    let mut count = 1;
+    for message in messages {
+        let node = OwnedAlloc::new(Node {
+            message: Some(message),
+            next: AtomicPtr::new(null_mut()),
+        })
+        .into_raw();
+        // This is safe because we only link nodes we just allocated, and the
+        // chain is not shared yet.
+        unsafe { last.as_ref().next.store(node.as_ptr(), Relaxed) };
+        last = node;
+        count += 1;
+    }
+
+    Some((first, last, count))
+}
+
+// This function is unsafe because the chain must have been created by
+// `make_chain` and must have never been shared. Takes the messages out of the
+// chain and deallocates its nodes.
+unsafe fn take_chain<T>(first: NonNull<Node<T>>) -> Vec<T> {
+    let mut messages = Vec::new();
+    let mut node_ptr = Some(first);
+
+    while let Some(node) = node_ptr {
+        let mut alloc = OwnedAlloc::from_raw(node);
+        node_ptr = NonNull::new(*alloc.next.get_mut());
+        messages.extend(alloc.message.take());
+    }
+
+    messages
+}
+
 // This function is unsafe because passing the wrong pointer may lead to
 // undefined behavior. The pointer `last` needs to be a pointer previously
 // loaded from the back, and must be reachable from `curr` if non-null. Also,
@@ -488,4 +721,91 @@ mod test {
             assert!(*status);
         }
     }
+
+    #[test]
+    fn timeout_and_disconnect() {
+        use std::time::{Duration, Instant};
+
+        let (sender, mut receiver) = mpsc::create::<usize>();
+        let start = Instant::now();
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_millis(20)),
+            Err(mpsc::NoMessage)
+        );
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        let thread = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            sender.send(5).unwrap();
+            thread::sleep(Duration::from_millis(10));
+        });
+
+        assert_eq!(receiver.recv_blocking(), Ok(5));
+        assert_eq!(receiver.recv_blocking(), Err(mpsc::NoSender));
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn batches_not_interleaved() {
+        const THREADS: usize = 4;
+        const BATCHES: usize = 32;
+        const BATCH: usize = 8;
+
+        let (sender, mut receiver) = mpsc::create::<usize>();
+        let mut threads = Vec::with_capacity(THREADS);
+
+        for i in 0..THREADS {
+            let sender = sender.clone();
+            threads.push(thread::spawn(move || {
+                for j in 0..BATCHES {
+                    let start = (i * BATCHES + j) * BATCH;
+                    sender.send_iter(start..start + BATCH).unwrap();
+                }
+            }))
+        }
+
+        drop(sender);
+
+        let mut received = Vec::new();
+        while let Ok(message) = receiver.recv_blocking() {
+            received.push(message);
+            received.extend(receiver.try_iter());
+        }
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(received.len(), THREADS * BATCHES * BATCH);
+        for batch in received.chunks(BATCH) {
+            assert_eq!(batch[0] % BATCH, 0);
+            for (offset, &message) in batch.iter().enumerate() {
+                assert_eq!(message, batch[0] + offset);
+            }
+        }
+    }
+
+    #[test]
+    fn batch_no_receiver() {
+        let (sender, receiver) = mpsc::create::<usize>();
+        drop(receiver);
+        let err = sender.send_iter(0..4).unwrap_err();
+        assert_eq!(err.message, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn len_and_is_empty() {
+        let (sender, mut receiver) = mpsc::create::<usize>();
+        assert!(receiver.is_empty());
+        sender.send(1).unwrap();
+        sender.clone().send_iter(2..5).unwrap();
+        assert!(!receiver.is_empty());
+        #[cfg(feature = "len")]
+        assert_eq!(receiver.len(), 4);
+        assert_eq!(receiver.recv(), Ok(1));
+        #[cfg(feature = "len")]
+        assert_eq!(sender.pending(), 3);
+        assert_eq!(receiver.try_iter().count(), 3);
+        assert!(receiver.is_empty());
+    }
 }
@@ -0,0 +1,364 @@
+use super::signal::deadline_of;
+pub use super::{
+    NoRecv,
+    RecvErr::{self, *},
+};
+use crate::removable::Removable;
+use alloc::sync::Arc;
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering::*},
+};
+use std::{
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+/// Creates a lock-free channel for a single message. Unlike the other
+/// channels, no node is allocated per message: the message is written in
+/// place into the state shared by both ends.
+pub fn create<T>() -> (Sender<T>, Receiver<T>) {
+    let inner = Arc::new(Inner {
+        state: AtomicUsize::new(0),
+        message: UnsafeCell::new(Removable::empty()),
+        waiter: UnsafeCell::new(None),
+    });
+
+    let sender = Sender {
+        inner: inner.clone(),
+    };
+    let receiver = Receiver { inner };
+
+    (sender, receiver)
+}
+
+/// The [`Sender`] handle of a oneshot channel. Created by [`create`]
+/// function. Sending consumes it.
+pub struct Sender<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends the message and if the receiver disconnected, an error is
+    /// returned.
+    pub fn send(self, message: T) -> Result<(), NoRecv<T>> {
+        if self.inner.state.load(Acquire) & RECEIVER_GONE != 0 {
+            return Err(NoRecv { message });
+        }
+
+        // This is safe because we are the only ones writing the message, and
+        // the receiver only reads it after we set the sent bit.
+        unsafe { (*self.inner.message.get()).replace(Some(message)) };
+
+        let prev = self.inner.state.fetch_or(SENT, AcqRel);
+
+        if prev & RECEIVER_GONE != 0 {
+            // The receiver disconnected meanwhile, and it will never read the
+            // message. Safe because nobody else has access to it.
+            let message = unsafe { (*self.inner.message.get()).replace(None) };
+            return Err(NoRecv {
+                message: message.unwrap(),
+            });
+        }
+
+        if prev & WAITING != 0 {
+            // Safe because the receiver registered itself before setting the
+            // waiting bit, and it does not change its registration anymore
+            // once we set the sent bit.
+            unsafe { self.inner.wake() };
+        }
+
+        Ok(())
+    }
+
+    /// Tests if the [`Receiver`] is still connected. There are no guarantees
+    /// that [`send`](Sender::send) will succeed if this method returns `true`
+    /// because the [`Receiver`] may disconnect meanwhile.
+    pub fn is_connected(&self) -> bool {
+        self.inner.state.load(Relaxed) & RECEIVER_GONE == 0
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let prev = self.inner.state.fetch_or(SENDER_GONE, AcqRel);
+
+        // If the message was sent, the receiver was already woken up.
+        if prev & WAITING != 0 && prev & SENT == 0 {
+            // Safe because the receiver registered itself before setting the
+            // waiting bit, and it does not change its registration anymore
+            // once we set the disconnected bit.
+            unsafe { self.inner.wake() };
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "oneshot::Sender {{ ptr: {:p} }}", self.inner)
+    }
+}
+
+unsafe impl<T> Send for Sender<T> where T: Send {}
+unsafe impl<T> Sync for Sender<T> where T: Send {}
+
+/// The [`Receiver`] handle of a oneshot channel. Created by [`create`]
+/// function.
+pub struct Receiver<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Tries to receive the message. If it was not sent yet,
+    /// [`Err`]`(`[`RecvErr::NoMessage`]`)` is returned. If the sender
+    /// disconnected without sending, or the message was already received,
+    /// [`Err`]`(`[`RecvErr::NoSender`]`)` is returned.
+    pub fn try_recv(&mut self) -> Result<T, RecvErr> {
+        let state = self.inner.state.load(Acquire);
+
+        if state & SENT != 0 {
+            // This is safe because the sender does not touch the message
+            // after setting the sent bit while we are connected.
+            let message = unsafe { (*self.inner.message.get()).take(Relaxed) };
+            message.ok_or(NoSender)
+        } else if state & SENDER_GONE != 0 {
+            Err(NoSender)
+        } else {
+            Err(NoMessage)
+        }
+    }
+
+    /// Receives the message, blocking the current thread until it is sent. If
+    /// the sender disconnected without sending, or the message was already
+    /// received, [`Err`]`(`[`RecvErr::NoSender`]`)` is returned.
+    pub fn recv(&mut self) -> Result<T, RecvErr> {
+        self.recv_until(None)
+    }
+
+    /// Same as [`recv`](Self::recv), but gives up after the given timeout,
+    /// returning [`Err`]`(`[`RecvErr::NoMessage`]`)`.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<T, RecvErr> {
+        self.recv_until(deadline_of(timeout))
+    }
+
+    /// Same as [`recv`](Self::recv), but gives up when the given deadline is
+    /// reached, returning [`Err`]`(`[`RecvErr::NoMessage`]`)`.
+    pub fn recv_deadline(&mut self, deadline: Instant) -> Result<T, RecvErr> {
+        self.recv_until(Some(deadline))
+    }
+
+    /// Tests if the [`Sender`] is still connected. There are no guarantees
+    /// that [`try_recv`](Receiver::try_recv) will succeed if this method
+    /// returns `true`. This method also returns `true` if the [`Sender`]
+    /// disconnected but the message is waiting to be received.
+    pub fn is_connected(&self) -> bool {
+        let state = self.inner.state.load(Acquire);
+        if state & SENT != 0 {
+            // This is safe because the sender does not touch the message
+            // after setting the sent bit while we are connected.
+            unsafe { (*self.inner.message.get()).is_present(Relaxed) }
+        } else {
+            state & SENDER_GONE == 0
+        }
+    }
+
+    /// Tests if there is no message waiting to be received. This is exact at
+    /// the moment it checks the state of the channel.
+    pub fn is_empty(&self) -> bool {
+        if self.inner.state.load(Acquire) & SENT != 0 {
+            // This is safe because the sender does not touch the message
+            // after setting the sent bit while we are connected.
+            unsafe { !(*self.inner.message.get()).is_present(Relaxed) }
+        } else {
+            true
+        }
+    }
+
+    fn recv_until(&mut self, deadline: Option<Instant>) -> Result<T, RecvErr> {
+        loop {
+            match self.try_recv() {
+                Err(NoMessage) => (),
+                res => break res,
+            }
+
+            if !self.register() {
+                // The sender finished meanwhile.
+                continue;
+            }
+
+            match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        self.unregister();
+                        break self.try_recv();
+                    }
+                    thread::park_timeout(deadline - now);
+                }
+
+                None => thread::park(),
+            }
+        }
+    }
+
+    // Makes sure the sender will wake the current thread up. Returns `false`
+    // if the sender already sent or disconnected, and will not wake anyone.
+    fn register(&mut self) -> bool {
+        let state = self.inner.state.load(Acquire);
+        if state & WAITING != 0 {
+            // We registered ourselves earlier in this very wait. Otherwise, we
+            // would have unregistered, or the sender would have finished.
+            return true;
+        }
+
+        // This is safe because the sender only reads the registration after
+        // seeing the waiting bit, which is not set.
+        unsafe { *self.inner.waiter.get() = Some(thread::current()) };
+        let prev = self.inner.state.fetch_or(WAITING, AcqRel);
+        prev & (SENT | SENDER_GONE) == 0
+    }
+
+    // Gives up waiting, so the receiver can register itself again later,
+    // possibly from another thread.
+    fn unregister(&mut self) {
+        let mut state = self.inner.state.load(Acquire);
+
+        // If the sender finished, it might be reading our registration. But
+        // then the channel will not change anymore, and we will never wait
+        // again.
+        while state & WAITING != 0 && state & (SENT | SENDER_GONE) == 0 {
+            match self
+                .inner
+                .state
+                .compare_exchange(state, state & !WAITING, AcqRel, Acquire)
+            {
+                Ok(_) => break,
+                Err(found) => state = found,
+            }
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.inner.state.fetch_or(RECEIVER_GONE, AcqRel);
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "oneshot::Receiver {{ ptr: {:p} }}", self.inner)
+    }
+}
+
+unsafe impl<T> Send for Receiver<T> where T: Send {}
+unsafe impl<T> Sync for Receiver<T> where T: Send {}
+
+// The message was written.
+const SENT: usize = 1;
+// The sender disconnected. It might have sent the message before.
+const SENDER_GONE: usize = 2;
+// The receiver disconnected.
+const RECEIVER_GONE: usize = 4;
+// The receiver registered itself to be woken up.
+const WAITING: usize = 8;
+
+struct Inner<T> {
+    state: AtomicUsize,
+    // Only written by the sender before it sets the sent bit.
+    message: UnsafeCell<Removable<T>>,
+    // Only written by the receiver while the waiting bit is clear.
+    waiter: UnsafeCell<Option<Thread>>,
+}
+
+impl<T> Inner<T> {
+    // This function is unsafe because the caller must have seen the waiting
+    // bit set, and the receiver must not be able to register itself again.
+    unsafe fn wake(&self) {
+        if let Some(thread) = &*self.waiter.get() {
+            thread.unpark();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::channel::oneshot;
+    use alloc::sync::Arc;
+    use std::{thread, time::Duration};
+
+    #[test]
+    fn send_then_recv() {
+        let (sender, mut receiver) = oneshot::create::<usize>();
+        assert_eq!(receiver.try_recv(), Err(oneshot::NoMessage));
+        assert!(receiver.is_empty());
+        sender.send(42).unwrap();
+        assert!(receiver.is_connected());
+        assert!(!receiver.is_empty());
+        assert_eq!(receiver.try_recv(), Ok(42));
+        assert!(receiver.is_empty());
+        assert!(!receiver.is_connected());
+        assert_eq!(receiver.try_recv(), Err(oneshot::NoSender));
+    }
+
+    #[test]
+    fn sender_dropped() {
+        let (sender, mut receiver) = oneshot::create::<usize>();
+        drop(sender);
+        assert_eq!(receiver.try_recv(), Err(oneshot::NoSender));
+        assert_eq!(receiver.recv(), Err(oneshot::NoSender));
+    }
+
+    #[test]
+    fn receiver_dropped() {
+        let (sender, receiver) = oneshot::create::<usize>();
+        drop(receiver);
+        assert!(!sender.is_connected());
+        assert_eq!(sender.send(42).unwrap_err().message, 42);
+    }
+
+    #[test]
+    fn drops_unreceived() {
+        let message = Arc::new(());
+        let (sender, receiver) = oneshot::create();
+        sender.send(message.clone()).unwrap();
+        assert_eq!(Arc::strong_count(&message), 2);
+        drop(receiver);
+        assert_eq!(Arc::strong_count(&message), 1);
+    }
+
+    #[test]
+    fn blocking() {
+        const ROUNDS: usize = 64;
+
+        for i in 0..ROUNDS {
+            let (sender, mut receiver) = oneshot::create::<usize>();
+            let thread = thread::spawn(move || receiver.recv());
+            if i % 2 == 0 {
+                thread::yield_now();
+            }
+            sender.send(i).unwrap();
+            assert_eq!(thread.join().unwrap(), Ok(i));
+        }
+
+        let (sender, mut receiver) = oneshot::create::<usize>();
+        let thread = thread::spawn(move || receiver.recv());
+        drop(sender);
+        assert_eq!(thread.join().unwrap(), Err(oneshot::NoSender));
+    }
+
+    #[test]
+    fn timeout_then_other_thread() {
+        let (sender, mut receiver) = oneshot::create::<usize>();
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_millis(10)),
+            Err(oneshot::NoMessage)
+        );
+
+        let thread = thread::spawn(move || receiver.recv());
+        thread::sleep(Duration::from_millis(10));
+        sender.send(7).unwrap();
+        assert_eq!(thread.join().unwrap(), Ok(7));
+    }
+}
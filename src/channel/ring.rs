@@ -0,0 +1,209 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    mem::MaybeUninit,
+    sync::atomic::{fence, AtomicUsize, Ordering::*},
+};
+
+/// A bounded lock-free ring buffer with sequence-numbered slots, following
+/// Dmitry Vyukov's bounded MPMC queue. Values are never allocated, they are
+/// written in place into preallocated slots.
+///
+/// Both the head and the tail are positions made of a lap and an index. The
+/// lap is counted in multiples of `one_lap`, the smallest power of two greater
+/// than the capacity, so the index never overflows into the lap bits. Each slot
+/// carries a stamp: when the stamp equals the tail, the slot is free for that
+/// lap; when it equals the head plus one, the slot holds a value for that lap.
+pub(super) struct Ring<T> {
+    head: Padded<AtomicUsize>,
+    tail: Padded<AtomicUsize>,
+    slots: Box<[Slot<T>]>,
+    one_lap: usize,
+}
+
+impl<T> Ring<T> {
+    /// Creates a ring with the given capacity. Panics if the capacity is zero.
+    pub fn new(cap: usize) -> Self {
+        assert!(cap > 0, "capacity must be positive");
+
+        let mut slots = Vec::with_capacity(cap);
+        for i in 0..cap {
+            slots.push(Slot {
+                stamp: AtomicUsize::new(i),
+                val: UnsafeCell::new(MaybeUninit::uninit()),
+            });
+        }
+
+        Self {
+            head: Padded(AtomicUsize::new(0)),
+            tail: Padded(AtomicUsize::new(0)),
+            slots: slots.into_boxed_slice(),
+            one_lap: (cap + 1).next_power_of_two(),
+        }
+    }
+
+    /// The maximum number of values in the ring.
+    pub fn cap(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Tries to push a value. If the ring is full, the value is given back.
+    pub fn push(&self, val: T) -> Result<(), T> {
+        let mut tail = self.tail.0.load(Relaxed);
+
+        loop {
+            let index = tail & (self.one_lap - 1);
+            let lap = tail & !(self.one_lap - 1);
+            let slot = &self.slots[index];
+            let stamp = slot.stamp.load(Acquire);
+
+            if stamp == tail {
+                // The slot is free for this lap. Let's try to reserve it.
+                let new_tail = if index + 1 < self.cap() {
+                    tail + 1
+                } else {
+                    lap.wrapping_add(self.one_lap)
+                };
+
+                match self
+                    .tail
+                    .0
+                    .compare_exchange_weak(tail, new_tail, SeqCst, Relaxed)
+                {
+                    Ok(_) => {
+                        // This is safe because we reserved the slot, and no
+                        // one reads it until we update the stamp.
+                        unsafe { (*slot.val.get()).as_mut_ptr().write(val) };
+                        slot.stamp.store(tail + 1, Release);
+                        break Ok(());
+                    }
+
+                    Err(found) => tail = found,
+                }
+            } else if stamp.wrapping_add(self.one_lap) == tail + 1 {
+                // The slot still holds the value of the previous lap. The ring
+                // might be full.
+                fence(SeqCst);
+                let head = self.head.0.load(Relaxed);
+                if head.wrapping_add(self.one_lap) == tail {
+                    break Err(val);
+                }
+                tail = self.tail.0.load(Relaxed);
+            } else {
+                // Some other thread reserved the slot in the meantime.
+                tail = self.tail.0.load(Relaxed);
+            }
+        }
+    }
+
+    /// Tries to pop a value. Returns `None` if the ring is empty.
+    pub fn pop(&self) -> Option<T> {
+        let mut head = self.head.0.load(Relaxed);
+
+        loop {
+            let index = head & (self.one_lap - 1);
+            let lap = head & !(self.one_lap - 1);
+            let slot = &self.slots[index];
+            let stamp = slot.stamp.load(Acquire);
+
+            if stamp == head + 1 {
+                // The slot holds a value for this lap. Let's try to take it.
+                let new_head = if index + 1 < self.cap() {
+                    head + 1
+                } else {
+                    lap.wrapping_add(self.one_lap)
+                };
+
+                match self
+                    .head
+                    .0
+                    .compare_exchange_weak(head, new_head, SeqCst, Relaxed)
+                {
+                    Ok(_) => {
+                        // This is safe because the stamp says the value was
+                        // written, and we reserved the slot.
+                        let val = unsafe { (*slot.val.get()).as_ptr().read() };
+                        slot.stamp.store(head.wrapping_add(self.one_lap), Release);
+                        break Some(val);
+                    }
+
+                    Err(found) => head = found,
+                }
+            } else if stamp == head {
+                // The slot is free. The ring might be empty.
+                fence(SeqCst);
+                let tail = self.tail.0.load(Relaxed);
+                if tail == head {
+                    break None;
+                }
+                head = self.head.0.load(Relaxed);
+            } else {
+                // Some other thread took the value in the meantime.
+                head = self.head.0.load(Relaxed);
+            }
+        }
+    }
+
+    /// Tests if the ring is empty.
+    pub fn is_empty(&self) -> bool {
+        let head = self.head.0.load(SeqCst);
+        let tail = self.tail.0.load(SeqCst);
+        head == tail
+    }
+
+    /// The number of values in the ring, taken from a consistent snapshot of
+    /// the head and the tail.
+    pub fn len(&self) -> usize {
+        loop {
+            let tail = self.tail.0.load(SeqCst);
+            let head = self.head.0.load(SeqCst);
+
+            // Only trust the snapshot if the tail did not move meanwhile.
+            if self.tail.0.load(SeqCst) == tail {
+                let head_index = head & (self.one_lap - 1);
+                let tail_index = tail & (self.one_lap - 1);
+
+                break if head_index < tail_index {
+                    tail_index - head_index
+                } else if head_index > tail_index {
+                    self.cap() - head_index + tail_index
+                } else if tail == head {
+                    0
+                } else {
+                    self.cap()
+                };
+            }
+        }
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+impl<T> fmt::Debug for Ring<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Ring {{ head: {:?}, tail: {:?}, cap: {} }}",
+            self.head.0,
+            self.tail.0,
+            self.cap()
+        )
+    }
+}
+
+unsafe impl<T> Send for Ring<T> where T: Send {}
+unsafe impl<T> Sync for Ring<T> where T: Send {}
+
+struct Slot<T> {
+    stamp: AtomicUsize,
+    val: UnsafeCell<MaybeUninit<T>>,
+}
+
+// Keeps the head and the tail in different cache lines.
+#[repr(align(64))]
+struct Padded<T>(T);
@@ -0,0 +1,316 @@
+use super::{
+    signal::{deadline_of, wait_any, Signal},
+    Receive,
+    RecvErr::{self, *},
+};
+use alloc::vec::Vec;
+use core::fmt;
+use std::time::{Duration, Instant};
+
+/// A set of receivers to wait on at once. Receivers of different flavours can
+/// be mixed, as long as their messages have the same type. Selecting returns
+/// the message together with the index of the receiver which produced it; the
+/// index is the one returned by [`add`](Select::add).
+///
+/// Selecting is fair: each selection starts polling right after the receiver
+/// selected last time, so a busy receiver cannot starve the others.
+///
+/// # Example
+/// ```rust
+/// use lock_freedom::channel::{mpmc, select::Select, spsc};
+///
+/// let (mut sender, mut fst) = spsc::create::<u32>();
+/// let (other, mut snd) = mpmc::create::<u32>();
+///
+/// let mut select = Select::new();
+/// let fst_idx = select.add(&mut fst);
+/// let snd_idx = select.add(&mut snd);
+///
+/// other.send(5).unwrap();
+/// assert_eq!(select.select(), Ok((snd_idx, 5)));
+///
+/// sender.send(3).unwrap();
+/// assert_eq!(select.select(), Ok((fst_idx, 3)));
+/// ```
+pub struct Select<'rx, T> {
+    receivers: Vec<&'rx mut dyn Receive<Message = T>>,
+    start: usize,
+}
+
+impl<'rx, T> Select<'rx, T> {
+    /// Creates a selection with no receivers.
+    pub fn new() -> Self {
+        Self {
+            receivers: Vec::new(),
+            start: 0,
+        }
+    }
+
+    /// Registers a receiver, returning the index identifying it in the results
+    /// of the selection.
+    pub fn add<R>(&mut self, receiver: &'rx mut R) -> usize
+    where
+        R: Receive<Message = T>,
+    {
+        self.receivers.push(receiver);
+        self.receivers.len() - 1
+    }
+
+    /// The number of registered receivers.
+    pub fn len(&self) -> usize {
+        self.receivers.len()
+    }
+
+    /// Tests if no receivers were registered.
+    pub fn is_empty(&self) -> bool {
+        self.receivers.is_empty()
+    }
+
+    /// Tries to receive a message from any of the receivers without blocking.
+    /// If all receivers are empty, [`Err`]`(`[`RecvErr::NoMessage`]`)` is
+    /// returned. If, besides that, the senders of every receiver disconnected,
+    /// [`Err`]`(`[`RecvErr::NoSender`]`)` is returned.
+    pub fn try_select(&mut self) -> Result<(usize, T), RecvErr> {
+        let len = self.receivers.len();
+        let mut disconnected = 0;
+
+        for offset in 0..len {
+            let index = (self.start + offset) % len;
+            match self.receivers[index].try_recv() {
+                Ok(message) => {
+                    self.start = (index + 1) % len;
+                    return Ok((index, message));
+                }
+
+                Err(NoSender) => disconnected += 1,
+                Err(NoMessage) => (),
+            }
+        }
+
+        if disconnected == len {
+            Err(NoSender)
+        } else {
+            Err(NoMessage)
+        }
+    }
+
+    /// Receives a message from any of the receivers, blocking the current
+    /// thread while all of them are empty. If the senders of every receiver
+    /// disconnected and all receivers are empty,
+    /// [`Err`]`(`[`RecvErr::NoSender`]`)` is returned.
+    pub fn select(&mut self) -> Result<(usize, T), RecvErr> {
+        self.select_until(None)
+    }
+
+    /// Same as [`select`](Self::select), but gives up after the given timeout,
+    /// returning [`Err`]`(`[`RecvErr::NoMessage`]`)`.
+    pub fn select_timeout(&mut self, timeout: Duration) -> Result<(usize, T), RecvErr> {
+        self.select_until(deadline_of(timeout))
+    }
+
+    /// Same as [`select`](Self::select), but gives up when the given deadline
+    /// is reached, returning [`Err`]`(`[`RecvErr::NoMessage`]`)`.
+    pub fn select_deadline(&mut self, deadline: Instant) -> Result<(usize, T), RecvErr> {
+        self.select_until(Some(deadline))
+    }
+
+    fn select_until(&mut self, deadline: Option<Instant>) -> Result<(usize, T), RecvErr> {
+        match self.try_select() {
+            Err(NoMessage) => (),
+            res => return res,
+        }
+
+        let signals = self
+            .receivers
+            .iter()
+            .map(|receiver| receiver.signal() as *const Signal)
+            .collect::<Vec<_>>();
+        // This is safe because the signals live in the channels' shared
+        // states, which the receivers keep alive. Receiving never moves or
+        // drops them.
+        let signals = signals
+            .into_iter()
+            .map(|signal| unsafe { &*signal })
+            .collect::<Vec<_>>();
+
+        wait_any(&signals, deadline, |fired| {
+            // The receiver whose signal woke us up is tried first. Otherwise,
+            // another receiver could take the wake-up meant for its message,
+            // leaving other threads waiting on that channel asleep.
+            if let Some(index) = fired {
+                if let Ok(message) = self.receivers[index].try_recv() {
+                    self.start = (index + 1) % self.receivers.len();
+                    return Some(Ok((index, message)));
+                }
+            }
+
+            match self.try_select() {
+                Err(NoMessage) => None,
+                res => Some(res),
+            }
+        })
+        .unwrap_or(Err(NoMessage))
+    }
+}
+
+impl<'rx, T> Default for Select<'rx, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'rx, T> fmt::Debug for Select<'rx, T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Select {{ receivers: {}, start: {} }}",
+            self.receivers.len(),
+            self.start
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::channel::{mpmc, mpsc, spmc, spsc};
+    use std::thread;
+
+    #[test]
+    fn empty_and_disconnected() {
+        let mut select = Select::<u32>::new();
+        assert_eq!(select.try_select(), Err(NoSender));
+
+        let (sender, mut receiver) = mpsc::create::<u32>();
+        select.add(&mut receiver);
+        assert_eq!(select.try_select(), Err(NoMessage));
+        assert_eq!(
+            select.select_timeout(Duration::from_millis(10)),
+            Err(NoMessage)
+        );
+
+        drop(sender);
+        assert_eq!(select.select(), Err(NoSender));
+    }
+
+    #[test]
+    fn fair_order() {
+        let (mut fst_tx, mut fst_rx) = spsc::create::<u32>();
+        let (mut snd_tx, mut snd_rx) = spmc::create::<u32>();
+        let (trd_tx, mut trd_rx) = mpmc::bounded::<u32>(8);
+
+        for i in 0..4 {
+            fst_tx.send(i).unwrap();
+            snd_tx.send(i).unwrap();
+            trd_tx.send(i).unwrap();
+        }
+
+        let mut select = Select::new();
+        select.add(&mut fst_rx);
+        select.add(&mut snd_rx);
+        select.add(&mut trd_rx);
+
+        for i in 0..4 {
+            for index in 0..3 {
+                assert_eq!(select.try_select(), Ok((index, i)));
+            }
+        }
+        assert_eq!(select.try_select(), Err(NoMessage));
+    }
+
+    #[test]
+    fn skips_disconnected() {
+        let (fst_tx, mut fst_rx) = mpmc::create::<u32>();
+        let (snd_tx, mut snd_rx) = mpmc::create::<u32>();
+        drop(fst_tx);
+
+        let mut select = Select::new();
+        select.add(&mut fst_rx);
+        select.add(&mut snd_rx);
+
+        snd_tx.send(7).unwrap();
+        assert_eq!(select.select(), Ok((1, 7)));
+        drop(snd_tx);
+        assert_eq!(select.select(), Err(NoSender));
+    }
+
+    #[test]
+    fn wakeup_kept_for_shared_receiver() {
+        let (mut fst_tx, fst_rx) = spsc::create::<u32>();
+        let (snd_tx, snd_rx) = mpmc::create::<u32>();
+        let other = snd_rx.clone();
+
+        let start = Instant::now();
+        let selecting = thread::spawn(move || {
+            let (mut fst_rx, mut snd_rx) = (fst_rx, snd_rx);
+            let mut select = Select::new();
+            select.add(&mut fst_rx);
+            select.add(&mut snd_rx);
+            select.select()
+        });
+        // The selecting thread must wait on the shared channel first.
+        thread::sleep(Duration::from_millis(50));
+        // Once its timeout runs out, the receiver would find the message even
+        // without being woken up, so that must not be what happens.
+        let blocked = thread::spawn(move || {
+            let res = other.recv_timeout(Duration::from_secs(10));
+            assert!(Instant::now() - start < Duration::from_secs(5));
+            res
+        });
+        thread::sleep(Duration::from_millis(50));
+
+        snd_tx.send(1).unwrap();
+        // The selecting thread might be done and gone already.
+        let _ = fst_tx.send(0);
+
+        // Whoever got the wake-up of the shared channel must take its message.
+        match selecting.join().unwrap() {
+            Ok((1, 1)) => {
+                snd_tx.send(2).unwrap();
+                assert_eq!(blocked.join().unwrap(), Ok(2));
+            }
+
+            res => {
+                assert_eq!(res, Ok((0, 0)));
+                assert_eq!(blocked.join().unwrap(), Ok(1));
+            }
+        }
+    }
+
+    #[test]
+    fn blocking_correct_numbers() {
+        const THREADS: usize = 4;
+        const MSGS: usize = 128;
+
+        let mut receivers = Vec::with_capacity(THREADS);
+        let mut threads = Vec::with_capacity(THREADS);
+        for i in 0..THREADS {
+            let (mut sender, receiver) = spsc::create::<usize>();
+            receivers.push(receiver);
+            threads.push(thread::spawn(move || {
+                for j in 0..MSGS {
+                    sender.send(i * MSGS + j).unwrap();
+                    if j % 16 == 0 {
+                        thread::yield_now();
+                    }
+                }
+            }));
+        }
+
+        let mut select = Select::new();
+        for receiver in &mut receivers {
+            select.add(receiver);
+        }
+
+        let mut next = [0; THREADS];
+        while let Ok((index, message)) = select.select() {
+            assert_eq!(message, index * MSGS + next[index]);
+            next[index] += 1;
+        }
+        assert_eq!(next, [MSGS; THREADS]);
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+}
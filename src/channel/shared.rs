@@ -0,0 +1,22 @@
+use super::signal::Signal;
+use crate::count::Counter;
+
+/// The state shared by both sides of an unbounded channel, apart from the
+/// nodes.
+#[derive(Debug)]
+pub struct Shared {
+    /// Notified when messages arrive or senders disconnect.
+    pub signal: Signal,
+    /// The approximate number of messages in the channel.
+    pub len: Counter,
+}
+
+impl Shared {
+    /// Creates the shared state of an empty channel.
+    pub fn new() -> Self {
+        Self {
+            signal: Signal::new(),
+            len: Counter::new(),
+        }
+    }
+}
@@ -0,0 +1,567 @@
+use super::RecvErr;
+use crate::queue::Queue;
+use alloc::{sync::Arc, vec::Vec};
+#[cfg(feature = "async")]
+use core::{
+    cell::UnsafeCell,
+    task::{Context, Poll, Waker},
+};
+use core::{
+    fmt,
+    sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering::*},
+};
+use std::{
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+/// A set of threads (or tasks) waiting for some channel state to change (e.g.
+/// a message to arrive). Notifying is lock-free, and it is almost free when
+/// nobody waits: just a fence and an atomic load. Only waiting threads park.
+///
+/// Waiters register themselves and then check the channel once more before
+/// parking. Notifiers change the channel state before checking for waiters.
+/// Both sides issue a sequentially consistent fence between the two steps, so
+/// either the waiter sees the new state, or the notifier sees the waiter.
+///
+/// Entries of waiters which gave up stay in the queue, since taking them out
+/// could hide the entries of other waiters from notifiers. Notifiers skip
+/// them, and once they are most of the queue, the waiter giving up prunes
+/// them.
+pub struct Signal {
+    // How many entries there are in the queue. Only incremented after the
+    // entry was pushed.
+    waiting: AtomicUsize,
+    // How many of those entries are stale, i.e. their waiters gave up.
+    stale: AtomicUsize,
+    waiters: Queue<Arc<Entry>>,
+}
+
+impl Signal {
+    /// Creates a signal with no waiters.
+    pub fn new() -> Self {
+        Self {
+            waiting: AtomicUsize::new(0),
+            stale: AtomicUsize::new(0),
+            waiters: Queue::new(),
+        }
+    }
+
+    /// Wakes up a single waiter, if any.
+    pub fn notify_one(&self) {
+        fence(SeqCst);
+        if self.waiting.load(Acquire) == 0 {
+            return;
+        }
+
+        // Entries of waiters which gave up are just skipped.
+        while let Some(entry) = self.pop() {
+            if self.claim(&entry) && entry.wake() {
+                break;
+            }
+        }
+    }
+
+    /// Wakes up all the waiters.
+    pub fn notify_all(&self) {
+        fence(SeqCst);
+        if self.waiting.load(Acquire) == 0 {
+            return;
+        }
+
+        while let Some(entry) = self.pop() {
+            if self.claim(&entry) {
+                entry.wake();
+            }
+        }
+    }
+
+    /// Retries the given operation until it produces something, parking the
+    /// thread between the retries. If the deadline is reached, `None` is
+    /// returned. No deadline means waiting forever.
+    pub fn wait_until<F, R>(&self, deadline: Option<Instant>, mut attempt: F) -> Option<R>
+    where
+        F: FnMut() -> Option<R>,
+    {
+        wait_any(&[self], deadline, |_| attempt())
+    }
+
+    /// Tries the given operation, and if it does not produce anything, makes
+    /// sure the task of the given context is woken up when notified. A task
+    /// keeps its registration between polls, and only updates its waker.
+    #[cfg(feature = "async")]
+    pub fn poll_until<F, R>(
+        &self,
+        registration: &mut Registration,
+        cx: &mut Context,
+        mut attempt: F,
+    ) -> Poll<R>
+    where
+        F: FnMut() -> Option<R>,
+    {
+        if let Some(res) = attempt() {
+            self.finish(registration);
+            return Poll::Ready(res);
+        }
+
+        match &registration.entry {
+            Some(entry) if entry.waiter.is_waiting() => {
+                if let WaiterKind::Task(slot) = &entry.waiter.kind {
+                    slot.register(cx.waker());
+                }
+            }
+
+            _ => {
+                let waiter = Arc::new(Waiter::new(WaiterKind::Task(WakerSlot::new(
+                    cx.waker().clone(),
+                ))));
+                let entry = Arc::new(Entry::new(waiter, 0));
+                self.push(entry.clone());
+                registration.entry = Some(entry);
+            }
+        }
+
+        fence(SeqCst);
+
+        // The state might have changed before we registered.
+        match attempt() {
+            Some(res) => {
+                self.finish(registration);
+                Poll::Ready(res)
+            }
+
+            None => Poll::Pending,
+        }
+    }
+
+    /// Gives up a task's registration, if any. If the task was notified but
+    /// will not act on it anymore, the notification is passed on to another
+    /// waiter, so it is not lost.
+    #[cfg(feature = "async")]
+    pub fn cancel(&self, registration: &mut Registration) {
+        if let Some(entry) = registration.entry.take() {
+            entry.waiter.leave();
+            if !self.leave(&entry) {
+                self.notify_one();
+            }
+        }
+    }
+
+    // Drops the registration of a task which got what it was waiting for, so
+    // any notification it got was consumed.
+    #[cfg(feature = "async")]
+    fn finish(&self, registration: &mut Registration) {
+        if let Some(entry) = registration.entry.take() {
+            entry.waiter.leave();
+            self.leave(&entry);
+        }
+    }
+
+    fn push(&self, entry: Arc<Entry>) {
+        self.waiters.push(entry);
+        self.waiting.fetch_add(1, Release);
+    }
+
+    fn pop(&self) -> Option<Arc<Entry>> {
+        let entry = self.waiters.pop()?;
+        self.waiting.fetch_sub(1, Relaxed);
+        Some(entry)
+    }
+
+    // Claims a popped entry. Returns whether it was still live, i.e. its
+    // waiter had not left it.
+    fn claim(&self, entry: &Entry) -> bool {
+        let live = !entry.claimed.swap(true, AcqRel);
+        if !live {
+            self.stale.fetch_sub(1, Relaxed);
+        }
+        live
+    }
+
+    // Leaves an entry of a waiter which stopped waiting. Returns whether the
+    // entry was still live, i.e. no notifier had claimed it.
+    fn leave(&self, entry: &Entry) -> bool {
+        // Counting before claiming, so the count never underflows.
+        self.stale.fetch_add(1, Relaxed);
+        if entry.claimed.swap(true, AcqRel) {
+            // Some notifier popped our entry already.
+            self.stale.fetch_sub(1, Relaxed);
+            false
+        } else {
+            self.prune();
+            true
+        }
+    }
+
+    // Keeps the queue from growing with stale entries when waiters give up
+    // and nobody notifies. Pops entries while most of them are stale. A live
+    // entry popped this way has its waiter woken up, instead of being pushed
+    // back and missing notifications meanwhile. Waiters always check the
+    // channel again after waking up, so this is harmless.
+    fn prune(&self) {
+        while self.stale.load(Relaxed) * 2 > self.waiting.load(Relaxed) {
+            match self.pop() {
+                Some(entry) => {
+                    if self.claim(&entry) {
+                        entry.wake();
+                    }
+                }
+
+                None => break,
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Signal {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Signal {{ waiting: {:?}, stale: {:?} }}",
+            self.waiting, self.stale
+        )
+    }
+}
+
+/// The registration of a task in a [`Signal`]. Must be cancelled through
+/// [`Signal::cancel`] when the task stops waiting.
+#[cfg(feature = "async")]
+#[derive(Debug, Default)]
+pub struct Registration {
+    entry: Option<Arc<Entry>>,
+}
+
+/// Like [`Signal::wait_until`], but the thread is woken up by a notification
+/// from any of the given signals. After a notification, the operation gets the
+/// index of the signal which woke the thread up, since that is where the state
+/// changed.
+pub(super) fn wait_any<F, R>(
+    signals: &[&Signal],
+    deadline: Option<Instant>,
+    mut attempt: F,
+) -> Option<R>
+where
+    F: FnMut(Option<usize>) -> Option<R>,
+{
+    let mut fired = None;
+    loop {
+        if let Some(res) = attempt(fired) {
+            break Some(res);
+        }
+
+        if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+            break None;
+        }
+
+        // The same waiter has an entry in every signal. The first notifier
+        // wakes it, and the others skip its entries.
+        let waiter = Arc::new(Waiter::new(WaiterKind::Thread(thread::current())));
+        let entries: Vec<_> = signals
+            .iter()
+            .enumerate()
+            .map(|(index, signal)| {
+                let entry = Arc::new(Entry::new(waiter.clone(), index));
+                signal.push(entry.clone());
+                entry
+            })
+            .collect();
+        fence(SeqCst);
+
+        // The state might have changed before we registered.
+        if let Some(res) = attempt(None) {
+            // A notification which arrived meanwhile might not be the one we
+            // acted on, so it is passed on.
+            if let Some(index) = leave_all(signals, &waiter, &entries) {
+                signals[index].notify_one();
+            }
+            break Some(res);
+        }
+
+        while waiter.is_waiting() {
+            match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    thread::park_timeout(deadline - now);
+                }
+
+                None => thread::park(),
+            }
+        }
+
+        // We always retry after leaving. This way, if we were notified
+        // right before giving up, the notification is not lost.
+        fired = leave_all(signals, &waiter, &entries);
+    }
+}
+
+// Returns the index of the signal which woke the waiter up, if any.
+fn leave_all(signals: &[&Signal], waiter: &Waiter, entries: &[Arc<Entry>]) -> Option<usize> {
+    // Notifiers popping our entries from now on skip them.
+    let fired = waiter.leave();
+    for (signal, entry) in signals.iter().zip(entries) {
+        signal.leave(entry);
+    }
+    fired
+}
+
+/// Converts a timeout into a deadline. A timeout too big to be represented
+/// means no deadline at all.
+pub(super) fn deadline_of(timeout: Duration) -> Option<Instant> {
+    Instant::now().checked_add(timeout)
+}
+
+/// Receives a message through the given non-blocking `recv`, waiting on the
+/// signal while the channel is empty. If the deadline is reached,
+/// [`RecvErr::NoMessage`] is returned.
+pub(super) fn recv_until<F, T>(
+    signal: &Signal,
+    deadline: Option<Instant>,
+    mut recv: F,
+) -> Result<T, RecvErr>
+where
+    F: FnMut() -> Result<T, RecvErr>,
+{
+    signal
+        .wait_until(deadline, || match recv() {
+            Err(RecvErr::NoMessage) => None,
+            res => Some(res),
+        })
+        .unwrap_or(Err(RecvErr::NoMessage))
+}
+
+// The entry of a waiter in the queue of a signal.
+#[derive(Debug)]
+struct Entry {
+    waiter: Arc<Waiter>,
+    // The index of the signal among the ones the waiter waits on.
+    index: usize,
+    // Set by whoever takes care of the entry first: either the notifier
+    // popping it, or the waiter leaving it in the queue, which makes it stale.
+    claimed: AtomicBool,
+}
+
+impl Entry {
+    fn new(waiter: Arc<Waiter>, index: usize) -> Self {
+        Self {
+            waiter,
+            index,
+            claimed: AtomicBool::new(false),
+        }
+    }
+
+    // Returns whether the waiter was still waiting.
+    fn wake(&self) -> bool {
+        self.waiter.wake(self.index)
+    }
+}
+
+// The state of a waiter still waiting.
+const WAITING: usize = usize::MAX;
+// The state of a waiter which gave up before being woken up.
+const LEFT: usize = usize::MAX - 1;
+
+#[derive(Debug)]
+struct Waiter {
+    // Changed once from waiting, either by the waiter giving up, or by the
+    // notifier, to the index of the entry it popped.
+    state: AtomicUsize,
+    kind: WaiterKind,
+}
+
+impl Waiter {
+    fn new(kind: WaiterKind) -> Self {
+        Self {
+            state: AtomicUsize::new(WAITING),
+            kind,
+        }
+    }
+
+    fn is_waiting(&self) -> bool {
+        self.state.load(Acquire) == WAITING
+    }
+
+    // Returns whether the waiter was still waiting.
+    fn wake(&self, index: usize) -> bool {
+        let waiting = self
+            .state
+            .compare_exchange(WAITING, index, AcqRel, Acquire)
+            .is_ok();
+        if waiting {
+            match &self.kind {
+                WaiterKind::Thread(thread) => thread.unpark(),
+                #[cfg(feature = "async")]
+                WaiterKind::Task(slot) => slot.wake(),
+            }
+        }
+        waiting
+    }
+
+    // Stops waiting, returning the index of the entry through which the
+    // waiter was woken up, if it was.
+    fn leave(&self) -> Option<usize> {
+        match self.state.compare_exchange(WAITING, LEFT, AcqRel, Acquire) {
+            Ok(_) | Err(LEFT) => None,
+            Err(index) => Some(index),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum WaiterKind {
+    Thread(Thread),
+    #[cfg(feature = "async")]
+    Task(WakerSlot),
+}
+
+#[cfg(feature = "async")]
+const SLOT_IDLE: usize = 0;
+#[cfg(feature = "async")]
+const SLOT_REGISTERING: usize = 1;
+#[cfg(feature = "async")]
+const SLOT_WAKING: usize = 2;
+
+/// A lock-free slot holding a task's waker. The waker may be replaced while
+/// some other thread tries to wake it. If a wake-up happens during the
+/// replacement, the thread replacing the waker wakes the task itself.
+#[cfg(feature = "async")]
+struct WakerSlot {
+    state: AtomicUsize,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+#[cfg(feature = "async")]
+impl WakerSlot {
+    fn new(waker: Waker) -> Self {
+        Self {
+            state: AtomicUsize::new(SLOT_IDLE),
+            waker: UnsafeCell::new(Some(waker)),
+        }
+    }
+
+    fn register(&self, waker: &Waker) {
+        match self
+            .state
+            .compare_exchange(SLOT_IDLE, SLOT_REGISTERING, Acquire, Acquire)
+        {
+            Ok(_) => {
+                // This is safe because the registering state gives us
+                // exclusive access to the waker.
+                unsafe {
+                    let stored = &mut *self.waker.get();
+                    if !stored
+                        .as_ref()
+                        .map_or(false, |stored| stored.will_wake(waker))
+                    {
+                        *stored = Some(waker.clone());
+                    }
+                }
+
+                let res = self
+                    .state
+                    .compare_exchange(SLOT_REGISTERING, SLOT_IDLE, AcqRel, Acquire);
+
+                if res.is_err() {
+                    // Someone tried to wake the task meanwhile. The only
+                    // possible state now is registering | waking.
+                    //
+                    // Safe because the waking thread backs off when it sees
+                    // the registering state.
+                    let waker = unsafe { (*self.waker.get()).take() };
+                    self.state.swap(SLOT_IDLE, AcqRel);
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            }
+
+            // Someone is waking the task right now. Let's just wake the task
+            // again.
+            Err(_) => waker.wake_by_ref(),
+        }
+    }
+
+    fn wake(&self) {
+        if self.state.fetch_or(SLOT_WAKING, AcqRel) == SLOT_IDLE {
+            // This is safe because the waking state gives us exclusive access
+            // to the waker.
+            let waker = unsafe { (*self.waker.get()).take() };
+            self.state.fetch_and(!SLOT_WAKING, Release);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl fmt::Debug for WakerSlot {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "WakerSlot {{ state: {:?} }}", self.state)
+    }
+}
+
+#[cfg(feature = "async")]
+unsafe impl Send for WakerSlot {}
+#[cfg(feature = "async")]
+unsafe impl Sync for WakerSlot {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stale_entries_pruned() {
+        let signal = Signal::new();
+        for _ in 0..1000 {
+            let deadline = Instant::now() + Duration::from_micros(10);
+            assert_eq!(signal.wait_until::<_, ()>(Some(deadline), || None), None);
+        }
+        assert!(signal.waiting.load(Relaxed) <= 1);
+        assert!(signal.stale.load(Relaxed) <= 1);
+    }
+
+    #[test]
+    fn waiter_not_lost_while_others_leave() {
+        const LEAVING: usize = 4;
+        const ROUNDS: usize = 200;
+
+        let signal = Arc::new(Signal::new());
+        let done = Arc::new(AtomicBool::new(false));
+
+        let leaving = (0..LEAVING)
+            .map(|_| {
+                let signal = signal.clone();
+                let done = done.clone();
+                thread::spawn(move || {
+                    while !done.load(Acquire) {
+                        let deadline = Instant::now() + Duration::from_micros(50);
+                        signal.wait_until::<_, ()>(Some(deadline), || None);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for _ in 0..ROUNDS {
+            let flag = Arc::new(AtomicBool::new(false));
+            let thread = {
+                let signal = signal.clone();
+                let flag = flag.clone();
+                thread::spawn(move || {
+                    signal.wait_until(None, || flag.load(Acquire).then(|| ()));
+                })
+            };
+            thread::sleep(Duration::from_micros(100));
+            flag.store(true, Release);
+            signal.notify_one();
+            thread.join().unwrap();
+        }
+
+        done.store(true, Release);
+        for thread in leaving {
+            thread.join().unwrap();
+        }
+    }
+}
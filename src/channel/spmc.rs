@@ -1,3 +1,5 @@
+use super::shared::Shared;
+use super::signal::{deadline_of, recv_until, Signal};
 pub use super::{
     NoRecv,
     RecvErr::{self, *},
@@ -8,12 +10,13 @@ use crate::{
     ptr::{bypass_null, check_null_align},
     removable::Removable,
 };
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 use core::{
     fmt,
     ptr::{null_mut, NonNull},
     sync::atomic::{AtomicPtr, Ordering::*},
 };
+use std::time::{Duration, Instant};
 
 /// Creates an asynchronous lock-free Single-Producer-Multi-Consumer (SPMC)
 /// channel. In order to allow multiple consumers, [`Receiver`] is clonable and
@@ -33,13 +36,18 @@ pub fn with_incin<T>(incin: SharedIncin<T>) -> (Sender<T>, Receiver<T>) {
         next: AtomicPtr::new(null_mut()),
     });
     let single_node = alloc.into_raw();
+    let shared = Arc::new(Shared::new());
 
     // Then put it on back and on the front.
-    let sender = Sender { back: single_node };
+    let sender = Sender {
+        back: single_node,
+        shared: shared.clone(),
+    };
     let receiver = Receiver {
         inner: Arc::new(ReceiverInner {
             front: AtomicPtr::new(single_node.as_ptr()),
             incin,
+            shared,
         }),
     };
 
@@ -50,6 +58,7 @@ pub fn with_incin<T>(incin: SharedIncin<T>) -> (Sender<T>, Receiver<T>) {
 /// [`with_incin`] function.
 pub struct Sender<T> {
     back: NonNull<Node<T>>,
+    shared: Arc<Shared>,
 }
 
 impl<T> Sender<T> {
@@ -61,6 +70,7 @@ impl<T> Sender<T> {
             next: AtomicPtr::new(null_mut()),
         });
         let nnptr = alloc.into_raw();
+        self.shared.len.add(1);
 
         // This dereferral is safe because the queue has at least one node. We
         // possess a single node in the back, and if the queue has just one
@@ -80,6 +90,7 @@ impl<T> Sender<T> {
             // If we succeeded, let's update the back so we keep the invariant
             // "the back has a single node".
             self.back = nnptr;
+            self.shared.signal.notify_one();
             Ok(())
         } else {
             // If we failed, receiver disconnected. It is safe to dealloc
@@ -87,10 +98,58 @@ impl<T> Sender<T> {
             // it with anyone (cas failed).
             let mut alloc = unsafe { OwnedAlloc::from_raw(nnptr) };
             let message = alloc.message.replace(None).unwrap();
+            self.shared.len.sub(1);
+            Err(NoRecv { message })
+        }
+    }
+
+    /// Sends all messages of the given iterator as a single batch. The nodes
+    /// are linked beforehand and published at once, so the receivers see
+    /// either none or all of them. If the receivers disconnected, the messages
+    /// are given back in the error.
+    pub fn send_iter<I>(&mut self, messages: I) -> Result<(), NoRecv<Vec<T>>>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let (first, last, count) = match make_chain(messages) {
+            Some(chain) => chain,
+            None => return Ok(()),
+        };
+        self.shared.len.add(count);
+
+        // This dereferral is safe because the queue has at least one node, and
+        // we are the only ones with access to the back. Just like in `send`,
+        // we publish the whole chain through the back's next field.
+        let res = unsafe {
+            self.back
+                .as_ref()
+                .next
+                .compare_exchange(null_mut(), first.as_ptr(), Release, Relaxed)
+        };
+
+        if res.is_ok() {
+            // The last node of the chain is our new single node in the back.
+            self.back = last;
+            // There might be many messages for many receivers.
+            self.shared.signal.notify_all();
+            Ok(())
+        } else {
+            // If we failed, receivers disconnected. This is safe because we
+            // did not share the chain.
+            let message = unsafe { take_chain(first) };
+            self.shared.len.sub(count);
             Err(NoRecv { message })
         }
     }
 
+    /// The approximate number of messages sent but not yet received. Just
+    /// like [`Receiver::len`], this is only a hint. Requires the `len`
+    /// feature.
+    #[cfg(feature = "len")]
+    pub fn pending(&self) -> usize {
+        self.shared.len.get()
+    }
+
     /// Tests if there are any [`Receiver`]s still connected. There are no
     /// guarantees that [`send`](Sender::send) will succeed if this method
     /// returns `true` because the [`Receiver`] may disconnect meanwhile.
@@ -125,6 +184,9 @@ impl<T> Drop for Sender<T> {
         if !res.is_null() {
             unsafe { OwnedAlloc::from_raw(self.back) };
         }
+
+        // Blocked receivers must see we disconnected.
+        self.shared.signal.notify_all();
     }
 }
 
@@ -166,6 +228,7 @@ impl<T> Receiver<T> {
             // delete nodes via incinerator.
             match unsafe { front_nnptr.as_ref().message.take(AcqRel) } {
                 Some(val) => {
+                    self.inner.shared.len.sub(1);
                     // Safe to call because we passed a pointer from the front
                     // which was loaded during the very same pause we are
                     // passing.
@@ -182,6 +245,71 @@ impl<T> Receiver<T> {
         }
     }
 
+    /// Receives a message, blocking the current thread while the channel is
+    /// empty. If the senders disconnected and the channel is empty,
+    /// [`Err`]`(`[`RecvErr::NoSender`]`)` is returned. The thread is only
+    /// parked when there are no messages, and sending stays lock-free.
+    pub fn recv_blocking(&self) -> Result<T, RecvErr> {
+        self.recv_until(None)
+    }
+
+    /// Same as [`recv_blocking`](Self::recv_blocking), but gives up after the
+    /// given timeout, returning [`Err`]`(`[`RecvErr::NoMessage`]`)`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvErr> {
+        self.recv_until(deadline_of(timeout))
+    }
+
+    /// Same as [`recv_blocking`](Self::recv_blocking), but gives up when the
+    /// given deadline is reached, returning
+    /// [`Err`]`(`[`RecvErr::NoMessage`]`)`.
+    pub fn recv_deadline(&self, deadline: Instant) -> Result<T, RecvErr> {
+        self.recv_until(Some(deadline))
+    }
+
+    pub(super) fn signal(&self) -> &Signal {
+        &self.inner.shared.signal
+    }
+
+    fn recv_until(&self, deadline: Option<Instant>) -> Result<T, RecvErr> {
+        recv_until(&self.inner.shared.signal, deadline, || self.recv())
+    }
+
+    /// The approximate number of messages in the channel. Concurrent sends
+    /// and receives may be partially observed, so this is only a hint.
+    /// Requires the `len` feature.
+    #[cfg(feature = "len")]
+    pub fn len(&self) -> usize {
+        self.inner.shared.len.get()
+    }
+
+    /// Tests if there are no messages in the channel. Unlike `len`, this is
+    /// exact at the moment it checks the front of the channel.
+    pub fn is_empty(&self) -> bool {
+        let pause = self.inner.incin.get_unchecked().pause();
+        let mut ptr = self.inner.front.load(Acquire);
+
+        while let Some(nnptr) = NonNull::new((ptr as usize & !1) as *mut Node<T>) {
+            // This is safe because we paused the incinerator and only delete
+            // nodes via incinerator. Nodes reachable from the front are not
+            // deleted during the pause.
+            let node = unsafe { nnptr.as_ref() };
+            if node.message.is_present(Acquire) {
+                return false;
+            }
+            ptr = node.next.load(Acquire);
+        }
+
+        pause.resume();
+        true
+    }
+
+    /// Creates an iterator over the messages currently available. The
+    /// iterator does not block: it stops as soon as the channel is empty or
+    /// disconnected.
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter { receiver: self }
+    }
+
     /// Tests if there are any [`Sender`]s still connected. There are no
     /// guarantees that [`recv`](Receiver::recv) will succeed if this method
     /// returns `true` because the [`Receiver`] may disconnect meanwhile.
@@ -264,10 +392,34 @@ impl<T> fmt::Debug for Receiver<T> {
 unsafe impl<T> Send for Receiver<T> where T: Send {}
 unsafe impl<T> Sync for Receiver<T> where T: Send {}
 
+/// An iterator based on [`recv`](Receiver::recv) operation of the
+/// [`Receiver`]. Created by [`Receiver::try_iter`].
+pub struct TryIter<'rx, T>
+where
+    T: 'rx,
+{
+    receiver: &'rx Receiver<T>,
+}
+
+impl<'rx, T> Iterator for TryIter<'rx, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl<'rx, T> fmt::Debug for TryIter<'rx, T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "TryIter {{ receiver: {:?} }}", self.receiver)
+    }
+}
+
 struct ReceiverInner<T> {
     // never null
     front: AtomicPtr<Node<T>>,
     incin: SharedIncin<T>,
+    shared: Arc<Shared>,
 }
 
 impl<T> Drop for ReceiverInner<T> {
@@ -325,6 +477,56 @@ struct Node<T> {
     next: AtomicPtr<Node<T>>,
 }
 
+// The first and the last nodes of a chain of nodes, and how many nodes it has.
+type Chain<T> = (NonNull<Node<T>>, NonNull<Node<T>>, usize);
+
+// Links the messages into a chain of fresh nodes, or returns `None` if there
+// are no messages.
+fn make_chain<I, T>(messages: I) -> Option<Chain<T>>
+where
+    I: IntoIterator<Item = T>,
+{
+    let mut messages = messages.into_iter();
+    let first = OwnedAlloc::new(Node {
+        message: Removable::new(messages.next()?),
+        next: AtomicPtr::new(null_mut()),
+    })
+    .into_raw();
+
+    let mut last = first;
+    let mut count = 1;
+    for message in messages {
+        let node = OwnedAlloc::new(Node {
+            message: Removable::new(message),
+            next: AtomicPtr::new(null_mut()),
+        })
+        .into_raw();
+        // This is safe because we only link nodes we just allocated, and the
+        // chain is not shared yet.
+        unsafe { last.as_ref().next.store(node.as_ptr(), Relaxed) };
+        last = node;
+        count += 1;
+    }
+
+    Some((first, last, count))
+}
+
+// This function is unsafe because the chain must have been created by
+// `make_chain` and must have never been shared. Takes the messages out of the
+// chain and deallocates its nodes.
+unsafe fn take_chain<T>(first: NonNull<Node<T>>) -> Vec<T> {
+    let mut messages = Vec::new();
+    let mut node_ptr = Some(first);
+
+    while let Some(node) = node_ptr {
+        let mut alloc = OwnedAlloc::from_raw(node);
+        node_ptr = NonNull::new(*alloc.next.get_mut());
+        messages.extend(alloc.message.replace(None));
+    }
+
+    messages
+}
+
 make_shared_incin! {
     { "`spmc::Receiver`" }
     pub SharedIncin<T> of OwnedAlloc<Node<T>>
@@ -378,4 +580,74 @@ mod test {
             assert!(status.load(Relaxed));
         }
     }
+
+    #[test]
+    fn blocking_correct_numbers() {
+        const THREADS: usize = 8;
+        const MSGS: usize = 512;
+
+        let mut done = Vec::with_capacity(MSGS);
+        for _ in 0..MSGS {
+            done.push(AtomicBool::new(false));
+        }
+        let done = Arc::<[AtomicBool]>::from(done);
+
+        let (mut sender, receiver) = spmc::create::<usize>();
+        let mut threads = Vec::with_capacity(THREADS);
+
+        for _ in 0..THREADS {
+            let done = done.clone();
+            let receiver = receiver.clone();
+            threads.push(thread::spawn(move || {
+                while let Ok(i) = receiver.recv_blocking() {
+                    assert!(!done[i].swap(true, AcqRel));
+                }
+            }))
+        }
+
+        for i in 0..MSGS {
+            sender.send(i).unwrap();
+        }
+
+        drop(sender);
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        for status in done.iter() {
+            assert!(status.load(Relaxed));
+        }
+    }
+
+    #[test]
+    fn batch_and_try_iter() {
+        let (mut sender, receiver) = spmc::create::<usize>();
+        sender.send_iter(0..16).unwrap();
+        sender.send_iter(None).unwrap();
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            (0..16).collect::<Vec<_>>()
+        );
+
+        drop(receiver);
+        let err = sender.send_iter(0..4).unwrap_err();
+        assert_eq!(err.message, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn len_and_is_empty() {
+        let (mut sender, receiver) = spmc::create::<usize>();
+        let other = receiver.clone();
+        assert!(receiver.is_empty());
+        sender.send_iter(0..3).unwrap();
+        #[cfg(feature = "len")]
+        assert_eq!(other.len(), 3);
+        assert_eq!(other.recv(), Ok(0));
+        assert!(!receiver.is_empty());
+        assert_eq!(receiver.try_iter().count(), 2);
+        assert!(other.is_empty());
+        #[cfg(feature = "len")]
+        assert_eq!(sender.pending(), 0);
+    }
 }
@@ -1,14 +1,18 @@
+use super::shared::Shared;
+use super::signal::{deadline_of, recv_until, Signal};
 pub use super::{
     NoRecv,
     RecvErr::{self, *},
 };
 use crate::owned_alloc::OwnedAlloc;
 use crate::ptr::check_null_align;
+use alloc::{sync::Arc, vec::Vec};
 use core::{
     fmt,
     ptr::{null_mut, NonNull},
     sync::atomic::{AtomicPtr, Ordering::*},
 };
+use std::time::{Duration, Instant};
 
 /// Creates an asynchronous lock-free Single-Producer-Single-Consumer (SPSC)
 /// channel.
@@ -21,13 +25,25 @@ pub fn create<T>() -> (Sender<T>, Receiver<T>) {
         next: AtomicPtr::new(null_mut()),
     });
     let nnptr = alloc.into_raw();
-
-    (Sender { back: nnptr }, Receiver { front: nnptr })
+    let shared = Arc::new(Shared::new());
+
+    let sender = Sender {
+        back: nnptr,
+        shared: shared.clone(),
+    };
+    (
+        sender,
+        Receiver {
+            front: nnptr,
+            shared,
+        },
+    )
 }
 
 /// The `Sender` handle of a SPSC channel. Created by [`create`] function.
 pub struct Sender<T> {
     back: NonNull<Node<T>>,
+    shared: Arc<Shared>,
 }
 
 impl<T> Sender<T> {
@@ -39,6 +55,7 @@ impl<T> Sender<T> {
             next: AtomicPtr::new(null_mut()),
         });
         let nnptr = alloc.into_raw();
+        self.shared.len.add(1);
 
         // This dereferral is safe because the queue will always have at least
         // one node. Our back is a single node. In any case, back will always be
@@ -62,15 +79,63 @@ impl<T> Sender<T> {
             // If we succeeded, let's update our back so we respect the rule of
             // having a single node in the back.
             self.back = nnptr;
+            self.shared.signal.notify_one();
             Ok(())
         } else {
             // If we failed, the receiver disconnected and marked the bit.
             let mut alloc = unsafe { OwnedAlloc::from_raw(nnptr) };
             let message = alloc.message.take().unwrap();
+            self.shared.len.sub(1);
             Err(NoRecv { message })
         }
     }
 
+    /// Sends all messages of the given iterator as a single batch. The nodes
+    /// are linked beforehand and published at once, so the receiver sees
+    /// either none or all of them. If the receiver disconnected, the messages
+    /// are given back in the error.
+    pub fn send_iter<I>(&mut self, messages: I) -> Result<(), NoRecv<Vec<T>>>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let (first, last, count) = match make_chain(messages) {
+            Some(chain) => chain,
+            None => return Ok(()),
+        };
+        self.shared.len.add(count);
+
+        // This dereferral is safe because the queue will always have at least
+        // one node. Our back is a single node. Just like in `send`, we publish
+        // the whole chain through the back's next field.
+        let res = unsafe {
+            self.back
+                .as_ref()
+                .next
+                .compare_exchange(null_mut(), first.as_ptr(), Release, Relaxed)
+        };
+
+        if res.is_ok() {
+            // The last node of the chain is our new single node in the back.
+            self.back = last;
+            self.shared.signal.notify_one();
+            Ok(())
+        } else {
+            // If we failed, the receiver disconnected and marked the bit. This
+            // is safe because we did not share the chain.
+            let message = unsafe { take_chain(first) };
+            self.shared.len.sub(count);
+            Err(NoRecv { message })
+        }
+    }
+
+    /// The approximate number of messages sent but not yet received. Just
+    /// like [`Receiver::len`], this is only a hint. Requires the `len`
+    /// feature.
+    #[cfg(feature = "len")]
+    pub fn pending(&self) -> usize {
+        self.shared.len.get()
+    }
+
     /// Tests if the [`Receiver`] is still connected. There are no guarantees
     /// that [`send`](Sender::send) will succeed if this method returns `true`
     /// because the [`Receiver`] may disconnect meanwhile.
@@ -104,6 +169,9 @@ impl<T> Drop for Sender<T> {
         if !res.is_null() {
             unsafe { OwnedAlloc::from_raw(self.back) };
         }
+
+        // A blocked receiver must see we disconnected.
+        self.shared.signal.notify_all();
     }
 }
 
@@ -119,6 +187,7 @@ impl<T> fmt::Debug for Sender<T> {
 /// The [`Receiver`] handle of a SPSC channel. Created by [`create`] function.
 pub struct Receiver<T> {
     front: NonNull<Node<T>>,
+    shared: Arc<Shared>,
 }
 
 impl<T> Receiver<T> {
@@ -137,6 +206,7 @@ impl<T> Receiver<T> {
             // First we remove a node logically.
             match node.message.take() {
                 Some(message) => {
+                    self.shared.len.sub(1);
                     let cleared = (next as usize & !1) as *mut _;
                     // But only if we have a new node. Otherwise we will not
                     // remove the only node of the queue. Also, let's clear the
@@ -179,6 +249,65 @@ impl<T> Receiver<T> {
         }
     }
 
+    /// Receives a message, blocking the current thread while the channel is
+    /// empty. If the senders disconnected and the channel is empty,
+    /// [`Err`]`(`[`RecvErr::NoSender`]`)` is returned. The thread is only
+    /// parked when there are no messages, and sending stays lock-free.
+    pub fn recv_blocking(&mut self) -> Result<T, RecvErr> {
+        self.recv_until(None)
+    }
+
+    /// Same as [`recv_blocking`](Self::recv_blocking), but gives up after the
+    /// given timeout, returning [`Err`]`(`[`RecvErr::NoMessage`]`)`.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<T, RecvErr> {
+        self.recv_until(deadline_of(timeout))
+    }
+
+    /// Same as [`recv_blocking`](Self::recv_blocking), but gives up when the
+    /// given deadline is reached, returning
+    /// [`Err`]`(`[`RecvErr::NoMessage`]`)`.
+    pub fn recv_deadline(&mut self, deadline: Instant) -> Result<T, RecvErr> {
+        self.recv_until(Some(deadline))
+    }
+
+    pub(super) fn signal(&self) -> &Signal {
+        &self.shared.signal
+    }
+
+    fn recv_until(&mut self, deadline: Option<Instant>) -> Result<T, RecvErr> {
+        match self.recv() {
+            Err(RecvErr::NoMessage) => (),
+            res => return res,
+        }
+        let shared = self.shared.clone();
+        recv_until(&shared.signal, deadline, || self.recv())
+    }
+
+    /// The approximate number of messages in the channel. Concurrent sends
+    /// and receives may be partially observed, so this is only a hint.
+    /// Requires the `len` feature.
+    #[cfg(feature = "len")]
+    pub fn len(&self) -> usize {
+        self.shared.len.get()
+    }
+
+    /// Tests if there are no messages in the channel. Unlike `len`, this is
+    /// exact at the moment it checks the front of the channel.
+    pub fn is_empty(&self) -> bool {
+        // Safe because we always have at least one node, which is only dropped
+        // in the last side to disconnect's drop. Nodes after the front always
+        // hold a message until we receive it.
+        let front = unsafe { self.front.as_ref() };
+        front.message.is_none() && front.next.load(Acquire) as usize & !1 == 0
+    }
+
+    /// Creates an iterator over the messages currently available. The
+    /// iterator does not block: it stops as soon as the channel is empty or
+    /// disconnected.
+    pub fn try_iter(&mut self) -> TryIter<'_, T> {
+        TryIter { receiver: self }
+    }
+
     /// Tests if the [`Sender`] is still connected. There are no guarantees
     /// that [`recv`](Receiver::recv) will succeed if this method returns `true`
     /// because the [`Receiver`] may disconnect meanwhile. This method may
@@ -242,6 +371,29 @@ impl<T> fmt::Debug for Receiver<T> {
     }
 }
 
+/// An iterator based on [`recv`](Receiver::recv) operation of the
+/// [`Receiver`]. Created by [`Receiver::try_iter`].
+pub struct TryIter<'rx, T>
+where
+    T: 'rx,
+{
+    receiver: &'rx mut Receiver<T>,
+}
+
+impl<'rx, T> Iterator for TryIter<'rx, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl<'rx, T> fmt::Debug for TryIter<'rx, T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "TryIter {{ receiver: {:?} }}", self.receiver)
+    }
+}
+
 #[repr(align(/* at least */ 2))]
 struct Node<T> {
     message: Option<T>,
@@ -249,6 +401,56 @@ struct Node<T> {
     next: AtomicPtr<Node<T>>,
 }
 
+// The first and the last nodes of a chain of nodes, and how many nodes it has.
+type Chain<T> = (NonNull<Node<T>>, NonNull<Node<T>>, usize);
+
+// Links the messages into a chain of fresh nodes, or returns `None` if there
+// are no messages.
+fn make_chain<I, T>(messages: I) -> Option<Chain<T>>
+where
+    I: IntoIterator<Item = T>,
+{
+    let mut messages = messages.into_iter();
+    let first = OwnedAlloc::new(Node {
+        message: Some(messages.next()?),
+        next: AtomicPtr::new(null_mut()),
+    })
+    .into_raw();
+
+    let mut last = first;
+    let mut count = 1;
+    for message in messages {
+        let node = OwnedAlloc::new(Node {
+            message: Some(message),
+            next: AtomicPtr::new(null_mut()),
+        })
+        .into_raw();
+        // This is safe because we only link nodes we just allocated, and the
+        // chain is not shared yet.
+        unsafe { last.as_ref().next.store(node.as_ptr(), Relaxed) };
+        last = node;
+        count += 1;
+    }
+
+    Some((first, last, count))
+}
+
+// This function is unsafe because the chain must have been created by
+// `make_chain` and must have never been shared. Takes the messages out of the
+// chain and deallocates its nodes.
+unsafe fn take_chain<T>(first: NonNull<Node<T>>) -> Vec<T> {
+    let mut messages = Vec::new();
+    let mut node_ptr = Some(first);
+
+    while let Some(node) = node_ptr {
+        let mut alloc = OwnedAlloc::from_raw(node);
+        node_ptr = NonNull::new(*alloc.next.get_mut());
+        messages.extend(alloc.message.take());
+    }
+
+    messages
+}
+
 #[cfg(test)]
 mod test {
     use crate::channel::spsc;
@@ -282,4 +484,76 @@ mod test {
 
         thread.join().unwrap();
     }
+
+    #[test]
+    fn blocking_sequence() {
+        const MSGS: usize = 512;
+
+        let (mut sender, mut receiver) = spsc::create::<usize>();
+        let thread = thread::spawn(move || {
+            for i in 0..MSGS {
+                assert_eq!(receiver.recv_blocking(), Ok(i));
+            }
+            assert_eq!(receiver.recv_blocking(), Err(spsc::NoSender));
+        });
+
+        for i in 0..MSGS {
+            sender.send(i).unwrap();
+        }
+        drop(sender);
+
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn batches_in_order() {
+        const BATCHES: usize = 64;
+        const BATCH: usize = 16;
+
+        let (mut sender, mut receiver) = spsc::create::<usize>();
+        let thread = thread::spawn(move || {
+            for i in 0..BATCHES {
+                sender.send_iter(i * BATCH..(i + 1) * BATCH).unwrap();
+            }
+            sender.send_iter(None).unwrap();
+        });
+
+        let mut received = Vec::new();
+        loop {
+            received.extend(receiver.try_iter());
+            match receiver.recv_blocking() {
+                Ok(message) => received.push(message),
+                Err(_) => break,
+            }
+        }
+        thread.join().unwrap();
+
+        assert_eq!(received, (0..BATCHES * BATCH).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn batch_no_receiver() {
+        let (mut sender, receiver) = spsc::create::<usize>();
+        drop(receiver);
+        let err = sender.send_iter(0..4).unwrap_err();
+        assert_eq!(err.message, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn len_and_is_empty() {
+        let (mut sender, mut receiver) = spsc::create::<usize>();
+        assert!(receiver.is_empty());
+        sender.send(1).unwrap();
+        sender.send_iter(2..5).unwrap();
+        assert!(!receiver.is_empty());
+        #[cfg(feature = "len")]
+        {
+            assert_eq!(receiver.len(), 4);
+            assert_eq!(sender.pending(), 4);
+        }
+        assert_eq!(receiver.try_iter().count(), 4);
+        assert!(receiver.is_empty());
+        #[cfg(feature = "len")]
+        assert_eq!(sender.pending(), 0);
+    }
 }
@@ -0,0 +1,82 @@
+use core::sync::atomic::{AtomicUsize, Ordering::*};
+
+/// An approximate count of the elements of a structure, which always counts.
+///
+/// Structures which can, should count elements before they are published, and
+/// uncount them after they are removed, so the count never underflows. When
+/// that is not possible, the count may transiently go below zero, which reads
+/// as zero.
+#[derive(Debug, Default)]
+pub struct AtomicCounter {
+    count: AtomicUsize,
+}
+
+impl AtomicCounter {
+    /// Creates a counter starting at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts `n` new elements.
+    #[inline]
+    pub fn add(&self, n: usize) {
+        self.count.fetch_add(n, Relaxed);
+    }
+
+    /// Uncounts `n` removed elements.
+    #[inline]
+    pub fn sub(&self, n: usize) {
+        self.count.fetch_sub(n, Relaxed);
+    }
+
+    /// The current count.
+    pub fn get(&self) -> usize {
+        // Wrapping arithmetic makes a transiently negative count huge.
+        let count = self.count.load(Relaxed);
+        if count > isize::MAX as usize {
+            0
+        } else {
+            count
+        }
+    }
+
+    /// Sets the count back to zero.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// An [`AtomicCounter`] which only counts with the `len` feature. Otherwise,
+/// it has no size and its operations do nothing.
+#[derive(Debug, Default)]
+pub struct Counter {
+    #[cfg(feature = "len")]
+    count: AtomicCounter,
+}
+
+impl Counter {
+    /// Creates a counter starting at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts `n` new elements.
+    #[inline]
+    pub fn add(&self, _n: usize) {
+        #[cfg(feature = "len")]
+        self.count.add(_n);
+    }
+
+    /// Uncounts `n` removed elements.
+    #[inline]
+    pub fn sub(&self, _n: usize) {
+        #[cfg(feature = "len")]
+        self.count.sub(_n);
+    }
+
+    /// The current count.
+    #[cfg(feature = "len")]
+    pub fn get(&self) -> usize {
+        self.count.get()
+    }
+}
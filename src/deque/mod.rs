@@ -0,0 +1,616 @@
+mod steal;
+
+pub use self::steal::{Steal, Stealer, Worker};
+use crate::owned_alloc::{Cache, OwnedAlloc};
+use crate::{incin::Pause, ptr::check_null_align};
+use core::{
+    fmt,
+    iter::FromIterator,
+    mem::ManuallyDrop,
+    ptr::{null_mut, NonNull},
+    sync::atomic::{AtomicPtr, Ordering::*},
+};
+
+/// A lock-free double-ended queue. Values can be pushed and popped at both
+/// ends, and both ends can be accessed concurrently by any number of threads.
+///
+/// # Design
+/// The implementation follows Maged Michael's CAS-based deque. The deque is a
+/// doubly linked list of nodes, and the whole state of the list is summarized
+/// by an "anchor": a pointer to the leftmost node, a pointer to the rightmost
+/// node and a status. The anchor is an immutable allocation and it is replaced
+/// as a whole through a single compare-and-swap, which gives us the effect of
+/// a double-word CAS. Replaced anchors and popped nodes are dropped through
+/// the incinerator, so no thread ever sees a freed anchor or node.
+///
+/// A push first swings the anchor to the new node, marking the anchor as
+/// "unstable", and only then links the previous end node to the new node.
+/// Any thread which finds an unstable anchor helps finishing the link before
+/// doing its own operation.
+pub struct Deque<T> {
+    anchor: AtomicPtr<Anchor<T>>,
+    incin: SharedIncin<T>,
+}
+
+impl<T> Deque<T> {
+    /// Creates a new empty deque.
+    pub fn new() -> Self {
+        check_null_align::<Node<T>>();
+        Self::with_incin(SharedIncin::new())
+    }
+
+    /// Creates an empty deque using the passed shared incinerator.
+    pub fn with_incin(incin: SharedIncin<T>) -> Self {
+        let anchor = Anchor {
+            left: null_mut(),
+            right: null_mut(),
+            status: Status::Stable,
+        };
+        Self {
+            anchor: AtomicPtr::new(OwnedAlloc::new(anchor).into_raw().as_ptr()),
+            incin,
+        }
+    }
+
+    /// Returns the shared incinerator used by this [`Deque`].
+    pub fn incin(&self) -> SharedIncin<T> {
+        self.incin.clone()
+    }
+
+    /// Creates an iterator over `T`s, based on [`pop_front`](Deque::pop_front)
+    /// operation of the [`Deque`].
+    pub fn pop_iter(&self) -> PopIter<'_, T> {
+        PopIter { deque: self }
+    }
+
+    /// Pushes a value into the front of the deque.
+    pub fn push_front(&self, item: T) {
+        self.push(item, Side::Front)
+    }
+
+    /// Pushes a value into the back of the deque.
+    pub fn push_back(&self, item: T) {
+        self.push(item, Side::Back)
+    }
+
+    /// Takes a value from the front of the deque, if it is available.
+    pub fn pop_front(&self) -> Option<T> {
+        self.pop(Side::Front)
+    }
+
+    /// Takes a value from the back of the deque, if it is available.
+    pub fn pop_back(&self) -> Option<T> {
+        self.pop(Side::Back)
+    }
+
+    /// Pushes elements from the given iterable into the back of the deque.
+    /// Acts just like [`Extend::extend`] but does not require mutability.
+    pub fn extend<I>(&self, iterable: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        for elem in iterable {
+            self.push_back(elem);
+        }
+    }
+
+    fn push(&self, item: T, side: Side) {
+        let node = OwnedAlloc::new(Node::new(item)).into_raw();
+        // Pausing because we dereference anchors and nodes which other threads
+        // might be removing.
+        let pause = self.incin.get_unchecked().pause();
+        let mut cache = Cache::<OwnedAlloc<Anchor<T>>>::new();
+
+        loop {
+            let loaded = self.anchor.load(Acquire);
+            // This dereferral is safe because we paused the incinerator and we
+            // only delete anchors via incinerator. Anchors are never null.
+            let anchor = unsafe { &*loaded };
+
+            let new = if anchor.is_empty() {
+                Anchor {
+                    left: node.as_ptr(),
+                    right: node.as_ptr(),
+                    status: Status::Stable,
+                }
+            } else if anchor.status == Status::Stable {
+                // The new node must point to the current end node before it is
+                // published. This is safe because the node is still ours.
+                let new = unsafe {
+                    match side {
+                        Side::Front => {
+                            node.as_ref().right.store(anchor.left, Relaxed);
+                            Anchor {
+                                left: node.as_ptr(),
+                                right: anchor.right,
+                                status: Status::PushLeft,
+                            }
+                        }
+
+                        Side::Back => {
+                            node.as_ref().left.store(anchor.right, Relaxed);
+                            Anchor {
+                                left: anchor.left,
+                                right: node.as_ptr(),
+                                status: Status::PushRight,
+                            }
+                        }
+                    }
+                };
+                new
+            } else {
+                // Some other push did not finish linking its node. Let's help
+                // it, and then try again.
+                //
+                // Safe because the anchor was loaded during this very pause.
+                unsafe { self.stabilize(loaded, &pause) };
+                continue;
+            };
+
+            // Safe because the anchor was loaded during this very pause.
+            if let Some(new_ptr) = unsafe { self.try_replace(loaded, new, &mut cache, &pause) } {
+                // Safe because the anchor was published during this pause.
+                unsafe { self.stabilize(new_ptr, &pause) };
+                break;
+            }
+        }
+    }
+
+    fn pop(&self, side: Side) -> Option<T> {
+        // Pausing because of ABA problem and use-after-free.
+        let pause = self.incin.get_unchecked().pause();
+        let mut cache = Cache::<OwnedAlloc<Anchor<T>>>::new();
+
+        let node = loop {
+            let loaded = self.anchor.load(Acquire);
+            // This dereferral is safe because we paused the incinerator and we
+            // only delete anchors via incinerator. Anchors are never null.
+            let anchor = unsafe { &*loaded };
+
+            if anchor.is_empty() {
+                return None;
+            }
+
+            let new = if anchor.left == anchor.right {
+                // A single node. Note that unstable anchors always have at
+                // least two nodes.
+                Anchor {
+                    left: null_mut(),
+                    right: null_mut(),
+                    status: Status::Stable,
+                }
+            } else if anchor.status == Status::Stable {
+                // In a stable anchor, end nodes are correctly linked to their
+                // neighbours. This dereferral is safe because we paused the
+                // incinerator and we only delete nodes via incinerator.
+                unsafe {
+                    match side {
+                        Side::Front => Anchor {
+                            left: (*anchor.left).right.load(Acquire),
+                            right: anchor.right,
+                            status: Status::Stable,
+                        },
+
+                        Side::Back => Anchor {
+                            left: anchor.left,
+                            right: (*anchor.right).left.load(Acquire),
+                            status: Status::Stable,
+                        },
+                    }
+                }
+            } else {
+                // Safe because the anchor was loaded during this very pause.
+                unsafe { self.stabilize(loaded, &pause) };
+                continue;
+            };
+
+            let node = match side {
+                Side::Front => anchor.left,
+                Side::Back => anchor.right,
+            };
+
+            // Safe because the anchor was loaded during this very pause.
+            if unsafe { self.try_replace(loaded, new, &mut cache, &pause) }.is_some() {
+                break node;
+            }
+        };
+
+        // The node was removed from the deque, nobody else will read its value.
+        // Other threads might still read its links, so the node itself is only
+        // dropped via incinerator. The value is never dropped by the node.
+        unsafe {
+            let mut nnptr = NonNull::new_unchecked(node);
+            let val = (&mut *nnptr.as_mut().val as *mut T).read();
+            pause.add_to_incin(Garbage::Node(OwnedAlloc::from_raw(nnptr)));
+            Some(val)
+        }
+    }
+
+    // Tries to replace the loaded anchor by a new one. In case of success, the
+    // pointer to the new anchor is returned. This function is unsafe because
+    // the loaded pointer must have been loaded during the passed pause.
+    unsafe fn try_replace(
+        &self,
+        loaded: *mut Anchor<T>,
+        new: Anchor<T>,
+        cache: &mut Cache<OwnedAlloc<Anchor<T>>>,
+        pause: &Pause<Garbage<T>>,
+    ) -> Option<*mut Anchor<T>> {
+        let mut alloc = cache.take_or(|| OwnedAlloc::new(new));
+        *alloc = new;
+        let new_ptr = alloc.raw().as_ptr();
+
+        match self
+            .anchor
+            .compare_exchange(loaded, new_ptr, AcqRel, Relaxed)
+        {
+            Ok(_) => {
+                alloc.into_raw();
+                // The old anchor is shared data. Must be deleted through the
+                // incinerator.
+                pause.add_to_incin(Garbage::Anchor(OwnedAlloc::from_raw(
+                    NonNull::new_unchecked(loaded),
+                )));
+                Some(new_ptr)
+            }
+
+            Err(_) => {
+                // We may reuse this allocation later.
+                cache.store(alloc);
+                None
+            }
+        }
+    }
+
+    // Finishes the link of a pushed node and marks the anchor as stable. This
+    // function is unsafe because the anchor must have been loaded during the
+    // passed pause.
+    unsafe fn stabilize(&self, loaded: *mut Anchor<T>, pause: &Pause<Garbage<T>>) {
+        let anchor = &*loaded;
+
+        // The pushed node and the previous end node. Note that the pushed node
+        // already points to its neighbour.
+        let (pushed, link) = match anchor.status {
+            Status::Stable => return,
+
+            Status::PushLeft => {
+                let prev = (*anchor.left).right.load(Acquire);
+                (anchor.left, &(*prev).left)
+            }
+
+            Status::PushRight => {
+                let prev = (*anchor.right).left.load(Acquire);
+                (anchor.right, &(*prev).right)
+            }
+        };
+
+        let found = link.load(Acquire);
+        if found != pushed {
+            // If the anchor changed, the link was already fixed by someone
+            // else. Do not touch the links then.
+            if self.anchor.load(Acquire) != loaded {
+                return;
+            }
+
+            if link
+                .compare_exchange(found, pushed, AcqRel, Relaxed)
+                .is_err()
+            {
+                return;
+            }
+        }
+
+        let new = Anchor {
+            left: anchor.left,
+            right: anchor.right,
+            status: Status::Stable,
+        };
+        // We are not obliged to succeed. Someone else might have stabilized it.
+        self.try_replace(loaded, new, &mut Cache::new(), pause);
+    }
+}
+
+impl<T> Default for Deque<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Deque<T> {
+    fn drop(&mut self) {
+        // This is safe because we have exclusive access to the deque and we
+        // only store anchors allocated via `OwnedAlloc`.
+        let alloc = unsafe { OwnedAlloc::from_raw(NonNull::new_unchecked(*self.anchor.get_mut())) };
+        let anchor = *alloc;
+
+        if anchor.is_empty() {
+            return;
+        }
+
+        // Let's first finish any pending link, so we can walk through the list
+        // from left to right.
+        unsafe {
+            match anchor.status {
+                Status::Stable => (),
+
+                Status::PushLeft => {
+                    let prev = *(*anchor.left).right.get_mut();
+                    *(*prev).left.get_mut() = anchor.left;
+                }
+
+                Status::PushRight => {
+                    let prev = *(*anchor.right).left.get_mut();
+                    *(*prev).right.get_mut() = anchor.right;
+                }
+            }
+        }
+
+        let mut curr = anchor.left;
+        loop {
+            // This is safe because we only store nodes allocated via
+            // `OwnedAlloc`. Also, we have exclusive access to these nodes.
+            let mut node = unsafe { OwnedAlloc::from_raw(NonNull::new_unchecked(curr)) };
+            unsafe { ManuallyDrop::drop(&mut node.val) };
+
+            if curr == anchor.right {
+                break;
+            }
+            curr = *node.right.get_mut();
+        }
+    }
+}
+
+impl<T> FromIterator<T> for Deque<T> {
+    fn from_iter<I>(iterable: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let this = Self::new();
+        this.extend(iterable);
+        this
+    }
+}
+
+impl<T> Extend<T> for Deque<T> {
+    fn extend<I>(&mut self, iterable: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        (*self).extend(iterable)
+    }
+}
+
+impl<T> fmt::Debug for Deque<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Deque {{ anchor: {:?}, incin: {:?} }}",
+            self.anchor, self.incin
+        )
+    }
+}
+
+unsafe impl<T> Send for Deque<T> where T: Send {}
+
+unsafe impl<T> Sync for Deque<T> where T: Send {}
+
+/// An iterator based on [`pop_front`](Deque::pop_front) operation of the
+/// [`Deque`].
+pub struct PopIter<'deque, T>
+where
+    T: 'deque,
+{
+    deque: &'deque Deque<T>,
+}
+
+impl<'deque, T> Iterator for PopIter<'deque, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.deque.pop_front()
+    }
+}
+
+impl<'deque, T> fmt::Debug for PopIter<'deque, T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "PopIter {{ deque: {:?} }}", self.deque)
+    }
+}
+
+make_shared_incin! {
+    { "[`Deque`]" }
+    pub SharedIncin<T> of Garbage<T>
+}
+
+impl<T> fmt::Debug for SharedIncin<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "SharedIncin {{ inner: {:?} }}", self.inner)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Side {
+    Front,
+    Back,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Stable,
+    // The leftmost node was pushed but its right neighbour does not point to
+    // it yet.
+    PushLeft,
+    // The rightmost node was pushed but its left neighbour does not point to
+    // it yet.
+    PushRight,
+}
+
+// Never mutated after being published.
+struct Anchor<T> {
+    left: *mut Node<T>,
+    right: *mut Node<T>,
+    status: Status,
+}
+
+impl<T> Anchor<T> {
+    fn is_empty(&self) -> bool {
+        self.right.is_null()
+    }
+}
+
+impl<T> Clone for Anchor<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Anchor<T> {}
+
+struct Node<T> {
+    val: ManuallyDrop<T>,
+    left: AtomicPtr<Node<T>>,
+    right: AtomicPtr<Node<T>>,
+}
+
+impl<T> Node<T> {
+    fn new(val: T) -> Self {
+        Self {
+            val: ManuallyDrop::new(val),
+            left: AtomicPtr::new(null_mut()),
+            right: AtomicPtr::new(null_mut()),
+        }
+    }
+}
+
+enum Garbage<T> {
+    Anchor(OwnedAlloc<Anchor<T>>),
+    Node(OwnedAlloc<Node<T>>),
+}
+
+impl<T> fmt::Debug for Garbage<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Garbage::Anchor(ptr) => write!(fmtr, "Garbage::Anchor({:?})", ptr),
+            Garbage::Node(ptr) => write!(fmtr, "Garbage::Node({:?})", ptr),
+        }
+    }
+}
+
+// Testing the safety of `unsafe` in this module is done with random operations
+// via fuzzing
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+    use core::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn on_empty_first_pop_is_none() {
+        let deque = Deque::<usize>::new();
+        assert!(deque.pop_front().is_none());
+        assert!(deque.pop_back().is_none());
+    }
+
+    #[test]
+    fn on_empty_last_pop_is_none() {
+        let deque = Deque::new();
+        deque.push_back(3);
+        deque.push_front(1234);
+        deque.pop_back();
+        deque.pop_front();
+        assert!(deque.pop_front().is_none());
+        assert!(deque.pop_back().is_none());
+    }
+
+    #[test]
+    fn order() {
+        let deque = Deque::new();
+        deque.push_back(3);
+        deque.push_back(5);
+        deque.push_front(2);
+        deque.push_front(1);
+        deque.push_back(6);
+        assert_eq!(deque.pop_front(), Some(1));
+        assert_eq!(deque.pop_back(), Some(6));
+        assert_eq!(deque.pop_back(), Some(5));
+        assert_eq!(deque.pop_front(), Some(2));
+        assert_eq!(deque.pop_front(), Some(3));
+        assert_eq!(deque.pop_back(), None);
+    }
+
+    #[test]
+    fn pop_iter_and_from_iter() {
+        let deque = (0..10).collect::<Deque<_>>();
+        deque.push_front(-1);
+        let popped = deque.pop_iter().collect::<Vec<_>>();
+        assert_eq!(popped, (-1..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn drops_remaining() {
+        let counter = Arc::new(());
+        let deque = Deque::new();
+        for _ in 0..10 {
+            deque.push_back(counter.clone());
+            deque.push_front(counter.clone());
+        }
+        deque.pop_back();
+        deque.pop_front();
+        assert_eq!(Arc::strong_count(&counter), 19);
+        drop(deque);
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn no_data_corruption() {
+        use std::thread;
+        const NTHREAD: usize = 20;
+        const NITER: usize = 800;
+        const NMOD: usize = 55;
+
+        let deque = Arc::new(Deque::new());
+        let mut handles = Vec::with_capacity(NTHREAD);
+        let removed = Arc::new(AtomicUsize::new(0));
+
+        for i in 0..NTHREAD {
+            let removed = removed.clone();
+            let deque = deque.clone();
+            handles.push(thread::spawn(move || {
+                for j in 0..NITER {
+                    let val = (i * NITER) + j;
+                    if j % 2 == 0 {
+                        deque.push_back(val);
+                    } else {
+                        deque.push_front(val);
+                    }
+                    if (val + 1) % NMOD == 0 {
+                        let popped = if i % 2 == 0 {
+                            deque.pop_front()
+                        } else {
+                            deque.pop_back()
+                        };
+                        if let Some(val) = popped {
+                            removed.fetch_add(1, Relaxed);
+                            assert!(val < NITER * NTHREAD);
+                        }
+                    }
+                }
+            }));
+        }
+
+        for handle in handles {
+            handle.join().expect("thread failed");
+        }
+
+        let expected = NITER * NTHREAD - removed.load(Relaxed);
+        let mut res = 0;
+        while let Some(val) = deque.pop_back() {
+            assert!(val < NITER * NTHREAD);
+            res += 1;
+        }
+
+        assert_eq!(res, expected);
+    }
+}
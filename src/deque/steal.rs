@@ -0,0 +1,476 @@
+use crate::{
+    incin::Incinerator,
+    owned_alloc::{OwnedAlloc, RawVec},
+};
+use alloc::sync::Arc;
+use core::{
+    fmt,
+    mem::MaybeUninit,
+    ptr::NonNull,
+    sync::atomic::{fence, AtomicIsize, AtomicPtr, Ordering::*},
+};
+
+// The capacity of the first buffer. Must be a power of two.
+const MIN_CAP: usize = 32;
+
+/// The owner handle of a Chase-Lev work-stealing deque. Only the owner pushes
+/// values, and it pops them either in LIFO or FIFO order, depending on how it
+/// was created. Any number of [`Stealer`]s may take values concurrently from
+/// the other end of the deque.
+///
+/// # Design
+/// The values are stored in a growable ring buffer indexed by two counters,
+/// the top (where stealers take from) and the bottom (where the owner pushes).
+/// When the buffer is full, the owner copies the values into a buffer twice as
+/// big and publishes it. Stealers might still be reading the old buffer, so it
+/// is only dropped through an incinerator. The memory orderings follow "Correct
+/// and Efficient Work-Stealing for Weak Memory Models" (Lê et al., 2013).
+pub struct Worker<T> {
+    inner: Arc<Inner<T>>,
+    flavor: Flavor,
+}
+
+impl<T> Worker<T> {
+    /// Creates a new worker which pops the value pushed most recently, i.e.
+    /// the opposite end of the stealers.
+    pub fn new_lifo() -> Self {
+        Self::with_flavor(Flavor::Lifo)
+    }
+
+    /// Creates a new worker which pops the value pushed least recently, i.e.
+    /// the same end of the stealers.
+    pub fn new_fifo() -> Self {
+        Self::with_flavor(Flavor::Fifo)
+    }
+
+    fn with_flavor(flavor: Flavor) -> Self {
+        let buffer = OwnedAlloc::new(Buffer::new(MIN_CAP));
+        let inner = Inner {
+            top: AtomicIsize::new(0),
+            bottom: AtomicIsize::new(0),
+            buffer: AtomicPtr::new(buffer.into_raw().as_ptr()),
+            incin: Incinerator::new(),
+        };
+        Self {
+            inner: Arc::new(inner),
+            flavor,
+        }
+    }
+
+    /// Creates a new [`Stealer`] connected to this worker.
+    pub fn stealer(&self) -> Stealer<T> {
+        Stealer {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Tests if the deque is empty. Note that stealers might take values
+    /// meanwhile.
+    pub fn is_empty(&self) -> bool {
+        let bottom = self.inner.bottom.load(Relaxed);
+        let top = self.inner.top.load(Relaxed);
+        bottom.wrapping_sub(top) <= 0
+    }
+
+    /// Pushes a value into the owner's end of the deque.
+    pub fn push(&mut self, val: T) {
+        let bottom = self.inner.bottom.load(Relaxed);
+        let top = self.inner.top.load(Acquire);
+        let mut buffer = self.inner.buffer.load(Relaxed);
+
+        // Only the owner replaces the buffer, so this dereferral is safe.
+        if bottom.wrapping_sub(top) >= unsafe { (*buffer).cap() } as isize {
+            // Safe because the indices were just loaded by the owner.
+            buffer = unsafe { self.grow(top, bottom) };
+        }
+
+        // Safe because the slot at the bottom is not visible to the stealers
+        // until the bottom is incremented.
+        unsafe { (*buffer).at(bottom).write(val) };
+        fence(Release);
+        self.inner.bottom.store(bottom.wrapping_add(1), Relaxed);
+    }
+
+    /// Pops a value from the deque, if it is available. Whether the most
+    /// recently pushed value or the least recently pushed value is popped
+    /// depends on whether the worker was created by
+    /// [`new_lifo`](Worker::new_lifo) or [`new_fifo`](Worker::new_fifo).
+    pub fn pop(&mut self) -> Option<T> {
+        match self.flavor {
+            Flavor::Lifo => self.pop_bottom(),
+            Flavor::Fifo => self.pop_top(),
+        }
+    }
+
+    fn pop_bottom(&mut self) -> Option<T> {
+        let bottom = self.inner.bottom.load(Relaxed).wrapping_sub(1);
+        let buffer = self.inner.buffer.load(Relaxed);
+        // We reserve the bottom slot before looking at the top. Stealers will
+        // not go past this reservation.
+        self.inner.bottom.store(bottom, Relaxed);
+        fence(SeqCst);
+        let top = self.inner.top.load(Relaxed);
+
+        let size = bottom.wrapping_sub(top);
+        if size < 0 {
+            // Empty. Let's undo the reservation.
+            self.inner.bottom.store(bottom.wrapping_add(1), Relaxed);
+            return None;
+        }
+
+        // This read is safe because only the owner replaces the buffer and the
+        // slot is between top and bottom. However, if this is the last value,
+        // a stealer might take it too, and so we cannot own it yet.
+        let val = unsafe { (*buffer).at(bottom).cast::<MaybeUninit<T>>().read() };
+
+        if size == 0 {
+            // The last value. We race with the stealers for it.
+            let res = self
+                .inner
+                .top
+                .compare_exchange(top, top.wrapping_add(1), SeqCst, Relaxed);
+            self.inner.bottom.store(bottom.wrapping_add(1), Relaxed);
+            if res.is_err() {
+                // A stealer won, and the value is theirs.
+                return None;
+            }
+        }
+
+        // Safe because we won the value.
+        Some(unsafe { val.assume_init() })
+    }
+
+    fn pop_top(&mut self) -> Option<T> {
+        let bottom = self.inner.bottom.load(Relaxed);
+        let buffer = self.inner.buffer.load(Relaxed);
+        // Stealers compare-and-swap the top, so incrementing it makes any
+        // stealer racing for this slot fail.
+        let top = self.inner.top.fetch_add(1, SeqCst);
+
+        if bottom.wrapping_sub(top.wrapping_add(1)) < 0 {
+            // Empty. Stealers cannot succeed meanwhile since they see an empty
+            // deque too, so we can just restore the top.
+            self.inner.top.store(top, Relaxed);
+            return None;
+        }
+
+        // Safe because we own the slot between the old top and the bottom,
+        // and only the owner replaces the buffer.
+        Some(unsafe { (*buffer).at(top).read() })
+    }
+
+    // Replaces the buffer with one twice as big. This function is unsafe
+    // because the passed indices must have been loaded by the owner.
+    unsafe fn grow(&mut self, top: isize, bottom: isize) -> *mut Buffer<T> {
+        let old = self.inner.buffer.load(Relaxed);
+        let new = Buffer::<T>::new((*old).cap() * 2);
+
+        let mut i = top;
+        while i != bottom {
+            // The values are only copied bitwise. The old buffer does not drop
+            // its values, so nothing is duplicated.
+            new.at(i).write((*old).at(i).read());
+            i = i.wrapping_add(1);
+        }
+
+        let new = OwnedAlloc::new(new).into_raw().as_ptr();
+        self.inner.buffer.store(new, Release);
+        // Stealers might be reading the old buffer. It must be deleted through
+        // the incinerator.
+        self.inner
+            .incin
+            .add(OwnedAlloc::from_raw(NonNull::new_unchecked(old)));
+        new
+    }
+}
+
+impl<T> fmt::Debug for Worker<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "Worker {{ flavor: {:?} }}", self.flavor)
+    }
+}
+
+unsafe impl<T> Send for Worker<T> where T: Send {}
+unsafe impl<T> Sync for Worker<T> where T: Send {}
+
+/// A handle which steals values from a [`Worker`]'s deque. It is clonable and
+/// does not require mutability.
+pub struct Stealer<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Stealer<T> {
+    /// Tries to steal the least recently pushed value. If the deque is empty,
+    /// [`Steal::Empty`] is returned. If another thread took the value
+    /// concurrently, [`Steal::Retry`] is returned.
+    pub fn steal(&self) -> Steal<T> {
+        let top = self.inner.top.load(Acquire);
+        fence(SeqCst);
+        let bottom = self.inner.bottom.load(Acquire);
+
+        if bottom.wrapping_sub(top) <= 0 {
+            return Steal::Empty;
+        }
+
+        // Pausing because the owner might replace the buffer meanwhile.
+        let pause = self.inner.incin.pause();
+        let buffer = self.inner.buffer.load(Acquire);
+        // This read is safe because we paused the incinerator and we only
+        // delete buffers via incinerator. We do not own the value until we
+        // win the race for it, though.
+        let val = unsafe { (*buffer).at(top).cast::<MaybeUninit<T>>().read() };
+        drop(pause);
+
+        match self
+            .inner
+            .top
+            .compare_exchange(top, top.wrapping_add(1), SeqCst, Relaxed)
+        {
+            // Safe because we won the value.
+            Ok(_) => Steal::Success(unsafe { val.assume_init() }),
+            Err(_) => Steal::Retry,
+        }
+    }
+
+    /// Tests if the deque is empty. Note that the worker might push values
+    /// meanwhile.
+    pub fn is_empty(&self) -> bool {
+        let top = self.inner.top.load(Acquire);
+        fence(SeqCst);
+        let bottom = self.inner.bottom.load(Acquire);
+        bottom.wrapping_sub(top) <= 0
+    }
+}
+
+impl<T> Clone for Stealer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Stealer<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "Stealer {{ ptr: {:p} }}", self.inner)
+    }
+}
+
+unsafe impl<T> Send for Stealer<T> where T: Send {}
+unsafe impl<T> Sync for Stealer<T> where T: Send {}
+
+/// The result of a [`Stealer::steal`] operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Steal<T> {
+    /// The deque was empty.
+    Empty,
+    /// A value was stolen.
+    Success(T),
+    /// Another thread took the value concurrently. The operation might be
+    /// retried.
+    Retry,
+}
+
+impl<T> Steal<T> {
+    /// Tests if the deque was empty.
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Steal::Empty)
+    }
+
+    /// Tests if the operation should be retried.
+    pub fn is_retry(&self) -> bool {
+        matches!(self, Steal::Retry)
+    }
+
+    /// Returns the stolen value, if any.
+    pub fn success(self) -> Option<T> {
+        match self {
+            Steal::Success(val) => Some(val),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flavor {
+    Lifo,
+    Fifo,
+}
+
+struct Inner<T> {
+    top: AtomicIsize,
+    bottom: AtomicIsize,
+    // never null
+    buffer: AtomicPtr<Buffer<T>>,
+    incin: Incinerator<OwnedAlloc<Buffer<T>>>,
+}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        let top = *self.top.get_mut();
+        let bottom = *self.bottom.get_mut();
+        // This is safe because we have exclusive access and we only store
+        // buffers allocated via `OwnedAlloc`.
+        let buffer =
+            unsafe { OwnedAlloc::from_raw(NonNull::new_unchecked(*self.buffer.get_mut())) };
+
+        let mut i = top;
+        while i != bottom {
+            // Safe because the values between top and bottom are initialized
+            // and nobody else owns them.
+            unsafe { buffer.at(i).drop_in_place() };
+            i = i.wrapping_add(1);
+        }
+    }
+}
+
+// A ring buffer. It never drops its values.
+struct Buffer<T> {
+    data: RawVec<T>,
+}
+
+impl<T> Buffer<T> {
+    fn new(cap: usize) -> Self {
+        debug_assert!(cap.is_power_of_two());
+        Self {
+            data: RawVec::with_capacity(cap),
+        }
+    }
+
+    fn cap(&self) -> usize {
+        self.data.cap()
+    }
+
+    // The slot of a given index. Indices wrap around the capacity.
+    fn at(&self, index: isize) -> *mut T {
+        let offset = index as usize & (self.cap() - 1);
+        // Safe because the offset is always in bounds.
+        unsafe { self.data.raw().as_ptr().add(offset) }
+    }
+}
+
+impl<T> fmt::Debug for Buffer<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "Buffer {{ data: {:?} }}", self.data)
+    }
+}
+
+// Testing the safety of `unsafe` in this module is done with random operations
+// via fuzzing
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn on_empty_pop_and_steal_are_none() {
+        let mut worker = Worker::<usize>::new_lifo();
+        assert!(worker.pop().is_none());
+        assert!(worker.stealer().steal().is_empty());
+    }
+
+    #[test]
+    fn lifo_order() {
+        let mut worker = Worker::new_lifo();
+        let stealer = worker.stealer();
+        for i in 0..100 {
+            worker.push(i);
+        }
+        assert_eq!(stealer.steal(), Steal::Success(0));
+        assert_eq!(worker.pop(), Some(99));
+        assert_eq!(worker.pop(), Some(98));
+        assert_eq!(stealer.steal(), Steal::Success(1));
+    }
+
+    #[test]
+    fn fifo_order() {
+        let mut worker = Worker::new_fifo();
+        let stealer = worker.stealer();
+        for i in 0..100 {
+            worker.push(i);
+        }
+        assert_eq!(worker.pop(), Some(0));
+        assert_eq!(stealer.steal(), Steal::Success(1));
+        assert_eq!(worker.pop(), Some(2));
+        let rest = (0..97).filter_map(|_| worker.pop()).collect::<Vec<_>>();
+        assert_eq!(rest, (3..100).collect::<Vec<_>>());
+        assert!(worker.pop().is_none());
+    }
+
+    #[test]
+    fn drops_remaining() {
+        let counter = Arc::new(());
+        let mut worker = Worker::new_lifo();
+        for _ in 0..100 {
+            worker.push(counter.clone());
+        }
+        worker.pop();
+        worker.stealer().steal();
+        assert_eq!(Arc::strong_count(&counter), 99);
+        drop(worker);
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn no_data_corruption() {
+        use core::sync::atomic::AtomicBool;
+        use std::thread;
+        const NTHREAD: usize = 8;
+        const NITER: usize = 20000;
+
+        for &lifo in &[true, false] {
+            let mut done = Vec::with_capacity(NITER);
+            for _ in 0..NITER {
+                done.push(AtomicBool::new(false));
+            }
+            let done = Arc::<[AtomicBool]>::from(done);
+
+            let mut worker = if lifo {
+                Worker::<usize>::new_lifo()
+            } else {
+                Worker::new_fifo()
+            };
+            let mut handles = Vec::with_capacity(NTHREAD);
+
+            for _ in 0..NTHREAD {
+                let stealer = worker.stealer();
+                let done = done.clone();
+                handles.push(thread::spawn(move || loop {
+                    match stealer.steal() {
+                        Steal::Success(i) => {
+                            if i == NITER {
+                                break;
+                            }
+                            assert!(!done[i].swap(true, AcqRel));
+                        }
+                        Steal::Empty | Steal::Retry => (),
+                    }
+                }));
+            }
+
+            for i in 0..NITER {
+                worker.push(i);
+                if i % 3 == 0 {
+                    if let Some(i) = worker.pop() {
+                        assert!(!done[i].swap(true, AcqRel));
+                    }
+                }
+            }
+            while let Some(i) = worker.pop() {
+                assert!(!done[i].swap(true, AcqRel));
+            }
+            for _ in 0..NTHREAD {
+                worker.push(NITER);
+            }
+
+            for handle in handles {
+                handle.join().expect("thread failed");
+            }
+
+            for status in done.iter() {
+                assert!(status.load(Relaxed));
+            }
+        }
+    }
+}
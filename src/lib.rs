@@ -22,9 +22,19 @@
 //! - `[x]` [Channels (SPSC, MPSC, SPMC, MPMC)](channel)
 //! - `[x]` [Map](map::Map)
 //! - `[x]` [Set](set::Set)
+//! - `[x]` [Ordered Map and Set](skiplist)
+//! - `[x]` [Cache](cache::Cache)
+//! - `[x]` [Map with Expiring Entries](ttl::TtlMap)
 //! - `[x]` [Stack](stack::Stack)
 //! - `[x]` [Queue](queue::Queue)
-//! - `[ ]` Deque
+//! - `[x]` [Deque](deque::Deque)
+//! - `[x]` [Work-Stealing Deque](deque::Worker)
+//!
+//! # Optional Features
+//! - `async`: `Stream` and `Sink` adapters for the channels.
+//! - `len`: approximate `len` methods for queues, stacks and channels, backed
+//!   by an atomic counter. Without this feature, nothing is counted. Maps,
+//!   sets and skip lists always count, so their `len` needs no feature.
 //!
 //! # Performance Guide
 //! In order to achieve a better time performance with lockfree, it is
@@ -54,6 +64,10 @@ pub mod queue;
 #[cfg(feature = "std")]
 pub mod stack;
 
+/// A lock-free double-ended queue and a work-stealing deque.
+#[cfg(feature = "std")]
+pub mod deque;
+
 /// A lock-free map.
 #[cfg(feature = "std")]
 pub mod map;
@@ -62,12 +76,23 @@ pub mod map;
 #[cfg(feature = "std")]
 pub mod set;
 
+/// A lock-free ordered map and set based on skip lists.
+#[cfg(feature = "std")]
+pub mod skiplist;
+
+/// A lock-free bounded cache with CLOCK eviction.
+#[cfg(feature = "std")]
+pub mod cache;
+
+/// A lock-free map whose entries expire after a time to live.
+#[cfg(feature = "std")]
+pub mod ttl;
+
 /// Collection of lock-free FIFO channels. These channels are fully asynchronous
-/// and their receivers do not provide any sort of `wait-for-message` operation.
-/// It would be blocking otherwise, thus not lock-free. If you need such a
-/// mechanism, consider using this channel with a
-/// [`Condvar`](std::sync::Condvar) or using things like
-/// [`thread::park`](std::thread::park) (not lock-free).
+/// and `recv` never waits for a message. Receivers also provide opt-in
+/// blocking operations (e.g. `recv_blocking` and `recv_timeout`), which park
+/// the thread only while the channel is empty. Those are blocking, thus not
+/// lock-free, but sending stays lock-free.
 #[cfg(feature = "std")]
 pub mod channel;
 
@@ -76,3 +101,5 @@ pub mod removable;
 
 #[allow(dead_code)]
 mod ptr;
+
+mod count;
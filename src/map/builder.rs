@@ -0,0 +1,123 @@
+use super::{Map, SharedIncin};
+use core::{fmt, hash::BuildHasher};
+use std::collections::hash_map::RandomState;
+
+/// The default number of hash bits consumed by each table level of a
+/// [`Map`]. Each table has `1 << DEFAULT_BITS` slots.
+pub const DEFAULT_BITS: u32 = 8;
+
+/// The maximum number of hash bits a table level of a [`Map`] can consume.
+pub const MAX_BITS: u32 = 16;
+
+/// A builder of [`Map`]s (and [`Set`](crate::set::Set)s), allowing one to
+/// configure the fan-out of the tables and their compression besides the
+/// hasher builder.
+///
+/// Each table of the map consumes `bits` bits of the hash, and so it has
+/// `1 << bits` slots. Fewer bits make tables smaller, which saves memory in
+/// small maps, at the cost of deeper trees in big maps. More bits do the
+/// opposite. The default is [`DEFAULT_BITS`].
+///
+/// # Example
+/// ```rust
+/// use lock_freedom::map::{Map, MapBuilder};
+///
+/// let map: Map<u64, &str> = MapBuilder::new().bits(4).build();
+/// map.insert(3, "three");
+/// assert_eq!(map.bits(), 4);
+/// assert_eq!(*map.get(&3).unwrap().val(), "three");
+/// ```
+#[derive(Clone)]
+pub struct MapBuilder<H = RandomState> {
+    bits: u32,
+    compressed: bool,
+    hasher: H,
+}
+
+impl MapBuilder {
+    /// Creates a new builder with the default hasher builder and
+    /// [`DEFAULT_BITS`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<H> MapBuilder<H> {
+    /// Sets the number of hash bits consumed by each table.
+    ///
+    /// # Panics
+    /// Panics if `bits` is `0` or greater than [`MAX_BITS`].
+    pub fn bits(self, bits: u32) -> Self {
+        assert!(
+            bits > 0 && bits <= MAX_BITS,
+            "table bits must be in 1..={}, got {}",
+            MAX_BITS,
+            bits
+        );
+        Self { bits, ..self }
+    }
+
+    /// Sets whether sparse sub-tables are compressed, i.e. hold only their
+    /// occupied slots. Compressed tables use memory proportional to their
+    /// occupancy, but they are copied on every write, which makes every
+    /// operation on big maps several times slower. Disabled by default.
+    ///
+    /// Tables with more than `1 << 10` slots are never compressed.
+    pub fn compressed(self, compressed: bool) -> Self {
+        Self { compressed, ..self }
+    }
+
+    /// Sets the hasher builder.
+    pub fn hasher<G>(self, hasher: G) -> MapBuilder<G> {
+        MapBuilder {
+            bits: self.bits,
+            compressed: self.compressed,
+            hasher,
+        }
+    }
+
+    pub(super) fn into_parts(self) -> (u32, bool, H) {
+        (self.bits, self.compressed, self.hasher)
+    }
+}
+
+impl<H> MapBuilder<H>
+where
+    H: BuildHasher,
+{
+    /// Builds a [`Map`] with the given configuration.
+    pub fn build<K, V>(self) -> Map<K, V, H> {
+        self.build_with_incin(SharedIncin::new())
+    }
+
+    /// Builds a [`Map`] with the given configuration and shared incinerator.
+    pub fn build_with_incin<K, V>(self, incin: SharedIncin<K, V>) -> Map<K, V, H> {
+        Map::with_builder_and_incin(self, incin)
+    }
+}
+
+impl<H> Default for MapBuilder<H>
+where
+    H: Default,
+{
+    fn default() -> Self {
+        Self {
+            bits: DEFAULT_BITS,
+            compressed: false,
+            hasher: H::default(),
+        }
+    }
+}
+
+impl<H> fmt::Debug for MapBuilder<H>
+where
+    H: fmt::Debug,
+{
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "MapBuilder {{ bits: {}, compressed: {}, hasher: {:?} }}",
+            self.bits, self.compressed, self.hasher
+        )
+    }
+}
@@ -0,0 +1,133 @@
+use super::{
+    insertion::{InsertEntry, Insertion},
+    Map, ReadGuard,
+};
+use core::{
+    fmt,
+    hash::{BuildHasher, Hash},
+};
+
+/// An entry of a [`Map`], which might be vacant or occupied. Created by
+/// [`Map::entry`]. Resolving the entry yields a guarded reference to the value
+/// that ended up stored, whether it was found, modified or inserted.
+///
+/// Since values are shared between threads, they cannot be modified in place.
+/// Instead, [`and_modify`](Entry::and_modify) computes a new value from the
+/// found one. Concurrent modifications of the [`Map`] may cause the closures to
+/// be called again, but the default value is generated at most once.
+///
+/// # Example
+/// ```rust
+/// use lock_freedom::map::Map;
+///
+/// let map = Map::new();
+/// for word in ["a", "b", "a"] {
+///     map.entry(word).and_modify(|count| count + 1).or_insert(1);
+/// }
+/// assert_eq!(*map.get("a").unwrap().val(), 2);
+/// assert_eq!(*map.get("b").unwrap().val(), 1);
+/// ```
+pub struct Entry<'map, K, V, H, M = fn(&V) -> V>
+where
+    K: 'map,
+    V: 'map,
+    H: 'map,
+{
+    map: &'map Map<K, V, H>,
+    key: K,
+    modify: Option<M>,
+}
+
+impl<'map, K, V, H> Entry<'map, K, V, H> {
+    pub(super) fn new(map: &'map Map<K, V, H>, key: K) -> Self {
+        Self {
+            map,
+            key,
+            modify: None,
+        }
+    }
+}
+
+impl<'map, K, V, H, M> Entry<'map, K, V, H, M>
+where
+    H: BuildHasher,
+    K: Hash + Eq,
+    M: FnMut(&V) -> V,
+{
+    /// The key of this entry.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Makes the entry replace a found value by the one computed from it by the
+    /// given closure. Calling this method again replaces the closure.
+    pub fn and_modify<F>(self, modify: F) -> Entry<'map, K, V, H, F>
+    where
+        F: FnMut(&V) -> V,
+    {
+        Entry {
+            map: self.map,
+            key: self.key,
+            modify: Some(modify),
+        }
+    }
+
+    /// Resolves the entry, inserting the given value if no value was found.
+    pub fn or_insert(self, val: V) -> ReadGuard<'map, K, V> {
+        self.or_insert_with(|| val)
+    }
+
+    /// Resolves the entry, inserting the value generated by the given closure
+    /// if no value was found. The closure is called at most once.
+    pub fn or_insert_with<D>(self, default: D) -> ReadGuard<'map, K, V>
+    where
+        D: FnOnce() -> V,
+    {
+        let map = self.map;
+        let hash = map.hash_of(&self.key);
+        let pause = map.incin.get_unchecked().pause();
+        let inserter = InsertEntry::new(self.key, default, self.modify);
+        let inserted = inserter.raw();
+
+        // Safe because we paused properly.
+        let insertion = unsafe { map.top.insert(inserter, hash, map.incin.get_unchecked()) };
+
+        let pair = match insertion {
+            Insertion::Created => {
+                map.len.add(1);
+                inserted
+            }
+            Insertion::Updated(_) => inserted,
+            // The inserter only rejects the conditions when it found an entry
+            // and there is nothing to modify.
+            Insertion::Failed(inserter) => inserter.found().expect("rejected a vacant entry"),
+        };
+
+        // This is safe because the pair was either inserted or found during
+        // this pause. Entries are only deleted through the incinerator, so the
+        // pair stays valid while the guard keeps the pause.
+        ReadGuard::new(unsafe { &*pair.as_ptr() }, pause)
+    }
+
+    /// Resolves the entry, inserting the default value if no value was found.
+    pub fn or_default(self) -> ReadGuard<'map, K, V>
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+}
+
+impl<'map, K, V, H, M> fmt::Debug for Entry<'map, K, V, H, M>
+where
+    K: fmt::Debug,
+{
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Entry {{ key: {:?}, modify: {} }}",
+            self.key,
+            self.modify.is_some()
+        )
+    }
+}
@@ -12,21 +12,35 @@ use core::{
     ptr::NonNull,
 };
 
+/// Garbage of a collection handing out [`ReadGuard`]s and [`Removed`] entries.
+/// Both [`Map`](super::Map) and [`SkipMap`](crate::skiplist::SkipMap) share
+/// these guards, each with its own garbage.
+pub trait PairGarbage<K, V> {
+    /// Wraps a removed pair so it can be added to an incinerator.
+    fn pair(alloc: OwnedAlloc<(K, V)>) -> Self;
+}
+
+impl<K, V> PairGarbage<K, V> for Garbage<K, V> {
+    fn pair(alloc: OwnedAlloc<(K, V)>) -> Self {
+        Garbage::Pair(alloc)
+    }
+}
+
 /// A read-operation guard. This ensures no entry allocation is
 /// mutated or freed while potential reads are performed.
 #[derive(Debug)]
 #[allow(dead_code)]
-pub struct ReadGuard<'map, K, V>
+pub struct ReadGuard<'map, K, V, G = Garbage<K, V>>
 where
     K: 'map,
     V: 'map,
 {
     pair: &'map (K, V),
-    pause: Pause<'map, Garbage<K, V>>,
+    pause: Pause<'map, G>,
 }
 
-impl<'map, K, V> ReadGuard<'map, K, V> {
-    pub(super) fn new(pair: &'map (K, V), pause: Pause<'map, Garbage<K, V>>) -> Self {
+impl<'map, K, V, G> ReadGuard<'map, K, V, G> {
+    pub(crate) fn new(pair: &'map (K, V), pause: Pause<'map, G>) -> Self {
         Self { pair, pause }
     }
 
@@ -45,7 +59,7 @@ impl<'map, K, V> ReadGuard<'map, K, V> {
     }
 }
 
-impl<'map, K, V> Deref for ReadGuard<'map, K, V> {
+impl<'map, K, V, G> Deref for ReadGuard<'map, K, V, G> {
     type Target = (K, V);
 
     fn deref(&self) -> &Self::Target {
@@ -53,7 +67,7 @@ impl<'map, K, V> Deref for ReadGuard<'map, K, V> {
     }
 }
 
-impl<'map, K, V> PartialEq for ReadGuard<'map, K, V>
+impl<'map, K, V, G> PartialEq for ReadGuard<'map, K, V, G>
 where
     (K, V): PartialEq,
 {
@@ -62,7 +76,7 @@ where
     }
 }
 
-impl<'map, K, V> PartialEq<(K, V)> for ReadGuard<'map, K, V>
+impl<'map, K, V, G> PartialEq<(K, V)> for ReadGuard<'map, K, V, G>
 where
     (K, V): PartialEq,
 {
@@ -71,9 +85,9 @@ where
     }
 }
 
-impl<'map, K, V> Eq for ReadGuard<'map, K, V> where (K, V): Eq {}
+impl<'map, K, V, G> Eq for ReadGuard<'map, K, V, G> where (K, V): Eq {}
 
-impl<'map, K, V> PartialOrd for ReadGuard<'map, K, V>
+impl<'map, K, V, G> PartialOrd for ReadGuard<'map, K, V, G>
 where
     (K, V): PartialOrd,
 {
@@ -82,7 +96,7 @@ where
     }
 }
 
-impl<'map, K, V> PartialOrd<(K, V)> for ReadGuard<'map, K, V>
+impl<'map, K, V, G> PartialOrd<(K, V)> for ReadGuard<'map, K, V, G>
 where
     (K, V): PartialOrd,
 {
@@ -91,7 +105,7 @@ where
     }
 }
 
-impl<'map, K, V> Ord for ReadGuard<'map, K, V>
+impl<'map, K, V, G> Ord for ReadGuard<'map, K, V, G>
 where
     (K, V): Ord,
 {
@@ -100,7 +114,7 @@ where
     }
 }
 
-impl<'map, K, V> Hash for ReadGuard<'map, K, V>
+impl<'map, K, V, G> Hash for ReadGuard<'map, K, V, G>
 where
     (K, V): Hash,
 {
@@ -112,26 +126,26 @@ where
     }
 }
 
-impl<'map, K, V> AsRef<(K, V)> for ReadGuard<'map, K, V> {
+impl<'map, K, V, G> AsRef<(K, V)> for ReadGuard<'map, K, V, G> {
     fn as_ref(&self) -> &(K, V) {
         self
     }
 }
 
-impl<'map, K, V> Borrow<(K, V)> for ReadGuard<'map, K, V> {
+impl<'map, K, V, G> Borrow<(K, V)> for ReadGuard<'map, K, V, G> {
     fn borrow(&self) -> &(K, V) {
         self
     }
 }
 
-unsafe impl<'map, K, V> Send for ReadGuard<'map, K, V>
+unsafe impl<'map, K, V, G> Send for ReadGuard<'map, K, V, G>
 where
     K: Send,
     V: Send,
 {
 }
 
-unsafe impl<'map, K, V> Sync for ReadGuard<'map, K, V>
+unsafe impl<'map, K, V, G> Sync for ReadGuard<'map, K, V, G>
 where
     K: Sync,
     V: Sync,
@@ -142,21 +156,28 @@ where
 /// removed. It can also be inserted on another [`Map`](super::Map), but only if
 /// either the [`Map`](super::Map) is dropped, there are no sensitive reads
 /// running on that [`Map`](super::Map) or both [`Map`](super::Map)s share the
-/// same incinerator.
-pub struct Removed<K, V> {
+/// same incinerator. Entries removed from a
+/// [`SkipMap`](crate::skiplist::SkipMap) cannot be reinserted.
+pub struct Removed<K, V, G = Garbage<K, V>>
+where
+    G: PairGarbage<K, V>,
+{
     nnptr: NonNull<(K, V)>,
-    origin: Weak<Incinerator<Garbage<K, V>>>,
+    origin: Weak<Incinerator<G>>,
 }
 
-impl<K, V> Removed<K, V> {
-    pub(super) fn new(alloc: OwnedAlloc<(K, V)>, origin: &Arc<Incinerator<Garbage<K, V>>>) -> Self {
+impl<K, V, G> Removed<K, V, G>
+where
+    G: PairGarbage<K, V>,
+{
+    pub(crate) fn new(alloc: OwnedAlloc<(K, V)>, origin: &Arc<Incinerator<G>>) -> Self {
         Self {
             nnptr: alloc.into_raw(),
             origin: Arc::downgrade(origin),
         }
     }
 
-    pub(super) fn into_alloc(mut this: Self) -> OwnedAlloc<(K, V)> {
+    pub(crate) fn into_alloc(mut this: Self) -> OwnedAlloc<(K, V)> {
         // It is safe because we own the allocation.
         let alloc = unsafe { OwnedAlloc::from_raw(this.nnptr) };
         // There is no other way of dropping the weak and forgetting ourselves.
@@ -166,11 +187,11 @@ impl<K, V> Removed<K, V> {
         alloc
     }
 
-    pub(super) fn raw(this: &Self) -> NonNull<(K, V)> {
+    pub(crate) fn raw(this: &Self) -> NonNull<(K, V)> {
         this.nnptr
     }
 
-    pub(super) fn is_usable_by(this: &mut Self, origin: &Arc<Incinerator<Garbage<K, V>>>) -> bool {
+    pub(crate) fn is_usable_by(this: &mut Self, origin: &Arc<Incinerator<G>>) -> bool {
         match &this.origin.upgrade() {
             None => true,
             Some(arc) if Arc::ptr_eq(arc, origin) => true,
@@ -200,8 +221,8 @@ impl<K, V> Removed<K, V> {
     }
 
     /// Tries to acquire a mutable reference to the pair. Succeeds only if
-    /// either the original [`Map`](super::Map) was dropped or no sensitive
-    /// reads are being performed.
+    /// either the original map was dropped or no sensitive reads are being
+    /// performed.
     pub fn try_as_mut(this: &mut Self) -> Option<&mut (K, V)> {
         let success = match this.origin.upgrade() {
             None => true,
@@ -224,8 +245,7 @@ impl<K, V> Removed<K, V> {
     }
 
     /// Tries to convert this wrapper into the pair. Succeeds only if either the
-    /// original [`Map`](super::Map) was dropped or no sensitive reads are being
-    /// performed.
+    /// original map was dropped or no sensitive reads are being performed.
     pub fn try_into(this: Self) -> Result<(K, V), Self> {
         let success = match this.origin.upgrade() {
             None => true,
@@ -243,17 +263,23 @@ impl<K, V> Removed<K, V> {
     }
 }
 
-impl<K, V> Drop for Removed<K, V> {
+impl<K, V, G> Drop for Removed<K, V, G>
+where
+    G: PairGarbage<K, V>,
+{
     fn drop(&mut self) {
         // We own the allocation. This must be safe.
         let alloc = unsafe { OwnedAlloc::from_raw(self.nnptr) };
         if let Some(incin) = self.origin.upgrade() {
-            incin.add(Garbage::Pair(alloc))
+            incin.add(G::pair(alloc))
         }
     }
 }
 
-impl<K, V> Deref for Removed<K, V> {
+impl<K, V, G> Deref for Removed<K, V, G>
+where
+    G: PairGarbage<K, V>,
+{
     type Target = (K, V);
 
     fn deref(&self) -> &Self::Target {
@@ -262,8 +288,9 @@ impl<K, V> Deref for Removed<K, V> {
     }
 }
 
-impl<K, V> fmt::Debug for Removed<K, V>
+impl<K, V, G> fmt::Debug for Removed<K, V, G>
 where
+    G: PairGarbage<K, V>,
     (K, V): fmt::Debug,
 {
     fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
@@ -271,8 +298,9 @@ where
     }
 }
 
-impl<K, V> PartialEq for Removed<K, V>
+impl<K, V, G> PartialEq for Removed<K, V, G>
 where
+    G: PairGarbage<K, V>,
     (K, V): PartialEq,
 {
     fn eq(&self, other: &Self) -> bool {
@@ -280,8 +308,9 @@ where
     }
 }
 
-impl<K, V> PartialEq<(K, V)> for Removed<K, V>
+impl<K, V, G> PartialEq<(K, V)> for Removed<K, V, G>
 where
+    G: PairGarbage<K, V>,
     (K, V): PartialEq,
 {
     fn eq(&self, other: &(K, V)) -> bool {
@@ -289,10 +318,16 @@ where
     }
 }
 
-impl<K, V> Eq for Removed<K, V> where (K, V): Eq {}
+impl<K, V, G> Eq for Removed<K, V, G>
+where
+    G: PairGarbage<K, V>,
+    (K, V): Eq,
+{
+}
 
-impl<K, V> PartialOrd for Removed<K, V>
+impl<K, V, G> PartialOrd for Removed<K, V, G>
 where
+    G: PairGarbage<K, V>,
     (K, V): PartialOrd,
 {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
@@ -300,8 +335,9 @@ where
     }
 }
 
-impl<K, V> PartialOrd<(K, V)> for Removed<K, V>
+impl<K, V, G> PartialOrd<(K, V)> for Removed<K, V, G>
 where
+    G: PairGarbage<K, V>,
     (K, V): PartialOrd,
 {
     fn partial_cmp(&self, other: &(K, V)) -> Option<Ordering> {
@@ -309,8 +345,9 @@ where
     }
 }
 
-impl<K, V> Ord for Removed<K, V>
+impl<K, V, G> Ord for Removed<K, V, G>
 where
+    G: PairGarbage<K, V>,
     (K, V): Ord,
 {
     fn cmp(&self, other: &Self) -> Ordering {
@@ -318,8 +355,9 @@ where
     }
 }
 
-impl<K, V> Hash for Removed<K, V>
+impl<K, V, G> Hash for Removed<K, V, G>
 where
+    G: PairGarbage<K, V>,
     (K, V): Hash,
 {
     fn hash<H>(&self, hasher: &mut H)
@@ -330,27 +368,35 @@ where
     }
 }
 
-impl<K, V> AsRef<(K, V)> for Removed<K, V> {
+impl<K, V, G> AsRef<(K, V)> for Removed<K, V, G>
+where
+    G: PairGarbage<K, V>,
+{
     fn as_ref(&self) -> &(K, V) {
         self
     }
 }
 
-impl<K, V> Borrow<(K, V)> for Removed<K, V> {
+impl<K, V, G> Borrow<(K, V)> for Removed<K, V, G>
+where
+    G: PairGarbage<K, V>,
+{
     fn borrow(&self) -> &(K, V) {
         self
     }
 }
 
-unsafe impl<K, V> Send for Removed<K, V>
+unsafe impl<K, V, G> Send for Removed<K, V, G>
 where
+    G: PairGarbage<K, V>,
     K: Send,
     V: Send,
 {
 }
 
-unsafe impl<K, V> Sync for Removed<K, V>
+unsafe impl<K, V, G> Sync for Removed<K, V, G>
 where
+    G: PairGarbage<K, V>,
     K: Sync,
     V: Sync,
 {
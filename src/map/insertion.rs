@@ -275,3 +275,161 @@ where
         forget(Removed::into_alloc(self.removed));
     }
 }
+
+// An inserter which resolves an entry: the found value is either kept or
+// modified, and a default value is only inserted if nothing was found. The
+// default value is kept aside when the conditions change, so it is generated
+// at most once, no matter how many times the insertion is retried.
+pub struct InsertEntry<D, M, K, V>
+where
+    D: FnOnce() -> V,
+    M: FnMut(&V) -> V,
+{
+    default: Option<D>,
+    modify: Option<M>,
+    // A default value taken back from the allocation.
+    stash: Option<V>,
+    nnptr: NonNull<(K, V)>,
+    val: EntryVal,
+    found: Option<NonNull<(K, V)>>,
+}
+
+// What the value of an `InsertEntry` allocation is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryVal {
+    Uninit,
+    Default,
+    Modified,
+}
+
+impl<D, M, K, V> InsertEntry<D, M, K, V>
+where
+    D: FnOnce() -> V,
+    M: FnMut(&V) -> V,
+{
+    pub fn new(key: K, default: D, modify: Option<M>) -> Self {
+        Self {
+            default: Some(default),
+            modify,
+            stash: None,
+            // Just like in `InsertNew`, only the key is initialized. We handle
+            // it in drop through the field `val`.
+            nnptr: unsafe {
+                let alloc =
+                    UninitAlloc::new().init_in_place(|(key_mem, _)| (key_mem as *mut K).write(key));
+                alloc.into_raw()
+            },
+            val: EntryVal::Uninit,
+            found: None,
+        }
+    }
+
+    // The allocation to be inserted. Its value is only initialized if the
+    // inserter accepted the last conditions.
+    pub fn raw(&self) -> NonNull<(K, V)> {
+        self.nnptr
+    }
+
+    // The pair found in the last conditions, if any.
+    pub fn found(&self) -> Option<NonNull<(K, V)>> {
+        self.found
+    }
+
+    fn take_val(&mut self) -> Option<V> {
+        if self.val == EntryVal::Uninit {
+            return None;
+        }
+
+        self.val = EntryVal::Uninit;
+        // Safe because we checked for the initialization of the value and we
+        // updated it too. This allocation is owned by us.
+        Some(unsafe { (&self.nnptr.as_ref().1 as *const V).read() })
+    }
+
+    fn put_val(&mut self, new_val: V, state: EntryVal) {
+        debug_assert!(self.val == EntryVal::Uninit);
+        // Safe because the value is uninitialized, and this allocation is
+        // owned by us.
+        unsafe { (&mut self.nnptr.as_mut().1 as *mut V).write(new_val) };
+        self.val = state;
+    }
+}
+
+impl<D, M, K, V> Drop for InsertEntry<D, M, K, V>
+where
+    D: FnOnce() -> V,
+    M: FnMut(&V) -> V,
+{
+    fn drop(&mut self) {
+        // Callers should forget the inserter if they are using the pointer.
+        self.take_val();
+        // Safe because the value is uninitialized now, and the key is always
+        // initialized.
+        unsafe {
+            {
+                let (key, _) = self.nnptr.as_mut();
+                (key as *mut K).drop_in_place();
+            }
+            UninitAlloc::from_raw(self.nnptr);
+        }
+    }
+}
+
+impl<D, M, K, V> Inserter<K, V> for InsertEntry<D, M, K, V>
+where
+    D: FnOnce() -> V,
+    M: FnMut(&V) -> V,
+{
+    fn input(&mut self, found: Option<&(K, V)>) {
+        self.found = found.map(NonNull::from);
+
+        // The value computed for the previous conditions is outdated, but a
+        // default value can be reused.
+        let was_default = self.val == EntryVal::Default;
+        if let Some(val) = self.take_val() {
+            if was_default {
+                self.stash = Some(val);
+            }
+        }
+
+        match (found, &mut self.modify) {
+            (Some((_, stored)), Some(modify)) => {
+                let new_val = modify(stored);
+                self.put_val(new_val, EntryVal::Modified);
+            }
+
+            // Rejecting the conditions keeps the found entry.
+            (Some(_), None) => (),
+
+            (None, _) => {
+                let default = match self.stash.take() {
+                    Some(val) => val,
+                    None => (self.default.take().expect("default used once"))(),
+                };
+                self.put_val(default, EntryVal::Default);
+            }
+        }
+    }
+
+    fn pointer(&self) -> Option<NonNull<(K, V)>> {
+        match self.val {
+            EntryVal::Uninit => None,
+            _ => Some(self.nnptr),
+        }
+    }
+
+    fn key(&self) -> &K {
+        // This is safe. This allocation is owned by us.
+        let (key, _) = unsafe { self.nnptr.as_ref() };
+        key
+    }
+
+    fn take_pointer(mut self) {
+        // The allocation now belongs to the map, but the closures and the
+        // stashed value are still ours.
+        self.default.take();
+        self.modify.take();
+        self.stash.take();
+        forget(self);
+    }
+}
@@ -1,12 +1,12 @@
 use super::{
     bucket::{self, Bucket, Garbage},
     guard::ReadGuard,
-    table::Table,
+    table::{Root, Table},
 };
 use crate::incin::Pause;
 use crate::owned_alloc::OwnedAlloc;
 use alloc::vec::Vec;
-use core::{fmt, ptr::NonNull, sync::atomic::Ordering::*};
+use core::{fmt, ptr::NonNull};
 
 /// An iterator over key-vaue entries of a [`Map`](super::Map). The `Item` of
 /// this iterator is a [`ReadGuard`]. This iterator may be inconsistent, but
@@ -21,17 +21,26 @@ where
     V: 'map,
 {
     pause: Pause<'map, Garbage<K, V>>,
+    root: &'map Root<K, V>,
     tables: Vec<&'map Table<K, V>>,
     curr_table: Option<(&'map Table<K, V>, usize)>,
     cache: Vec<ReadGuard<'map, K, V>>,
 }
 
 impl<'map, K, V> Iter<'map, K, V> {
-    pub(super) fn new(pause: Pause<'map, Garbage<K, V>>, top: &'map Table<K, V>) -> Self {
+    pub(super) fn new(
+        pause: Pause<'map, Garbage<K, V>>,
+        root: &'map Root<K, V>,
+        top: &'map Table<K, V>,
+        slot: usize,
+    ) -> Self {
+        // Starting past the first slot of the top table skips the entries
+        // whose hashes fall in the slots before it.
         Self {
             pause,
+            root,
             tables: Vec::new(),
-            curr_table: Some((top, 0)),
+            curr_table: Some((top, slot)),
             cache: Vec::new(),
         }
     }
@@ -50,7 +59,9 @@ impl<'map, K, V> Iterator for Iter<'map, K, V> {
             // If the iterator was empty, let's try to get a new one from
             // another bucket.
             let (table, index) = self.curr_table?;
-            self.curr_table = match table.load_index(index, Acquire) {
+            // This is safe because the incinerator is paused.
+            let loaded = unsafe { table.load_index(index, self.root) };
+            self.curr_table = match loaded {
                 // If the pointer is null, simply go to the next element.
                 Some(ptr) if ptr.is_null() => Some((table, index + 1)),
 
@@ -142,8 +153,8 @@ impl<K, V> Iterator for IntoIter<K, V> {
 
             // If the iterator was empty, let's try to get a new one from
             // another bucket.
-            let (table, index) = self.curr_table.take()?;
-            self.curr_table = match table.load_index(index, Relaxed) {
+            let (mut table, index) = self.curr_table.take()?;
+            self.curr_table = match table.load_index_mut(index) {
                 // If the pointer is null, simply go to the next element.
                 Some(ptr) if ptr.is_null() => Some((table, index + 1)),
 
@@ -255,7 +266,7 @@ impl<'map, K, V> Iterator for IterMut<'map, K, V> {
             // If the iterator was empty, let's try to get a new one from
             // another bucket.
             let (table, index) = self.curr_table.take()?;
-            self.curr_table = match table.load_index(index, Relaxed) {
+            self.curr_table = match table.load_index_mut(index) {
                 // If the pointer is null, simply go to the next element.
                 Some(ptr) if ptr.is_null() => Some((table, index + 1)),
 
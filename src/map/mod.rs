@@ -1,40 +1,57 @@
 mod bucket;
+mod builder;
+mod entry;
 mod guard;
 mod insertion;
 mod iter;
+mod snapshot;
+mod stats;
 mod table;
+mod watch;
 
 pub use self::{
+    builder::{MapBuilder, DEFAULT_BITS, MAX_BITS},
+    entry::Entry,
     guard::{ReadGuard, Removed},
     insertion::{Insertion, Preview},
     iter::{IntoIter, Iter, IterMut},
+    snapshot::MapSnapshot,
+    stats::MapStats,
+    watch::Event,
 };
-use alloc::vec::Vec;
+
+pub(crate) use self::guard::PairGarbage;
 
 use self::{
     bucket::{Bucket, Garbage},
     insertion::{InsertNew, Reinsert},
-    table::Table,
+    table::{Root, Table},
+    watch::Watcher,
 };
+use crate::channel::mpsc;
+use crate::count::AtomicCounter;
 use crate::owned_alloc::OwnedAlloc;
 use crate::ptr::check_null_align;
 use core::{
     borrow::Borrow,
+    cell::Cell,
     fmt,
     hash::{BuildHasher, Hash, Hasher},
     iter::FromIterator,
-    mem,
+    ptr::{self, NonNull},
 };
 use std::collections::hash_map::RandomState;
 
 /// A lock-free map. Implemented using multi-level hash-tables (in a tree
-/// fashion) with ordered buckets.
+/// fashion) with buckets of entries with colliding hashes. Keys only need
+/// [`Hash`] and [`Eq`].
 ///
 /// # Design
 /// In order to implement this map, we shall fix a constant named `BITS`, which
-/// should be smaller than the number of bits in the hash (and not 0). We chose
-/// `8` for it. Now, we define a table structure: an array of nodes with length
-/// `1 << BITS` (`256` in this case).
+/// should be smaller than the number of bits in the hash (and not 0). It is
+/// `8` by default, and it can be configured through [`MapBuilder::bits`]. Now,
+/// we define a table structure: an array of nodes with length `1 << BITS`
+/// (`256` by default).
 ///
 /// For inserting, we take the first `BITS` bits of the hash. Now, we verify
 /// the node. If it is empty, insert a new bucket with our entry (a leaf of the
@@ -46,25 +63,54 @@ use std::collections::hash_map::RandomState;
 /// create a sub-table, insert the old leaf into the new sub-table, and insert
 /// our pair after.
 ///
-/// Entries in a bucket are a single linked list ordered by key. The ordering
-/// of the list is because of possible race conditions if e.g. new nodes were
-/// always inserted at end. And if a bucket is detected to be empty, the
-/// table will be requested to delete the bucket.
+/// If enabled through [`MapBuilder::compressed`], sub-tables are created
+/// compressed: a bitmap of the occupied nodes plus an array with only those
+/// nodes, so sparse levels use memory proportional to their occupancy. Compressed tables are never modified in place. Instead,
+/// they are copied with the modification, the copy replaces them in the closest
+/// full table, and the old copy is destroyed through the incinerator. A
+/// compressed table which gets too many nodes is copied into a full table.
+///
+/// Removals compact sub-tables as they go. A compressed copy left with no
+/// nodes is dropped, and one left with a single bucket is replaced by the
+/// bucket. A full sub-table which a removal leaves sparse (or, without
+/// compression, empty or with a single bucket) is frozen: each of its nodes is
+/// marked so that it can no longer be changed, and any operation finding a
+/// marked node helps replacing the table by a compressed copy, by nothing or by
+/// its only bucket before trying again. Replaced tables are
+/// destroyed through the incinerator, so shared maps give memory back without
+/// [`optimize_space`](Map::optimize_space).
+///
+/// Buckets are never modified in place either. They hold their entries in no
+/// particular order, and any insertion or removal copies the bucket with the
+/// change and replaces the old copy in its table, which makes any concurrent
+/// change to the same bucket fail and search again. Thus, a key is never
+/// inserted twice. A removal which would leave a bucket empty deletes the
+/// bucket instead.
+///
+/// Snapshots are taken as in Ctrie: every full table is tagged with a
+/// generation, and taking a snapshot freezes the top table and replaces it by a
+/// copy with a new generation, which shares all the sub-tables. The snapshot
+/// keeps reading the old top table. Whenever an operation enters a sub-table
+/// of an older generation, it first replaces the sub-table by a copy with the
+/// current generation, so the snapshot's tables are never changed. Changes
+/// to full sub-tables are first installed as descriptors, which only take
+/// effect if the generation of the top table did not change in the meantime.
 ///
 /// For searching, in a similar way, the hash is shifted and sub-tables are
 /// entered until either a node is empty or a leaf is found. If the hash of the
 /// leaf's bucket is equal to our hash, we search for the entry into the bucket.
-/// Because the bucket is ordered, we may know the entry is not present with
-/// ease.
+/// Since buckets only hold entries whose whole hashes collide, they are
+/// usually short.
 ///
 /// Because of limitation of sharing in concurrent contexts, we do return plain
 /// references to the entries, neither allow the user to move out removed
 /// values, as they must be deinitialized correctly. Instead, we return guarded
 /// references to the entries and wrappers over removed entries.
 pub struct Map<K, V, H = RandomState> {
-    top: OwnedAlloc<Table<K, V>>,
+    top: Root<K, V>,
     incin: SharedIncin<K, V>,
     builder: H,
+    len: AtomicCounter,
 }
 
 impl<K, V> Map<K, V> {
@@ -87,12 +133,53 @@ impl<K, V, H> Map<K, V, H> {
         self.into_iter()
     }
 
+    // Creates an iterator over the entries whose hashes select the given slot
+    // of the top table, or a later one. The slot is selected by the lowest
+    // `bits` bits of the hash.
+    pub(crate) fn iter_from(&self, slot: usize) -> Iter<'_, K, V> {
+        let pause = self.incin.get_unchecked().pause();
+        // Safe because we paused properly.
+        let top = unsafe { self.top.load() };
+        Iter::new(pause, &self.top, top, slot)
+    }
+
     /// Creates an iterator over the key-value entries, with a mutable reference
     /// to the value.
     pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
         self.into_iter()
     }
 
+    /// Takes a snapshot of the map: a read-only view of the entries present
+    /// at the time this method is called, which later modifications do not
+    /// affect. This does not copy the entries, and it takes time proportional
+    /// to the size of the top-level table only: the snapshot shares every
+    /// other table with the map, which copies them before modifying them.
+    ///
+    /// The snapshot keeps the incinerator of the map paused while it is
+    /// alive, so no removed entry nor replaced table of the map, or of maps
+    /// sharing its incinerator, is destroyed until the snapshot is dropped.
+    /// Long-lived snapshots of a map under writes should be
+    /// [`snapshot_mut`](Map::snapshot_mut) instead, which does not keep
+    /// anything paused.
+    pub fn snapshot(&self) -> MapSnapshot<'_, K, V, H> {
+        let pause = self.incin.get_unchecked().pause();
+        // Safe because we paused properly, and the snapshot keeps the pause.
+        let top = unsafe { self.top.snapshot(self.incin.get_unchecked()) };
+        MapSnapshot::new(self, top, pause)
+    }
+
+    /// The number of hash bits consumed by each table of this map. Each table
+    /// has `1 << bits` slots. See [`MapBuilder::bits`].
+    pub fn bits(&self) -> u32 {
+        self.top.bits()
+    }
+
+    /// Tests if sparse sub-tables of this map are compressed. See
+    /// [`MapBuilder::compressed`].
+    pub fn is_compressed(&self) -> bool {
+        self.top.is_compressed()
+    }
+
     /// Tries to optimize space by removing unnecessary tables *without removing
     /// any entry*. This method might also clear delayed resource destruction.
     /// This method cannot be performed in a shared context.
@@ -101,18 +188,67 @@ impl<K, V, H> Map<K, V, H> {
         self.top.optimize_space();
     }
 
+    /// The approximate number of entries in the map. It is maintained by
+    /// insertions and removals, so concurrent modifications may be partially
+    /// observed.
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    /// Tests if the map has no entries. Unlike `len`, this does not rely on a
+    /// counter: it scans the tables, stopping at the first entry found.
+    pub fn is_empty(&self) -> bool {
+        let _pause = self.incin.get_unchecked().pause();
+        // Safe because we paused properly.
+        unsafe { self.top.load().is_empty(&self.top) }
+    }
+
+    /// Walks the tables of the map and gathers structural statistics, such as
+    /// the depth of the tables and the length of the buckets. This takes time
+    /// proportional to the size of the map.
+    pub fn stats(&self) -> MapStats {
+        let _pause = self.incin.get_unchecked().pause();
+        // Safe because we paused properly.
+        unsafe { self.top.load().stats(&self.top) }
+    }
+
+    // Gives the map its own copies of the tables and entries it shares with
+    // maps created by `snapshot_mut`, so that they can be modified in place.
+    fn unshare(&mut self) {
+        let _pause = self.incin.get_unchecked().pause();
+        // Safe because we paused properly.
+        unsafe { self.top.unshare() }
+    }
+
     /// Removes all entries. This method might also clear delayed resource
     /// destruction. This method cannot be performed in a shared context.
     pub fn clear(&mut self) {
+        if self.top.is_watched() {
+            let incin = self.incin.get_unchecked();
+            let _pause = incin.pause();
+            // Safe because we paused properly, and the top table is the node
+            // of the lower bit set.
+            unsafe {
+                let top = self.top.load() as *const Table<K, V> as usize | 1;
+                self.top.notify_removed(top as *mut (), incin);
+            }
+        }
+
         self.incin.clear();
-        let mut tables = Vec::new();
-        self.top.clear(&mut tables);
+        self.len.reset();
+        self.top.clear();
+    }
 
-        while let Some(mut table) = tables.pop() {
-            // This is safe because we won't be using these tables anymore. We
-            // won't load its nodes' contents.
-            unsafe { table.free_nodes(&mut tables) }
-        }
+    /// Removes all entries, just like [`Map::clear`], but in a shared context.
+    /// The top-level table is replaced by an empty one and sent to the
+    /// incinerator, which destroys it along with the old tables and entries
+    /// once no thread is reading them. Operations running concurrently with
+    /// this either take effect before it, and are cleared, or after it.
+    pub fn clear_shared(&self) {
+        let _pause = self.incin.get_unchecked().pause();
+        // Safe because we paused properly.
+        let removed = unsafe { self.top.clear_shared(self.incin.get_unchecked()) };
+        self.len.sub(removed);
     }
 }
 
@@ -128,10 +264,24 @@ where
     /// Creates the [`Map`] using the given hasher builder and shared
     /// incinerator.
     pub fn with_hasher_and_incin(builder: H, incin: SharedIncin<K, V>) -> Self {
+        Self::with_builder_and_incin(MapBuilder::new().hasher(builder), incin)
+    }
+
+    /// Creates the [`Map`] using the configuration of the given
+    /// [`MapBuilder`].
+    pub fn with_builder(builder: MapBuilder<H>) -> Self {
+        Self::with_builder_and_incin(builder, SharedIncin::new())
+    }
+
+    /// Creates the [`Map`] using the configuration of the given [`MapBuilder`]
+    /// and the given shared incinerator.
+    pub fn with_builder_and_incin(builder: MapBuilder<H>, incin: SharedIncin<K, V>) -> Self {
+        let (bits, compressed, builder) = builder.into_parts();
         Self {
-            top: Table::new_alloc(),
+            top: Root::new(bits, compressed),
             incin,
             builder,
+            len: AtomicCounter::new(),
         }
     }
 
@@ -149,40 +299,47 @@ where
     /// is a guarded reference. Guarded to ensure no thread deallocates the
     /// allocation for the entry while it is being used. The method accepts
     /// a type resulted from borrowing the stored key. This method will only
-    /// work correctly if [`Hash`] and [`Ord`] are implemented in the same way
+    /// work correctly if [`Hash`] and [`Eq`] are implemented in the same way
     /// for the borrowed type and the stored type. If the entry was not
     /// found, [`None`] is returned.
     pub fn get<'map, Q>(&'map self, key: &Q) -> Option<ReadGuard<'map, K, V>>
     where
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Eq,
         K: Borrow<Q>,
     {
         let hash = self.hash_of(key);
-        let pause = self.incin.get_unchecked().pause();
-        // Safe because we paused properly.
-        unsafe { self.top.get(key, hash, pause) }
+        self.raw_get(hash, |stored| stored.borrow() == key)
     }
 
     /// Inserts unconditionally the given key and value. If there was a
     /// previously stored value, it is returned.
     pub fn insert(&self, key: K, val: V) -> Option<Removed<K, V>>
     where
-        K: Hash + Ord,
+        K: Hash + Eq,
     {
-        let pause = self.incin.get_unchecked().pause();
         let hash = self.hash_of(&key);
+        self.raw_insert(hash, key, val)
+    }
+
+    fn raw_insert(&self, hash: u64, key: K, val: V) -> Option<Removed<K, V>>
+    where
+        K: Hash + Eq,
+    {
+        let _pause = self.incin.get_unchecked().pause();
         // Safe because we paused properly.
         let insertion = unsafe {
             self.top.insert(
                 InsertNew::with_pair(|_, _, _| Preview::Keep, (key, val)),
                 hash,
-                &pause,
                 self.incin.get_unchecked(),
             )
         };
 
         match insertion {
-            Insertion::Created => None,
+            Insertion::Created => {
+                self.len.add(1);
+                None
+            }
             Insertion::Updated(old) => Some(old),
             Insertion::Failed(_) => unreachable!(),
         }
@@ -202,28 +359,53 @@ where
     /// insertion now".
     pub fn insert_with<F>(&self, key: K, interactive: F) -> Insertion<K, V, (K, Option<V>)>
     where
-        K: Hash + Ord,
+        K: Hash + Eq,
         F: FnMut(&K, Option<&mut V>, Option<&(K, V)>) -> Preview<V>,
     {
         let hash = self.hash_of(&key);
-        let pause = self.incin.get_unchecked().pause();
+        self.raw_insert_with(hash, key, interactive)
+    }
+
+    pub(crate) fn raw_insert_with<F>(
+        &self,
+        hash: u64,
+        key: K,
+        interactive: F,
+    ) -> Insertion<K, V, (K, Option<V>)>
+    where
+        K: Hash + Eq,
+        F: FnMut(&K, Option<&mut V>, Option<&(K, V)>) -> Preview<V>,
+    {
+        let _pause = self.incin.get_unchecked().pause();
         // Safe because we paused properly.
         let insertion = unsafe {
             self.top.insert(
                 InsertNew::with_key(interactive, key),
                 hash,
-                &pause,
                 self.incin.get_unchecked(),
             )
         };
 
         match insertion {
-            Insertion::Created => Insertion::Created,
+            Insertion::Created => {
+                self.len.add(1);
+                Insertion::Created
+            }
             Insertion::Updated(old) => Insertion::Updated(old),
             Insertion::Failed(inserter) => Insertion::Failed(inserter.into_pair()),
         }
     }
 
+    /// Gets the entry identified by the given key, to be resolved later with
+    /// methods such as [`Entry::or_insert`] and [`Entry::and_modify`]. Nothing
+    /// is looked up nor inserted until the entry is resolved.
+    pub fn entry(&self, key: K) -> Entry<'_, K, V, H>
+    where
+        K: Hash + Eq,
+    {
+        Entry::new(self, key)
+    }
+
     /// Reinserts a previously removed entry. The entry must have been either:
     ///
     /// 1. Removed from any [`Map`] using the same [`SharedIncin`] as this
@@ -235,7 +417,7 @@ where
     /// Otherwise, insertion cannot fail.
     pub fn reinsert(&self, mut removed: Removed<K, V>) -> Insertion<K, V, Removed<K, V>>
     where
-        K: Hash + Ord,
+        K: Hash + Eq,
     {
         if !Removed::is_usable_by(&mut removed, self.incin.get_unchecked()) {
             return Insertion::Failed(removed);
@@ -243,19 +425,21 @@ where
 
         let hash = self.hash_of(removed.key());
 
-        let pause = self.incin.get_unchecked().pause();
+        let _pause = self.incin.get_unchecked().pause();
         // Safe because we paused properly.
         let insertion = unsafe {
             self.top.insert(
                 Reinsert::new(|_, _| true, removed),
                 hash,
-                &pause,
                 self.incin.get_unchecked(),
             )
         };
 
         match insertion {
-            Insertion::Created => Insertion::Created,
+            Insertion::Created => {
+                self.len.add(1);
+                Insertion::Created
+            }
             Insertion::Updated(old) => Insertion::Updated(old),
             Insertion::Failed(_) => unreachable!(),
         }
@@ -286,7 +470,7 @@ where
         interactive: F,
     ) -> Insertion<K, V, Removed<K, V>>
     where
-        K: Hash + Ord,
+        K: Hash + Eq,
         F: FnMut(&(K, V), Option<&(K, V)>) -> bool,
     {
         if !Removed::is_usable_by(&mut removed, self.incin.get_unchecked()) {
@@ -295,19 +479,21 @@ where
 
         let hash = self.hash_of(removed.key());
 
-        let pause = self.incin.get_unchecked().pause();
+        let _pause = self.incin.get_unchecked().pause();
         // Safe because we paused properly.
         let insertion = unsafe {
             self.top.insert(
                 Reinsert::new(interactive, removed),
                 hash,
-                &pause,
                 self.incin.get_unchecked(),
             )
         };
 
         match insertion {
-            Insertion::Created => Insertion::Created,
+            Insertion::Created => {
+                self.len.add(1);
+                Insertion::Created
+            }
             Insertion::Updated(old) => Insertion::Updated(old),
             Insertion::Failed(inserter) => Insertion::Failed(inserter.into_removed()),
         }
@@ -315,12 +501,12 @@ where
 
     /// Removes unconditionally the entry identified by the given key. If no
     /// entry was found, [`None`] is returned. This method will only work
-    /// correctly if [`Hash`] and [`Ord`] are implemented in the same way for
+    /// correctly if [`Hash`] and [`Eq`] are implemented in the same way for
     /// the borrowed type and the stored type. If the entry was not found,
     /// `None` is returned.
     pub fn remove<Q>(&self, key: &Q) -> Option<Removed<K, V>>
     where
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Eq,
         K: Borrow<Q>,
     {
         self.remove_with(key, |_| true)
@@ -330,36 +516,284 @@ where
     /// is passed to validate the removal. The only argument passed to the
     /// closure is a reference to the found entry. The closure returns if the
     /// removal should go on. If no entry was found, `None` is returned. This
-    /// method will only work correctly if [`Hash`] and [`Ord`] are implemented
+    /// method will only work correctly if [`Hash`] and [`Eq`] are implemented
     /// in the same way for the borrowed type and the stored type. If the
     /// entry was not found, [`None`] is returned.
     pub fn remove_with<Q, F>(&self, key: &Q, interactive: F) -> Option<Removed<K, V>>
     where
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Eq,
         K: Borrow<Q>,
         F: FnMut(&(K, V)) -> bool,
     {
         let hash = self.hash_of(key);
-        let pause = self.incin.get_unchecked().pause();
+        self.raw_remove_with(hash, key, interactive)
+    }
+
+    fn raw_remove_with<Q, F>(&self, hash: u64, key: &Q, interactive: F) -> Option<Removed<K, V>>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+        F: FnMut(&(K, V)) -> bool,
+    {
+        let _pause = self.incin.get_unchecked().pause();
         // Safe because we paused properly.
-        unsafe {
+        let removed = unsafe {
             self.top
-                .remove(key, interactive, hash, &pause, self.incin.get_unchecked())
+                .remove(key, interactive, hash, self.incin.get_unchecked())
+        };
+
+        if removed.is_some() {
+            self.len.sub(1);
+        }
+        removed
+    }
+
+    /// Atomically replaces the value of the entry identified by the given key
+    /// with the one computed by the closure from the current value. If the
+    /// closure returns [`None`], the entry is removed instead. The closure
+    /// might get recalled many times due to concurrent modifications of the
+    /// [`Map`], but the result is only stored if the value it was computed
+    /// from is still there. The stored key is cloned into the updated entry.
+    /// If the entry was updated or removed, the old entry is returned. If no
+    /// entry was found, [`None`] is returned and the closure is not called.
+    pub fn update<Q, F>(&self, key: &Q, mut update: F) -> Option<Removed<K, V>>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q> + Hash + Eq + Clone,
+        F: FnMut(&V) -> Option<V>,
+    {
+        let key = self.get(key)?.key().clone();
+        self.compute(key, |found| found.and_then(&mut update))
+    }
+
+    /// Atomically computes the value of the entry identified by the given key
+    /// from the current value, if any. If the closure returns [`None`], the
+    /// entry is removed (or stays vacant). The closure might get recalled many
+    /// times due to concurrent modifications of the [`Map`], but the result is
+    /// only stored if the entry did not change since the closure saw it. If an
+    /// entry was updated or removed, the old entry is returned. Otherwise,
+    /// [`None`] is returned.
+    pub fn compute<F>(&self, key: K, mut compute: F) -> Option<Removed<K, V>>
+    where
+        K: Hash + Eq,
+        F: FnMut(Option<&V>) -> Option<V>,
+    {
+        let hash = self.hash_of(&key);
+        let _pause = self.incin.get_unchecked().pause();
+        let mut key = key;
+
+        loop {
+            // The stored pair the closure wants to remove, if any.
+            let rejected = Cell::new(None);
+            let inserter = InsertNew::with_key(
+                |_, _, found: Option<&(K, V)>| match compute(found.map(|(_, val)| val)) {
+                    Some(val) => Preview::New(val),
+                    None => {
+                        rejected.set(found.map(NonNull::from));
+                        Preview::Discard
+                    }
+                },
+                key,
+            );
+
+            // Safe because we paused properly.
+            let insertion = unsafe { self.top.insert(inserter, hash, self.incin.get_unchecked()) };
+
+            let inserter = match insertion {
+                Insertion::Created => {
+                    self.len.add(1);
+                    break None;
+                }
+                Insertion::Updated(old) => break Some(old),
+                Insertion::Failed(inserter) => inserter,
+            };
+
+            // The closure rejected either a vacant entry, which is left as it
+            // is, or a stored pair, which must be removed.
+            let expected = match rejected.get() {
+                Some(expected) => expected,
+                None => break None,
+            };
+            let (back, _) = inserter.into_pair();
+            key = back;
+
+            // Only the very pair the closure saw may be removed. Its address
+            // cannot be reused by another pair while we keep the pause, since
+            // pairs are only deleted through the incinerator. Safe because we
+            // paused properly.
+            let removed = unsafe {
+                self.top.remove(
+                    &key,
+                    |pair| ptr::eq(pair, expected.as_ptr()),
+                    hash,
+                    self.incin.get_unchecked(),
+                )
+            };
+
+            if removed.is_some() {
+                self.len.sub(1);
+                break removed;
+            }
+        }
+    }
+
+    /// Replaces the value of the entry identified by the given key with the new
+    /// value, but only if the stored value is equal to the expected one. On
+    /// success, the old entry is returned. If no entry was found, or its value
+    /// differs from the expected one, the new value is given back in the
+    /// error.
+    pub fn compare_and_swap(&self, key: K, expected: &V, new: V) -> Result<Removed<K, V>, V>
+    where
+        K: Hash + Eq,
+        V: PartialEq,
+    {
+        let hash = self.hash_of(&key);
+        let _pause = self.incin.get_unchecked().pause();
+        // The new pair is never shared unless the swap succeeds, so it can be
+        // given back on failure.
+        let removed = Removed::new(OwnedAlloc::new((key, new)), self.incin.get_unchecked());
+        let inserter = Reinsert::new(
+            |_, found: Option<&(K, V)>| matches!(found, Some((_, val)) if val == expected),
+            removed,
+        );
+
+        // Safe because we paused properly.
+        let insertion = unsafe { self.top.insert(inserter, hash, self.incin.get_unchecked()) };
+
+        match insertion {
+            Insertion::Updated(old) => Ok(old),
+            Insertion::Failed(inserter) => {
+                let ((_, new), _) = Removed::into_alloc(inserter.into_removed()).move_inner();
+                Err(new)
+            }
+            Insertion::Created => unreachable!(),
+        }
+    }
+
+    /// Removes the entry identified by the given key, but only if the stored
+    /// value is equal to the expected one. If no entry was found, or its value
+    /// differs from the expected one, [`None`] is returned. This method will
+    /// only work correctly if [`Hash`] and [`Eq`] are implemented in the same
+    /// way for the borrowed type and the stored type.
+    pub fn remove_if_eq<Q>(&self, key: &Q, expected: &V) -> Option<Removed<K, V>>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+        V: PartialEq,
+    {
+        self.remove_with(key, |(_, val)| val == expected)
+    }
+
+    /// Removes every entry for which the given closure returns `false`,
+    /// without requiring exclusive access. The closure is called once for
+    /// each entry found while walking the tables. An entry is only removed if
+    /// it was not replaced since the closure saw it. Entries inserted
+    /// concurrently might not be visited.
+    pub fn retain<F>(&self, mut keep: F)
+    where
+        K: Hash + Eq,
+        F: FnMut(&K, &V) -> bool,
+    {
+        for guard in self.iter() {
+            let (key, val) = &*guard;
+            if !keep(key, val) {
+                self.remove_with(key, |pair| ptr::eq(pair, &*guard));
+            }
+        }
+    }
+
+    /// Creates an independent [`Map`] with the entries present at the time
+    /// this method is called, just like a [`snapshot`](Map::snapshot) which
+    /// can be modified. Just like a snapshot, it takes time proportional to
+    /// the size of the top-level table only: both maps share every other
+    /// table, and the entries, until either modifies them.
+    ///
+    /// Since a [`Map`] owns its entries and hands them out as [`Removed`], a
+    /// shared entry is cloned when either map removes or replaces it, or
+    /// modifies the bucket holding it, and the clone is what is handed out.
+    /// [`iter_mut`](Map::iter_mut) and [`into_iter`](Map::into_iter) clone
+    /// every shared entry first. The new [`Map`] shares the incinerator of
+    /// this one, and it starts with the [`len`](Map::len) this one has,
+    /// which is only exact if no entry was inserted nor removed meanwhile.
+    pub fn snapshot_mut(&self) -> Self
+    where
+        K: Clone,
+        V: Clone,
+        H: Clone,
+    {
+        let incin = self.incin.get_unchecked();
+        let pause = incin.pause();
+        // Safe because we paused properly.
+        let frozen = unsafe { self.top.share(<(K, V)>::clone, incin) };
+        let len = AtomicCounter::new();
+        len.add(self.len.get());
+        drop(pause);
+
+        Self {
+            top: Root::with_frozen(frozen, self.top.is_compressed()),
+            incin: self.incin.clone(),
+            builder: self.builder.clone(),
+            len,
         }
     }
 
+    /// Creates a receiver of the [`Event`]s of the entries with the given key.
+    /// An event is sent after each successful insertion or removal of such an
+    /// entry, including those by [`clear`](Map::clear) and
+    /// [`clear_shared`](Map::clear_shared). Events of changes made by the same
+    /// thread arrive in order, but events of concurrent changes might arrive
+    /// in any order. Dropping the receiver stops the watch.
+    ///
+    /// While nobody watches a map, its changes only pay for checking that.
+    /// Otherwise, every change is checked against every watch.
+    pub fn watch(&self, key: &K) -> mpsc::Receiver<Event<K, V>>
+    where
+        K: Hash + Eq + Clone + Send,
+        V: Clone + Send,
+    {
+        let hash = self.hash_of(key);
+        self.add_watcher(Some((hash, key.clone())))
+    }
+
+    /// Creates a receiver of the [`Event`]s of every entry, just like
+    /// [`watch`](Map::watch) does for a single key.
+    pub fn watch_all(&self) -> mpsc::Receiver<Event<K, V>>
+    where
+        K: Eq + Clone + Send,
+        V: Clone + Send,
+    {
+        self.add_watcher(None)
+    }
+
+    fn add_watcher(&self, key: Option<(u64, K)>) -> mpsc::Receiver<Event<K, V>>
+    where
+        K: Eq + Clone + Send,
+        V: Clone + Send,
+    {
+        let (sender, receiver) = mpsc::create();
+        let incin = self.incin.get_unchecked();
+        let _pause = incin.pause();
+        // Safe because we paused properly.
+        unsafe { self.top.watch(Watcher::new(key, sender), incin) };
+        receiver
+    }
+
     /// Acts just like [`Extend::extend`] but does not require mutability.
     pub fn extend<I>(&self, iterable: I)
     where
         I: IntoIterator<Item = (K, V)>,
-        K: Hash + Ord,
+        K: Hash + Eq,
     {
         for (key, val) in iterable {
             self.insert(key, val);
         }
     }
 
-    fn hash_of<Q>(&self, key: &Q) -> u64
+    /// Computes the hash of the given key with the hasher builder of this
+    /// [`Map`]. The hash can be passed to [`get_hashed`](Map::get_hashed),
+    /// [`insert_hashed`](Map::insert_hashed) and the like, so that a key used
+    /// several times is only hashed once.
+    pub fn hash_of<Q>(&self, key: &Q) -> u64
     where
         Q: ?Sized + Hash,
     {
@@ -367,6 +801,80 @@ where
         key.hash(&mut hasher);
         hasher.finish()
     }
+
+    /// Searches for the entry identified by the given key, just like
+    /// [`get`](Map::get), but using the given hash instead of hashing the key
+    /// again. The hash must be the one [`hash_of`](Map::hash_of) computes for
+    /// the key, which is checked in debug builds.
+    pub fn get_hashed<'map, Q>(&'map self, hash: u64, key: &Q) -> Option<ReadGuard<'map, K, V>>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        debug_assert_eq!(hash, self.hash_of(key), "hash does not match the key");
+        self.raw_get(hash, |stored| stored.borrow() == key)
+    }
+
+    /// Searches for an entry with the given hash whose key matches the given
+    /// closure, without requiring a type the stored key can be borrowed as.
+    /// Among the entries with the same hash, the first one found to match is
+    /// returned. The hash must be the one [`hash_of`](Map::hash_of) computes
+    /// for the matching keys, which is checked in debug builds when an entry
+    /// is found.
+    pub fn get_hashed_with<'map, F>(
+        &'map self,
+        hash: u64,
+        is_match: F,
+    ) -> Option<ReadGuard<'map, K, V>>
+    where
+        K: Hash,
+        F: FnMut(&K) -> bool,
+    {
+        let found = self.raw_get(hash, is_match);
+        if let Some(guard) = &found {
+            debug_assert_eq!(
+                hash,
+                self.hash_of(guard.key()),
+                "hash does not match the key"
+            );
+        }
+        found
+    }
+
+    /// Inserts unconditionally the given key and value, just like
+    /// [`insert`](Map::insert), but using the given hash instead of hashing
+    /// the key. The hash must be the one [`hash_of`](Map::hash_of) computes
+    /// for the key, which is checked in debug builds.
+    pub fn insert_hashed(&self, hash: u64, key: K, val: V) -> Option<Removed<K, V>>
+    where
+        K: Hash + Eq,
+    {
+        debug_assert_eq!(hash, self.hash_of(&key), "hash does not match the key");
+        self.raw_insert(hash, key, val)
+    }
+
+    /// Removes unconditionally the entry identified by the given key, just
+    /// like [`remove`](Map::remove), but using the given hash instead of
+    /// hashing the key again. The hash must be the one
+    /// [`hash_of`](Map::hash_of) computes for the key, which is checked in
+    /// debug builds.
+    pub fn remove_hashed<Q>(&self, hash: u64, key: &Q) -> Option<Removed<K, V>>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        debug_assert_eq!(hash, self.hash_of(key), "hash does not match the key");
+        self.raw_remove_with(hash, key, |_| true)
+    }
+
+    fn raw_get<'map, F>(&'map self, hash: u64, is_match: F) -> Option<ReadGuard<'map, K, V>>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        let pause = self.incin.get_unchecked().pause();
+        // Safe because we paused properly.
+        unsafe { self.top.load().get(&self.top, is_match, hash, pause) }
+    }
 }
 
 impl<K, V, H> Default for Map<K, V, H>
@@ -385,37 +893,25 @@ where
     fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
         write!(
             fmtr,
-            "Map {{ top_table: {:?}, incin: {:?}, build_hasher: {:?} }}",
+            "Map {{ top_table: {:?}, incin: {:?}, build_hasher: {:?}, len: {:?} }}",
             self.top,
             self.incin.get_unchecked(),
-            self.builder
+            self.builder,
+            self.len
         )
     }
 }
 
-impl<K, V, H> Drop for Map<K, V, H> {
-    fn drop(&mut self) {
-        let mut tables = Vec::new();
-
-        // Safe because we won't use these nodes anymore. We are in the
-        // destructor.
-        unsafe { self.top.free_nodes(&mut tables) }
-
-        while let Some(mut table) = tables.pop() {
-            // Safe because we won't use these nodes anymore. We are in the
-            // destructor.
-            unsafe { table.free_nodes(&mut tables) }
-        }
-    }
-}
-
 impl<'map, K, V, H> IntoIterator for &'map Map<K, V, H> {
     type Item = ReadGuard<'map, K, V>;
 
     type IntoIter = Iter<'map, K, V>;
 
     fn into_iter(self) -> Self::IntoIter {
-        Iter::new(self.incin.get_unchecked().pause(), &self.top)
+        let pause = self.incin.get_unchecked().pause();
+        // Safe because we paused properly.
+        let top = unsafe { self.top.load() };
+        Iter::new(pause, &self.top, top, 0)
     }
 }
 
@@ -425,7 +921,8 @@ impl<'map, K, V, H> IntoIterator for &'map mut Map<K, V, H> {
     type IntoIter = IterMut<'map, K, V>;
 
     fn into_iter(self) -> Self::IntoIter {
-        IterMut::new(&mut self.top)
+        self.unshare();
+        IterMut::new(self.top.get_mut())
     }
 }
 
@@ -435,22 +932,15 @@ impl<K, V, H> IntoIterator for Map<K, V, H> {
     type IntoIter = IntoIter<K, V>;
 
     fn into_iter(mut self) -> Self::IntoIter {
-        let raw = self.top.raw();
-        // Unfortunately, this unsafe is needed since there is no other way of
-        // dropping the field and forgetting the Map.
-        unsafe {
-            (&mut self.builder as *mut H).drop_in_place();
-            (&mut self.incin as *mut SharedIncin<K, V>).drop_in_place();
-            mem::forget(self);
-            IntoIter::new(OwnedAlloc::from_raw(raw))
-        }
+        self.unshare();
+        IntoIter::new(self.top.into_table())
     }
 }
 
 impl<K, V, H> Extend<(K, V)> for Map<K, V, H>
 where
     H: BuildHasher,
-    K: Hash + Ord,
+    K: Hash + Eq,
 {
     fn extend<I>(&mut self, iterable: I)
     where
@@ -463,7 +953,7 @@ where
 impl<K, V, H> FromIterator<(K, V)> for Map<K, V, H>
 where
     H: BuildHasher + Default,
-    K: Hash + Ord,
+    K: Hash + Eq,
 {
     fn from_iter<I>(iterable: I) -> Self
     where
@@ -507,6 +997,11 @@ mod test {
     use super::*;
     use alloc::format;
     use alloc::sync::Arc;
+    use core::{
+        hash::BuildHasherDefault,
+        mem,
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst},
+    };
     use std::{collections::HashMap, thread};
 
     #[test]
@@ -780,6 +1275,1239 @@ mod test {
         }
     }
 
+    #[test]
+    fn entry() {
+        let map = Map::new();
+        assert_eq!(*map.entry("five".to_owned()).or_insert(5).val(), 5);
+        assert_eq!(*map.entry("five".to_owned()).or_insert(50).val(), 5);
+
+        let guard = map
+            .entry("five".to_owned())
+            .and_modify(|n| n + 1)
+            .or_insert_with(|| unreachable!());
+        assert_eq!(*guard.val(), 6);
+        drop(guard);
+
+        let guard = map
+            .entry("four".to_owned())
+            .and_modify(|n| n + 1)
+            .or_default();
+        assert_eq!(*guard.val(), 0);
+        assert_eq!(*map.get("five").unwrap().val(), 6);
+        assert_eq!(*map.get("four").unwrap().val(), 0);
+    }
+
+    #[test]
+    fn entry_drops_values() {
+        let val = Arc::new(());
+        let map = Map::new();
+        map.entry(0).or_insert(val.clone());
+        map.entry(0).or_insert(val.clone());
+        map.entry(0)
+            .and_modify(|val| val.clone())
+            .or_insert(val.clone());
+        assert_eq!(Arc::strong_count(&val), 2);
+        drop(map);
+        assert_eq!(Arc::strong_count(&val), 1);
+    }
+
+    #[test]
+    fn entry_counts_concurrently() {
+        const THREADS: usize = 8;
+        const KEYS: usize = 16;
+        const ROUNDS: usize = 64;
+
+        let map = Arc::new(Map::new());
+        let mut threads = Vec::with_capacity(THREADS);
+        for _ in 0..THREADS {
+            let map = map.clone();
+            threads.push(thread::spawn(move || {
+                for i in 0..ROUNDS * KEYS {
+                    map.entry(i % KEYS).and_modify(|n| n + 1).or_insert(1);
+                    if i % 32 == 0 {
+                        thread::yield_now();
+                    }
+                }
+            }));
+        }
+        for thread in threads {
+            thread.join().expect("thread failed");
+        }
+        for i in 0..KEYS {
+            assert_eq!(*map.get(&i).unwrap().val(), THREADS * ROUNDS);
+        }
+    }
+
+    #[test]
+    fn update_and_compute() {
+        let map = Map::new();
+        assert!(map.update("five", |n| Some(n + 1)).is_none());
+        assert!(map.get("five").is_none());
+
+        assert!(map
+            .compute("five".to_owned(), |n| n.map(|n| n + 1))
+            .is_none());
+        assert!(map.get("five").is_none());
+        assert!(map
+            .compute("five".to_owned(), |n| Some(n.map_or(5, |n| n + 1)))
+            .is_none());
+        assert_eq!(*map.get("five").unwrap().val(), 5);
+
+        let old = map.update("five", |n| Some(n * 2)).unwrap();
+        assert_eq!(old.key(), "five");
+        assert_eq!(*old.val(), 5);
+        assert_eq!(*map.get("five").unwrap().val(), 10);
+
+        let old = map.compute("five".to_owned(), |_| None).unwrap();
+        assert_eq!(*old.val(), 10);
+        assert!(map.get("five").is_none());
+
+        map.insert("four".to_owned(), 4);
+        assert_eq!(*map.update("four", |_| None).unwrap().val(), 4);
+        assert!(map.get("four").is_none());
+    }
+
+    #[test]
+    fn compute_counts_concurrently() {
+        const THREADS: usize = 8;
+        const ROUNDS: usize = 256;
+
+        let map = Arc::new(Map::new());
+        let mut threads = Vec::with_capacity(THREADS);
+        for i in 0..THREADS {
+            let map = map.clone();
+            threads.push(thread::spawn(move || {
+                for j in 0..ROUNDS {
+                    // Key 0 counts up, while key 1 toggles between present
+                    // and removed.
+                    map.compute(0, |n| Some(n.map_or(1, |n| n + 1)));
+                    map.compute(1, |n| match n {
+                        Some(_) => None,
+                        None => Some(i),
+                    });
+                    map.update(&0, |n| Some(n + 1));
+                    if j % 32 == 0 {
+                        thread::yield_now();
+                    }
+                }
+            }));
+        }
+        for thread in threads {
+            thread.join().expect("thread failed");
+        }
+        assert_eq!(*map.get(&0).unwrap().val(), 2 * THREADS * ROUNDS);
+        assert!(map.get(&1).is_none());
+    }
+
+    #[test]
+    fn compare_and_swap() {
+        let map = Map::new();
+        assert_eq!(map.compare_and_swap("five".to_owned(), &5, 6), Err(6));
+        assert!(map.get("five").is_none());
+
+        map.insert("five".to_owned(), 5);
+        assert_eq!(map.compare_and_swap("five".to_owned(), &4, 6), Err(6));
+        let old = map.compare_and_swap("five".to_owned(), &5, 6).unwrap();
+        assert_eq!(*old.val(), 5);
+        assert_eq!(*map.get("five").unwrap().val(), 6);
+
+        assert!(map.remove_if_eq("five", &5).is_none());
+        assert_eq!(*map.remove_if_eq("five", &6).unwrap().val(), 6);
+        assert!(map.get("five").is_none());
+    }
+
+    #[test]
+    fn compare_and_swap_counts_concurrently() {
+        const THREADS: usize = 8;
+        const ROUNDS: usize = 256;
+
+        let map = Arc::new(Map::new());
+        map.insert(0, 0);
+        let mut threads = Vec::with_capacity(THREADS);
+        for _ in 0..THREADS {
+            let map = map.clone();
+            threads.push(thread::spawn(move || {
+                for _ in 0..ROUNDS {
+                    loop {
+                        let curr = *map.get(&0).unwrap().val();
+                        if map.compare_and_swap(0, &curr, curr + 1).is_ok() {
+                            break;
+                        }
+                        thread::yield_now();
+                    }
+                }
+            }));
+        }
+        for thread in threads {
+            thread.join().expect("thread failed");
+        }
+        assert_eq!(*map.get(&0).unwrap().val(), THREADS * ROUNDS);
+    }
+
+    #[test]
+    fn len_and_is_empty() {
+        let mut map = Map::new();
+        assert!(map.is_empty());
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        map.insert(0, 0);
+        map.entry(100).or_insert(100);
+        map.compute(101, |_| Some(101));
+        assert!(!map.is_empty());
+        assert_eq!(map.len(), 102);
+
+        for i in 0..50 {
+            map.remove(&i);
+        }
+        map.remove(&0);
+        map.compute(101, |_| None);
+        assert_eq!(map.len(), 51);
+
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.len(), 0);
+    }
+
+    #[test]
+    fn stats() {
+        let map = Map::new();
+        let stats = map.stats();
+        assert_eq!(stats.entries, 0);
+        assert_eq!(stats.tables, 1);
+        assert_eq!(stats.depth, 1);
+        assert_eq!(stats.empty_slots, 256);
+        let nodes_bytes = 256 * mem::size_of::<usize>();
+        assert_eq!(
+            stats.table_bytes,
+            mem::size_of::<Table<u64, u64>>() + nodes_bytes
+        );
+
+        for i in 0..1000u64 {
+            map.insert(i, i);
+        }
+        let stats = map.stats();
+        assert_eq!(stats.entries, 1000);
+        assert_eq!(stats.buckets, 1000);
+        assert_eq!(stats.max_bucket_len, 1);
+        assert!(stats.tables > 1);
+        assert!(stats.depth > 1);
+    }
+
+    #[derive(Default)]
+    struct Flooded;
+
+    impl Hasher for Flooded {
+        fn finish(&self) -> u64 {
+            0
+        }
+
+        fn write(&mut self, _: &[u8]) {}
+    }
+
+    #[test]
+    fn stats_catches_collisions() {
+        let map = Map::with_hasher(BuildHasherDefault::<Flooded>::default());
+        for i in 0..10 {
+            map.insert(i, i);
+        }
+        let stats = map.stats();
+        assert_eq!(stats.entries, 10);
+        assert_eq!(stats.buckets, 1);
+        assert_eq!(stats.max_bucket_len, 10);
+    }
+
+    // A key type which is not `Ord`.
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    enum Shape {
+        Circle(u32),
+        Square(u32),
+    }
+
+    #[test]
+    fn eq_only_keys() {
+        let map = Map::with_hasher(BuildHasherDefault::<Flooded>::default());
+        for i in 0..10 {
+            assert!(map.insert(Shape::Circle(i), i).is_none());
+            assert!(map.insert(Shape::Square(i), i + 10).is_none());
+        }
+        assert_eq!(map.stats().buckets, 1);
+
+        assert_eq!(map.insert(Shape::Circle(3), 30).unwrap().val(), &3);
+        assert_eq!(*map.get(&Shape::Circle(3)).unwrap().val(), 30);
+        assert_eq!(*map.get(&Shape::Square(3)).unwrap().val(), 13);
+        assert_eq!(map.remove(&Shape::Square(5)).unwrap().val(), &15);
+        assert!(map.get(&Shape::Square(5)).is_none());
+        assert!(map.remove(&Shape::Square(5)).is_none());
+        assert_eq!(map.iter().count(), 19);
+
+        for i in 0..10 {
+            map.remove(&Shape::Circle(i)).unwrap();
+        }
+        assert_eq!(map.stats().entries, 9);
+        assert!(map.insert(Shape::Circle(0), 0).is_none());
+        assert_eq!(*map.get(&Shape::Circle(0)).unwrap().val(), 0);
+    }
+
+    #[test]
+    fn eq_only_keys_multithreaded() {
+        const THREADS: u32 = 8;
+        const ROUNDS: u32 = 32;
+
+        // All keys share a single bucket, and every thread inserts the same
+        // circles, so insertions keep racing at the head of the bucket.
+        let map = Arc::new(Map::with_hasher(BuildHasherDefault::<Flooded>::default()));
+        let mut threads = Vec::new();
+        for i in 0..THREADS {
+            let map = map.clone();
+            threads.push(thread::spawn(move || {
+                let mut created = 0;
+                for j in 0..ROUNDS {
+                    let square = Shape::Square(i * ROUNDS + j);
+                    map.insert(square.clone(), i);
+                    if map.insert(Shape::Circle(j), i).is_none() {
+                        created += 1;
+                    }
+                    thread::yield_now();
+                    assert_eq!(map.remove(&square).unwrap().val(), &i);
+                }
+                created
+            }));
+        }
+
+        let mut created = 0;
+        for thread in threads {
+            created += thread.join().unwrap();
+        }
+        assert_eq!(created, ROUNDS);
+        assert_eq!(map.stats().entries, ROUNDS as usize);
+        for j in 0..ROUNDS {
+            assert!(map.get(&Shape::Circle(j)).is_some());
+        }
+    }
+
+    #[test]
+    fn builder_bits() {
+        for &bits in &[1, 4, 11, MAX_BITS] {
+            let mut map = MapBuilder::new().bits(bits).build();
+            assert_eq!(map.bits(), bits);
+            assert_eq!(map.stats().empty_slots, 1 << bits);
+
+            for i in 0..500u64 {
+                assert!(map.insert(i, i * 3).is_none());
+            }
+            for i in 0..500u64 {
+                assert_eq!(*map.get(&i).unwrap().val(), i * 3);
+            }
+            assert_eq!(map.stats().entries, 500);
+            assert_eq!(map.iter().count(), 500);
+
+            for i in 0..250u64 {
+                assert!(map.remove(&i).is_some());
+            }
+            map.optimize_space();
+            for i in 0..500u64 {
+                assert_eq!(map.get(&i).is_some(), i >= 250);
+            }
+        }
+    }
+
+    #[test]
+    fn builder_bits_saves_space() {
+        let small = MapBuilder::new().bits(4).build::<u64, u64>();
+        let default = Map::<u64, u64>::new();
+        assert_eq!(default.bits(), DEFAULT_BITS);
+        assert!(small.stats().table_bytes < default.stats().table_bytes);
+    }
+
+    #[test]
+    #[should_panic]
+    fn builder_rejects_zero_bits() {
+        MapBuilder::new().bits(0);
+    }
+
+    #[test]
+    fn builder_bits_multithreaded() {
+        const THREADS: u64 = 8;
+        const ROUNDS: u64 = 200;
+
+        let map = Arc::new(MapBuilder::new().bits(2).build());
+        let mut threads = Vec::new();
+        for i in 0..THREADS {
+            let map = map.clone();
+            threads.push(thread::spawn(move || {
+                for j in 0..ROUNDS {
+                    map.insert(i * ROUNDS + j, i);
+                    thread::yield_now();
+                }
+            }));
+        }
+        for thread in threads {
+            thread.join().expect("thread failed");
+        }
+        for i in 0..THREADS * ROUNDS {
+            assert_eq!(*map.get(&i).unwrap().val(), i / ROUNDS);
+        }
+    }
+
+    #[derive(Default)]
+    struct Identity(u64);
+
+    impl Hasher for Identity {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for &byte in bytes {
+                self.0 = self.0 << 8 | byte as u64;
+            }
+        }
+
+        fn write_u64(&mut self, n: u64) {
+            self.0 = n;
+        }
+    }
+
+    #[test]
+    fn compressed_tables() {
+        let mut map = MapBuilder::new()
+            .hasher(BuildHasherDefault::<Identity>::default())
+            .compressed(true)
+            .build();
+
+        // Keys colliding in the top table share a compressed sub-table.
+        for i in 0..32u64 {
+            map.insert(i << 8, i);
+        }
+        let stats = map.stats();
+        assert_eq!(stats.tables, 2);
+        assert_eq!(stats.compressed_tables, 1);
+        assert_eq!(stats.entries, 32);
+        assert_eq!(stats.empty_slots, 255 + 256 - 32);
+
+        // Too many children make it a full table.
+        map.insert(32 << 8, 32);
+        let stats = map.stats();
+        assert_eq!(stats.tables, 2);
+        assert_eq!(stats.compressed_tables, 0);
+
+        for i in 0..=32u64 {
+            assert_eq!(*map.get(&(i << 8)).unwrap().val(), i);
+        }
+        assert!(map.get(&(33 << 8)).is_none());
+
+        // Sparse full tables are compressed again by `optimize_space`.
+        for i in 2..=32u64 {
+            assert_eq!(map.remove(&(i << 8)).unwrap().val(), &i);
+        }
+        map.optimize_space();
+        assert_eq!(map.stats().compressed_tables, 1);
+        assert_eq!(map.iter().count(), 2);
+
+        // A compressed table with a single bucket left is removed.
+        map.remove(&(1 << 8)).unwrap();
+        let stats = map.stats();
+        assert_eq!(stats.tables, 1);
+        assert_eq!(stats.entries, 1);
+        assert_eq!(*map.get(&0).unwrap().val(), 0);
+    }
+
+    #[test]
+    fn removal_compacts_tables() {
+        let map = MapBuilder::new()
+            .hasher(BuildHasherDefault::<Identity>::default())
+            .compressed(true)
+            .build();
+
+        for i in 0..64u64 {
+            map.insert(i << 8, i);
+        }
+        let stats = map.stats();
+        assert_eq!(stats.tables, 2);
+        assert_eq!(stats.compressed_tables, 0);
+
+        // A full table left sparse is compressed.
+        for i in 16..64u64 {
+            assert_eq!(map.remove(&(i << 8)).unwrap().val(), &i);
+        }
+        let stats = map.stats();
+        assert_eq!(stats.tables, 2);
+        assert_eq!(stats.compressed_tables, 1);
+        for i in 0..16u64 {
+            assert_eq!(*map.get(&(i << 8)).unwrap().val(), i);
+        }
+
+        // Nested full tables collapse up to the top one.
+        for i in 0..64u64 {
+            map.insert(i << 16 | 1, i);
+        }
+        assert_eq!(map.stats().depth, 3);
+        for i in 0..64u64 {
+            assert_eq!(map.remove(&(i << 16 | 1)).unwrap().val(), &i);
+        }
+        for i in 1..16u64 {
+            map.remove(&(i << 8)).unwrap();
+        }
+        let stats = map.stats();
+        assert_eq!(stats.tables, 1);
+        assert_eq!(stats.entries, 1);
+        assert_eq!(*map.get(&0).unwrap().val(), 0);
+    }
+
+    #[test]
+    fn removal_compacts_tables_with_random_hashes() {
+        let map = Map::new();
+        for i in 0..20000 {
+            map.insert(i, i);
+        }
+        assert!(map.stats().depth > 1);
+        for i in 0..20000 {
+            assert_eq!(map.remove(&i).unwrap().val(), &i);
+        }
+        let stats = map.stats();
+        assert_eq!(stats.tables, 1);
+        assert_eq!(stats.empty_slots, 256);
+    }
+
+    #[test]
+    fn removal_compacts_tables_multithreaded() {
+        const THREADS: u64 = 8;
+        const ROUNDS: u64 = 32;
+
+        // All keys go through the same sub-table, which keeps being frozen by
+        // removals while other threads insert into it.
+        let map = Arc::new(Map::with_hasher(BuildHasherDefault::<Identity>::default()));
+        let mut threads = Vec::new();
+        for i in 0..THREADS {
+            let map = map.clone();
+            threads.push(thread::spawn(move || {
+                for _ in 0..16 {
+                    for j in 0..ROUNDS {
+                        map.insert((j * THREADS + i) << 16, i);
+                        thread::yield_now();
+                    }
+                    for j in 0..ROUNDS {
+                        let key = (j * THREADS + i) << 16;
+                        assert_eq!(map.remove(&key).unwrap().val(), &i);
+                        thread::yield_now();
+                    }
+                }
+            }));
+        }
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let stats = map.stats();
+        assert_eq!(stats.tables, 1);
+        assert_eq!(stats.entries, 0);
+    }
+
+    #[test]
+    fn compressed_tables_iter() {
+        let mut map = MapBuilder::new()
+            .hasher(BuildHasherDefault::<Identity>::default())
+            .compressed(true)
+            .build();
+        for i in 0..20u64 {
+            for j in 0..3 {
+                map.insert(i << 8 | j, i);
+            }
+        }
+        assert_eq!(map.stats().compressed_tables, 3);
+
+        let mut keys = map.iter().map(|guard| *guard.key()).collect::<Vec<_>>();
+        keys.sort_unstable();
+        let expected = (0..3)
+            .flat_map(|j| (0..20).map(move |i| i << 8 | j))
+            .collect::<Vec<_>>();
+        let mut sorted = expected.clone();
+        sorted.sort_unstable();
+        assert_eq!(keys, sorted);
+
+        for (k, v) in &mut map {
+            *v = *k;
+        }
+        for (k, v) in map {
+            assert_eq!(k, v);
+        }
+    }
+
+    #[test]
+    fn compressed_tables_save_space() {
+        let map = MapBuilder::new().compressed(true).build();
+        let plain = Map::new();
+        assert!(map.is_compressed());
+        assert!(!plain.is_compressed());
+        for i in 0..2000u64 {
+            map.insert(i, i);
+            plain.insert(i, i);
+        }
+        let stats = map.stats();
+        assert!(stats.compressed_tables > 0);
+        assert!(stats.table_bytes < stats.tables * 256 * mem::size_of::<usize>());
+        let plain_stats = plain.stats();
+        assert_eq!(plain_stats.compressed_tables, 0);
+        assert!(stats.table_bytes < plain_stats.table_bytes);
+    }
+
+    #[test]
+    fn compressed_tables_multithreaded() {
+        const THREADS: u64 = 8;
+        const ROUNDS: u64 = 64;
+
+        // All keys go through the same sub-tables, which are copied on every
+        // new slot.
+        let map = Arc::new(
+            MapBuilder::new()
+                .hasher(BuildHasherDefault::<Identity>::default())
+                .compressed(true)
+                .build(),
+        );
+        let mut threads = Vec::new();
+        for i in 0..THREADS {
+            let map = map.clone();
+            threads.push(thread::spawn(move || {
+                for j in 0..ROUNDS {
+                    let key = (i * ROUNDS + j) << 8;
+                    map.insert(key, i);
+                    thread::yield_now();
+                    if j % 2 == 1 {
+                        assert_eq!(map.remove(&key).unwrap().val(), &i);
+                    }
+                }
+            }));
+        }
+        for thread in threads {
+            thread.join().expect("thread failed");
+        }
+        for i in 0..THREADS {
+            for j in 0..ROUNDS {
+                let key = (i * ROUNDS + j) << 8;
+                assert_eq!(map.get(&key).is_some(), j % 2 == 0);
+            }
+        }
+        assert_eq!(map.stats().entries, (THREADS * ROUNDS / 2) as usize);
+    }
+
+    #[test]
+    fn retain() {
+        let map = (0..200).map(|i| (i, i * 3)).collect::<Map<_, _>>();
+        let mut calls = 0;
+        map.retain(|key, val| {
+            calls += 1;
+            assert_eq!(*val, key * 3);
+            key % 4 == 0
+        });
+        assert_eq!(calls, 200);
+        for i in 0..200 {
+            assert_eq!(map.get(&i).is_some(), i % 4 == 0);
+        }
+        assert_eq!(map.len(), 50);
+    }
+
+    #[test]
+    fn retain_multithreaded() {
+        const THREADS: usize = 8;
+        const ROUNDS: usize = 512;
+
+        let map = Arc::new(Map::new());
+        let mut threads = Vec::with_capacity(THREADS);
+        for i in 0..THREADS {
+            let map = map.clone();
+            threads.push(thread::spawn(move || {
+                for j in 0..ROUNDS {
+                    map.insert(j * THREADS + i, j % 2 == 0);
+                    if j % 64 == 0 {
+                        map.retain(|_, &keep| keep);
+                        thread::yield_now();
+                    }
+                }
+            }));
+        }
+        for thread in threads {
+            thread.join().expect("thread failed");
+        }
+        map.retain(|_, &keep| keep);
+        for key in 0..THREADS * ROUNDS {
+            assert_eq!(map.get(&key).is_some(), key / THREADS % 2 == 0);
+        }
+        assert_eq!(map.stats().entries, THREADS * ROUNDS / 2);
+    }
+
+    #[test]
+    fn clear_shared() {
+        let val = Arc::new(());
+        // Few bits, so there are a lot of sub-tables.
+        let map = MapBuilder::new().bits(2).build();
+        for i in 0..500 {
+            map.insert(i, val.clone());
+        }
+        assert!(map.stats().tables > 1);
+        map.clear_shared();
+        assert!(map.is_empty());
+        assert_eq!(map.stats().tables, 1);
+        assert_eq!(map.len(), 0);
+        assert_eq!(Arc::strong_count(&val), 1);
+
+        map.insert(3, val.clone());
+        assert!(map.get(&3).is_some());
+        drop(map);
+        assert_eq!(Arc::strong_count(&val), 1);
+    }
+
+    #[test]
+    fn clear_shared_multithreaded() {
+        const THREADS: usize = 8;
+        const ROUNDS: usize = 512;
+
+        let val = Arc::new(());
+        let map = Arc::new(MapBuilder::new().bits(3).build());
+        let mut threads = Vec::with_capacity(THREADS);
+        for i in 0..THREADS {
+            let map = map.clone();
+            let val = val.clone();
+            threads.push(thread::spawn(move || {
+                for j in 0..ROUNDS {
+                    let key = j * THREADS + i;
+                    map.insert(key, val.clone());
+                    map.remove(&(key / 2));
+                    if j % 128 == 0 {
+                        map.clear_shared();
+                        thread::yield_now();
+                    }
+                }
+            }));
+        }
+        for thread in threads {
+            thread.join().expect("thread failed");
+        }
+        map.clear_shared();
+        assert!(map.is_empty());
+        // Garbage left by the threads is only destroyed once the incinerator
+        // is cleared.
+        let mut map = Arc::try_unwrap(map).unwrap();
+        map.optimize_space();
+        assert_eq!(Arc::strong_count(&val), 1);
+    }
+
+    #[test]
+    fn clear_shared_races_writes() {
+        const THREADS: usize = 6;
+        const ROUNDS: usize = 2048;
+
+        let map = Arc::new(MapBuilder::new().bits(2).build());
+        let mut all = map.watch_all();
+        let done = Arc::new(AtomicBool::new(false));
+        let clearer = {
+            let map = map.clone();
+            let done = done.clone();
+            thread::spawn(move || {
+                while !done.load(SeqCst) {
+                    map.clear_shared();
+                    thread::yield_now();
+                }
+            })
+        };
+
+        let mut threads = Vec::with_capacity(THREADS);
+        for i in 0..THREADS {
+            let map = map.clone();
+            threads.push(thread::spawn(move || {
+                for j in 0..ROUNDS {
+                    map.insert(j * THREADS + i, j);
+                    if j % 3 == 0 {
+                        map.remove(&((j / 2) * THREADS + i));
+                    }
+                }
+            }));
+        }
+        for thread in threads {
+            thread.join().expect("thread failed");
+        }
+        done.store(true, SeqCst);
+        clearer.join().expect("thread failed");
+
+        map.clear_shared();
+        assert_eq!(map.iter().count(), 0);
+        assert_eq!(map.len(), 0);
+        assert_eq!(map.stats().entries, 0);
+
+        // Every entry inserted was removed, be it by a thread or cleared.
+        let mut present = HashMap::new();
+        for event in all.try_iter() {
+            let count = present.entry(*event.key()).or_insert(0isize);
+            match event {
+                Event::Inserted(..) => *count += 1,
+                Event::Removed(..) => *count -= 1,
+                Event::Updated(..) => (),
+            }
+        }
+        assert!(present.values().all(|&count| count == 0));
+    }
+
+    #[test]
+    fn hashed() {
+        let map = Map::new();
+        let key = "hello".to_owned();
+        let hash = map.hash_of(&key);
+        assert_eq!(hash, map.hash_of("hello"));
+
+        assert!(map.insert_hashed(hash, key, 1).is_none());
+        assert_eq!(*map.get_hashed(hash, "hello").unwrap().val(), 1);
+        assert_eq!(*map.get("hello").unwrap().val(), 1);
+        let removed = map.insert_hashed(hash, "hello".to_owned(), 2).unwrap();
+        assert_eq!(removed.val(), &1);
+
+        // No need for a borrowed form of the key.
+        let found = map.get_hashed_with(hash, |stored| stored.len() == 5);
+        assert_eq!(*found.unwrap().val(), 2);
+        assert!(map
+            .get_hashed_with(hash, |stored| stored.is_empty())
+            .is_none());
+        assert!(map.get_hashed_with(hash ^ 1, |_| true).is_none());
+
+        assert_eq!(map.remove_hashed(hash, "hello").unwrap().val(), &2);
+        assert!(map.remove_hashed(hash, "hello").is_none());
+        assert!(map.get_hashed(hash, "hello").is_none());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn hashed_rejects_wrong_hash() {
+        let map = Map::new();
+        let hash = map.hash_of(&5);
+        map.insert_hashed(hash ^ 1, 5, 5);
+    }
+
+    #[test]
+    fn watch() {
+        let map = Map::new();
+        assert!(!map.top.is_watched());
+        let mut one = map.watch(&1);
+        let mut all = map.watch_all();
+
+        map.insert(1, 10);
+        map.insert(2, 20);
+        map.insert(1, 11);
+        map.remove(&2);
+        map.remove(&3);
+        map.compute(1, |val| val.map(|val| val + 1));
+        map.insert_with(4, |_, _, _| Preview::Discard);
+
+        let events = one.try_iter().collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                Event::Inserted(1, 10),
+                Event::Updated(1, 11),
+                Event::Updated(1, 12),
+            ]
+        );
+        let events = all.try_iter().collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                Event::Inserted(1, 10),
+                Event::Inserted(2, 20),
+                Event::Updated(1, 11),
+                Event::Removed(2, 20),
+                Event::Updated(1, 12),
+            ]
+        );
+
+        // Dropped receivers are forgotten by the next change.
+        drop(one);
+        map.remove(&1);
+        assert_eq!(all.try_iter().collect::<Vec<_>>(), [Event::Removed(1, 12)]);
+        drop(all);
+        map.insert(5, 50);
+        assert!(!map.top.is_watched());
+    }
+
+    #[test]
+    fn watch_forgets_dropped_key_receiver() {
+        let map = Map::new();
+        let one = map.watch(&1);
+        drop(one);
+        assert!(map.top.is_watched());
+        map.insert(2, 20);
+        assert!(!map.top.is_watched());
+    }
+
+    #[test]
+    fn watch_clear() {
+        let mut map = MapBuilder::new().bits(2).build();
+        for i in 0..50 {
+            map.insert(i, i);
+        }
+        let mut all = map.watch_all();
+        map.clear_shared();
+        let mut removed = all
+            .try_iter()
+            .map(|event| match event {
+                Event::Removed(key, val) => {
+                    assert_eq!(key, val);
+                    key
+                }
+                event => panic!("unexpected {:?}", event),
+            })
+            .collect::<Vec<_>>();
+        removed.sort();
+        assert_eq!(removed, (0..50).collect::<Vec<_>>());
+
+        map.insert(7, 7);
+        map.clear();
+        let events = all.try_iter().collect::<Vec<_>>();
+        assert_eq!(events, [Event::Inserted(7, 7), Event::Removed(7, 7)]);
+    }
+
+    #[test]
+    fn watch_multithreaded() {
+        const THREADS: usize = 8;
+        const ROUNDS: usize = 256;
+
+        let map = Arc::new(Map::new());
+        let mut watched = map.watch(&(0, 0));
+        let mut all = map.watch_all();
+        let mut threads = Vec::with_capacity(THREADS);
+        for i in 0..THREADS {
+            let map = map.clone();
+            threads.push(thread::spawn(move || {
+                for j in 0..ROUNDS {
+                    map.insert((i, j), j);
+                    map.insert((0, 0), j);
+                    if j % 2 == 0 {
+                        map.remove(&(i, j));
+                    }
+                    if j % 64 == 0 {
+                        // Watches which come and go meanwhile.
+                        map.watch(&(i, j));
+                        thread::yield_now();
+                    }
+                }
+            }));
+        }
+        for thread in threads {
+            thread.join().expect("thread failed");
+        }
+
+        assert!(watched.try_iter().all(|event| *event.key() == (0, 0)));
+        let mut entries = HashMap::new();
+        for event in all.try_iter() {
+            match event {
+                Event::Inserted(key, val) | Event::Updated(key, val) => {
+                    entries.insert(key, val);
+                }
+                Event::Removed(key, _) => {
+                    entries.remove(&key);
+                }
+            }
+        }
+        // Each thread's own keys only change by that thread, in order.
+        for guard in map.iter() {
+            if *guard.key() != (0, 0) {
+                assert_eq!(entries.get(guard.key()), Some(guard.val()));
+            }
+        }
+        let own = entries.keys().filter(|&&key| key != (0, 0)).count();
+        assert_eq!(own, THREADS * ROUNDS / 2);
+    }
+
+    #[test]
+    fn snapshot() {
+        for &bits in &[3, DEFAULT_BITS] {
+            let map = MapBuilder::new().bits(bits).build();
+            for i in 0..1000 {
+                map.insert(i, i);
+            }
+
+            let snapshot = map.snapshot();
+            for i in 0..1000 {
+                if i % 2 == 0 {
+                    map.remove(&i);
+                } else {
+                    map.insert(i, i * 2);
+                }
+            }
+            for i in 1000..2000 {
+                map.insert(i, i);
+            }
+            let later = map.snapshot();
+            map.clear_shared();
+
+            assert_eq!(snapshot.len(), 1000);
+            for i in 0..1000 {
+                assert_eq!(*snapshot.get(&i).unwrap(), (i, i));
+            }
+            assert!(snapshot.get(&1000).is_none());
+            let mut entries = snapshot.iter().map(|guard| *guard).collect::<Vec<_>>();
+            entries.sort();
+            assert_eq!(entries, (0..1000).map(|i| (i, i)).collect::<Vec<_>>());
+
+            assert_eq!(later.len(), 1500);
+            assert!(later.get(&0).is_none());
+            assert_eq!(*later.get(&1).unwrap(), (1, 2));
+            assert_eq!(*later.get(&1999).unwrap(), (1999, 1999));
+
+            assert!(map.is_empty());
+            assert!(!snapshot.is_empty());
+        }
+    }
+
+    #[test]
+    fn snapshot_keeps_removed_entries() {
+        let val = Arc::new(());
+        let map = MapBuilder::new().bits(3).build();
+        for i in 0..100 {
+            map.insert(i, val.clone());
+        }
+
+        let snapshot = map.snapshot();
+        for i in 0..100 {
+            if i % 2 == 0 {
+                map.remove(&i);
+            } else {
+                map.insert(i, val.clone());
+            }
+        }
+        assert_eq!(Arc::strong_count(&val), 151);
+        assert_eq!(snapshot.stats().entries, 100);
+        assert_eq!(map.stats().entries, 50);
+
+        drop(snapshot);
+        drop(map);
+        assert_eq!(Arc::strong_count(&val), 1);
+    }
+
+    #[test]
+    fn snapshot_mut() {
+        let map = Map::new();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+
+        let clone = map.snapshot_mut();
+        clone.insert(100, 100);
+        clone.remove(&0);
+        map.insert(1, 10);
+
+        assert_eq!(clone.bits(), map.bits());
+        assert_eq!(*clone.get(&1).unwrap(), (1, 1));
+        assert!(clone.get(&0).is_none());
+        assert_eq!(*map.get(&1).unwrap(), (1, 10));
+        assert!(map.get(&100).is_none());
+        assert_eq!(clone.len(), 100);
+    }
+
+    #[test]
+    fn snapshot_mut_shares_entries() {
+        let val = Arc::new(());
+        let map = MapBuilder::new().bits(3).build();
+        for i in 0..100 {
+            map.insert(i, val.clone());
+        }
+
+        let clone = map.snapshot_mut();
+        assert_eq!(Arc::strong_count(&val), 101);
+        for i in 0..100 {
+            if i % 2 == 0 {
+                map.remove(&i);
+            } else {
+                clone.insert(i, Arc::new(()));
+            }
+        }
+        let other = clone.snapshot_mut();
+        other.remove(&1);
+        map.clear_shared();
+
+        assert_eq!(map.stats().entries, 0);
+        assert_eq!(clone.stats().entries, 100);
+        assert_eq!(other.stats().entries, 99);
+        for i in 0..100 {
+            let guard = clone.get(&i).unwrap();
+            assert_eq!(Arc::ptr_eq(guard.val(), &val), i % 2 == 0);
+        }
+
+        drop(clone);
+        assert_eq!(other.iter().count(), 99);
+        drop(map);
+        drop(other);
+        assert_eq!(Arc::strong_count(&val), 1);
+    }
+
+    #[test]
+    fn snapshot_mut_exclusive() {
+        for &bits in &[3, DEFAULT_BITS] {
+            let mut map = MapBuilder::new().bits(bits).build();
+            for i in 0..1000 {
+                map.insert(i, i);
+            }
+
+            let mut clone = map.snapshot_mut();
+            for i in 0..1000 {
+                if i % 4 != 0 {
+                    map.remove(&i);
+                }
+            }
+            map.optimize_space();
+            for (_, val) in map.iter_mut() {
+                *val += 1;
+            }
+            clone.optimize_space();
+            let mut other = clone.snapshot_mut();
+            for (_, val) in clone.iter_mut() {
+                *val *= 2;
+            }
+
+            let mut entries = map.into_iter().collect::<Vec<_>>();
+            entries.sort();
+            let expected = (0..1000).step_by(4).map(|i| (i, i + 1));
+            assert_eq!(entries, expected.collect::<Vec<_>>());
+            for i in 0..1000 {
+                assert_eq!(*clone.get(&i).unwrap(), (i, i * 2));
+                assert_eq!(*other.get(&i).unwrap(), (i, i));
+            }
+
+            clone.clear();
+            assert!(clone.is_empty());
+            assert_eq!(other.len(), 1000);
+            other.clear();
+            assert!(other.is_empty());
+        }
+    }
+
+    #[test]
+    fn snapshot_multithreaded() {
+        const THREADS: usize = 4;
+        const SNAPSHOTS: usize = 2;
+        const PASSES: usize = 16;
+        const ROUNDS: usize = 512;
+
+        // Compressed tables are copied on write, unlike full ones, and
+        // snapshots must see neither change.
+        let map = Arc::new(MapBuilder::new().bits(3).compressed(true).build());
+        let running = Arc::new(AtomicUsize::new(THREADS));
+        let mut threads = Vec::with_capacity(THREADS + SNAPSHOTS);
+        for i in 0..THREADS {
+            let map = map.clone();
+            let running = running.clone();
+            threads.push(thread::spawn(move || {
+                // Keys are inserted in order and then removed in order, so the
+                // keys of each thread present at any point form a range.
+                for _ in 0..PASSES {
+                    for j in 0..ROUNDS {
+                        map.insert((i, j), j);
+                        if j % 64 == 0 {
+                            thread::yield_now();
+                        }
+                    }
+                    for j in 0..ROUNDS {
+                        map.remove(&(i, j));
+                        if j % 64 == 0 {
+                            thread::yield_now();
+                        }
+                    }
+                }
+                running.fetch_sub(1, SeqCst);
+            }));
+        }
+        for _ in 0..SNAPSHOTS {
+            let map = map.clone();
+            let running = running.clone();
+            threads.push(thread::spawn(move || {
+                while running.load(SeqCst) > 0 {
+                    let snapshot = map.snapshot();
+                    let mut keys = vec![Vec::new(); THREADS];
+                    for (count, guard) in snapshot.iter().enumerate() {
+                        let &((i, j), val) = &*guard;
+                        assert_eq!(j, val);
+                        keys[i].push(j);
+                        // Lets the map change in the middle of the iteration.
+                        if count % 64 == 0 {
+                            thread::yield_now();
+                        }
+                    }
+
+                    for (i, keys) in keys.iter_mut().enumerate() {
+                        keys.sort();
+                        assert!(keys.windows(2).all(|pair| pair[0] + 1 == pair[1]));
+                        if let (Some(&first), Some(&last)) = (keys.first(), keys.last()) {
+                            assert!(snapshot.get(&(i, first)).is_some());
+                            assert!(snapshot.get(&(i, last + 1)).is_none());
+                        }
+                    }
+                    assert_eq!(snapshot.len(), keys.iter().map(Vec::len).sum::<usize>());
+                    thread::yield_now();
+                }
+            }));
+        }
+        for thread in threads {
+            thread.join().expect("thread failed");
+        }
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn snapshot_mut_multithreaded() {
+        const THREADS: usize = 4;
+        const ROUNDS: usize = 1024;
+
+        let map = Arc::new(MapBuilder::new().bits(3).build());
+        let running = Arc::new(AtomicUsize::new(THREADS));
+        let mut threads = Vec::with_capacity(THREADS + 1);
+        for i in 0..THREADS {
+            let map = map.clone();
+            let running = running.clone();
+            threads.push(thread::spawn(move || {
+                // The keys of each thread present at any point form a range.
+                for j in 0..ROUNDS {
+                    map.insert((i, j), j);
+                    if j >= ROUNDS / 2 {
+                        map.remove(&(i, j - ROUNDS / 2));
+                    }
+                }
+                running.fetch_sub(1, SeqCst);
+            }));
+        }
+        {
+            let map = map.clone();
+            threads.push(thread::spawn(move || {
+                while running.load(SeqCst) > 0 {
+                    let clone = map.snapshot_mut();
+                    let mut keys = vec![Vec::new(); THREADS];
+                    for guard in clone.iter() {
+                        let &((i, j), val) = &*guard;
+                        assert_eq!(j, val);
+                        keys[i].push(j);
+                    }
+                    for (i, keys) in keys.iter_mut().enumerate() {
+                        keys.sort();
+                        assert!(keys.windows(2).all(|pair| pair[0] + 1 == pair[1]));
+                        for &j in keys.iter() {
+                            if j % 2 == 0 {
+                                clone.remove(&(i, j));
+                            } else {
+                                clone.insert((i, j), j * 2);
+                            }
+                        }
+                    }
+                    for guard in clone.iter() {
+                        let &((_, j), val) = &*guard;
+                        assert_eq!(j * 2, val);
+                    }
+                    thread::yield_now();
+                }
+            }));
+        }
+        for thread in threads {
+            thread.join().expect("thread failed");
+        }
+        assert_eq!(map.len(), THREADS * ROUNDS / 2);
+    }
+
     #[test]
     fn multithreaded() {
         let map = Arc::new(Map::new());
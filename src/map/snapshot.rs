@@ -0,0 +1,128 @@
+use super::{bucket::Garbage, guard::ReadGuard, iter::Iter, stats::MapStats, table::Table, Map};
+use crate::incin::Pause;
+use core::{
+    borrow::Borrow,
+    fmt,
+    hash::{BuildHasher, Hash},
+};
+use std::collections::hash_map::RandomState;
+
+/// A read-only view of a [`Map`] at the point in time it was taken, created by
+/// [`Map::snapshot`]. Later modifications of the [`Map`] are not seen through
+/// the snapshot. While the snapshot is alive, no removed entry nor replaced
+/// table of the [`Map`] is destroyed, since the snapshot might still be
+/// reading them.
+pub struct MapSnapshot<'map, K, V, H = RandomState>
+where
+    K: 'map,
+    V: 'map,
+{
+    map: &'map Map<K, V, H>,
+    top: &'map Table<K, V>,
+    pause: Pause<'map, Garbage<K, V>>,
+}
+
+impl<'map, K, V, H> MapSnapshot<'map, K, V, H> {
+    pub(super) fn new(
+        map: &'map Map<K, V, H>,
+        top: &'map Table<K, V>,
+        pause: Pause<'map, Garbage<K, V>>,
+    ) -> Self {
+        Self { map, top, pause }
+    }
+
+    /// Creates an iterator over guarded references to the key-value entries.
+    /// Unlike [`Map::iter`], this yields exactly the entries present when the
+    /// snapshot was taken.
+    pub fn iter(&self) -> Iter<'map, K, V> {
+        Iter::new(self.pause.clone(), &self.map.top, self.top, 0)
+    }
+
+    /// The number of entries in the snapshot. Unlike [`Map::len`], this does
+    /// not rely on a counter: it walks the tables, which takes time
+    /// proportional to the size of the snapshot.
+    pub fn len(&self) -> usize {
+        self.stats().entries
+    }
+
+    /// Tests if the snapshot has no entries.
+    pub fn is_empty(&self) -> bool {
+        // Safe because we keep the pause from before the snapshot was taken.
+        unsafe { self.top.is_empty(&self.map.top) }
+    }
+
+    /// Walks the tables of the snapshot and gathers structural statistics,
+    /// just like [`Map::stats`].
+    pub fn stats(&self) -> MapStats {
+        // Safe because we keep the pause from before the snapshot was taken.
+        unsafe { self.top.stats(&self.map.top) }
+    }
+
+    /// The [`Map`] this snapshot was taken from.
+    pub fn map(&self) -> &'map Map<K, V, H> {
+        self.map
+    }
+}
+
+impl<'map, K, V, H> MapSnapshot<'map, K, V, H>
+where
+    H: BuildHasher,
+{
+    /// Searches for the entry identified by the given key, just like
+    /// [`Map::get`], as it was when the snapshot was taken.
+    pub fn get<Q>(&self, key: &Q) -> Option<ReadGuard<'map, K, V>>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let hash = self.map.hash_of(key);
+        // Safe because we keep the pause from before the snapshot was taken.
+        unsafe {
+            self.top.get(
+                &self.map.top,
+                |stored| stored.borrow() == key,
+                hash,
+                self.pause.clone(),
+            )
+        }
+    }
+}
+
+impl<'map, K, V, H> IntoIterator for &MapSnapshot<'map, K, V, H> {
+    type Item = ReadGuard<'map, K, V>;
+
+    type IntoIter = Iter<'map, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'map, K, V, H> fmt::Debug for MapSnapshot<'map, K, V, H>
+where
+    H: fmt::Debug,
+{
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "MapSnapshot {{ map: {:?}, top_table: {:?}, pause: {:?} }}",
+            self.map, self.top as *const Table<K, V>, self.pause
+        )
+    }
+}
+
+unsafe impl<'map, K, V, H> Send for MapSnapshot<'map, K, V, H>
+where
+    K: Send + Sync,
+    V: Send + Sync,
+    H: Sync,
+{
+}
+
+unsafe impl<'map, K, V, H> Sync for MapSnapshot<'map, K, V, H>
+where
+    K: Sync,
+    V: Sync,
+    H: Sync,
+{
+}
@@ -0,0 +1,32 @@
+/// Structural statistics of a [`Map`](super::Map), gathered by
+/// [`Map::stats`](super::Map::stats). They are meant for capacity planning and
+/// for catching degenerate hashing, such as hash flooding, which shows up as
+/// long buckets or deep tables.
+///
+/// The statistics are gathered by walking the tables while other threads may
+/// modify the [`Map`](super::Map), so they are not an atomic snapshot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MapStats {
+    /// The number of entries found.
+    pub entries: usize,
+    /// The number of buckets holding entries. Entries in the same bucket
+    /// have exactly the same hash.
+    pub buckets: usize,
+    /// The number of entries in the longest bucket, i.e. the longest chain of
+    /// full hash collisions.
+    pub max_bucket_len: usize,
+    /// The number of tables, including the top-level one.
+    pub tables: usize,
+    /// The number of compressed tables, included in `tables`. Compressed
+    /// tables only allocate the slots they use.
+    pub compressed_tables: usize,
+    /// The depth of the deepest table. The top-level table has depth `1`.
+    pub depth: usize,
+    /// The number of slots in all tables holding neither a bucket nor a
+    /// sub-table.
+    pub empty_slots: usize,
+    /// The memory used by the tables themselves, in bytes. Buckets and
+    /// entries are not included.
+    pub table_bytes: usize,
+}
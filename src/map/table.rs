@@ -1,313 +1,381 @@
 use super::{
-    bucket::{Bucket, Garbage, GetRes, InsertRes},
+    bucket::{Bucket, Garbage},
     guard::{ReadGuard, Removed},
     insertion::{Inserter, Insertion},
+    stats::MapStats,
+    watch::{Change, Watcher, Watchers},
 };
 use crate::incin::{Incinerator, Pause};
-use crate::owned_alloc::{Cache, OwnedAlloc, UninitAlloc};
+use crate::owned_alloc::{Cache, OwnedAlloc};
+use alloc::boxed::Box;
 use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::{
     borrow::Borrow,
     fmt,
     marker::PhantomData,
-    ptr::{null_mut, NonNull},
-    sync::atomic::{
-        AtomicPtr,
-        Ordering::{self, *},
-    },
+    mem,
+    ptr::{self, null_mut, NonNull},
+    sync::atomic::{AtomicPtr, AtomicUsize, Ordering::*},
 };
 
-const BITS: usize = 8;
+// A sub-table is compressed while it has at most `1 / COMPRESS_RATIO` of its
+// slots occupied. Beyond that, it becomes a full table.
+const COMPRESS_RATIO: usize = 8;
 
-// If you remove this alignment, don't remove it. Please, set it to 2.
+// Tables with more slots than `1 << MAX_COMPRESSED_BITS` are never compressed,
+// which keeps the bitmap short enough to be scanned on every lookup.
+const MAX_COMPRESSED_BITS: u32 = 10;
+
+// Second lower bit of a slot of a full table being compacted, or of the top
+// table being replaced by a snapshot. A frozen slot is never changed again,
+// and the operation which freezes a table makes sure it was replaced or
+// detached before returning, so operations with exclusive access to the map
+// never find frozen slots in it.
+const FROZEN: usize = 2;
+
+// Third lower bit of a slot of a full sub-table holding the descriptor of a
+// pending write instead of a node. See `Cursor::write`.
+const DESC: usize = 4;
+
+// The states of a descriptor.
+const UNDECIDED: usize = 0;
+const COMMITTED: usize = 1;
+const ABORTED: usize = 2;
+
+// If you remove this alignment, don't remove it. Please, set it to 8.
 #[repr(align(64))]
 pub struct Table<K, V> {
-    nodes: [Node<K, V>; 1 << BITS],
+    // Number of hash bits consumed by this table. There are `1 << bits` slots.
+    bits: u32,
+    // The generation of the top table this table was created under. A full
+    // table is only modified by operations which started from a top table of
+    // the same generation. Otherwise, it might be shared with a snapshot, and
+    // the operation copies it first.
+    gen: u64,
+    // Empty for full tables. For compressed tables, there is one bit per slot,
+    // set if the slot is present in `nodes`. Compressed tables are never
+    // modified in place: they are copied on write and the old copy is sent to
+    // the incinerator.
+    bitmap: Box<[u64]>,
+    // For full tables, one node per slot. For compressed tables, one node per
+    // present slot, in the order of the slots.
+    nodes: Box<[Node<K, V>]>,
+    // For top tables only. The frozen tree this table shares the tables,
+    // buckets and entries up to its generation with, if any. See `Frozen`.
+    frozen: Option<Arc<Frozen<K, V>>>,
+    // For top tables only. Null until the table is about to be frozen, then
+    // what freezes it: `plain()` for a snapshot or for `clear_shared`, or the
+    // frozen tree taking the table over for `Map::snapshot_mut`, which holds
+    // a reference to it until the table is replaced.
+    freezer: AtomicPtr<Frozen<K, V>>,
 }
 
 impl<K, V> Table<K, V> {
-    pub fn new_alloc() -> OwnedAlloc<Self> {
-        // Safe because it calls a correctly a function which correctly
-        // initializes uninitialized memory with, indeed, uninitialized memory.
-        unsafe { UninitAlloc::<Self>::new().init_in_place(|val| val.init_in_place()) }
+    pub fn new_alloc(bits: u32, gen: u64) -> OwnedAlloc<Self> {
+        OwnedAlloc::new(Self {
+            bits,
+            gen,
+            bitmap: Box::new([]),
+            nodes: (0..1usize << bits).map(|_| Node::new()).collect(),
+            frozen: None,
+            freezer: AtomicPtr::new(null_mut()),
+        })
     }
 
-    // Unsafe because passing ininitialized memory may cause leaks.
-    #[inline]
-    pub unsafe fn init_in_place(&mut self) {
-        for node in &mut self.nodes as &mut [_] {
-            (node as *mut Node<K, V>).write(Node::new())
+    // Allocates a compressed table holding the given children, which must
+    // not be null and must be sorted by slot.
+    fn compressed_alloc(bits: u32, gen: u64, children: &[(usize, *mut ())]) -> OwnedAlloc<Self> {
+        let mut bitmap = vec![0u64; ((1usize << bits) + 63) / 64].into_boxed_slice();
+        for &(slot, _) in children {
+            bitmap[slot / 64] |= 1 << (slot % 64);
         }
-    }
-
-    // Unsafe because the incinerator needs to be paused and there are no
-    // guarantees the passed pause comes from the incinerator used with the map
-    // by other threads. Map implementation guarantees that.
-    pub unsafe fn get<'map, Q>(
-        &self,
-        key: &Q,
-        hash: u64,
-        pause: Pause<'map, Garbage<K, V>>,
-    ) -> Option<ReadGuard<'map, K, V>>
-    where
-        Q: ?Sized + Ord,
-        K: Borrow<Q>,
-    {
-        let mut shifted = hash;
-        let mut table = self;
-
-        loop {
-            // Compute the index from the shifted hash's lower bits.
-            let index = shifted as usize & ((1 << BITS) - 1);
-            let loaded = table.nodes[index].atomic.load(Acquire);
-
-            // Null means we have nothing.
-            if loaded.is_null() {
-                break None;
-            }
-
-            // Cleared lower bit means this is a bucket.
-            if loaded as usize & 1 == 0 {
-                let bucket = &*(loaded as *mut Bucket<K, V>);
-
-                // This bucket only matters if it has the same hash we do.
-                if bucket.hash() != hash {
-                    break None;
-                }
 
-                break match bucket.get(key, pause) {
-                    // Success.
-                    GetRes::Found(pair) => Some(pair),
-
-                    // Not here.
-                    GetRes::NotFound => None,
-
-                    // Delete the bucket completely.
-                    GetRes::Delete(pause) => {
-                        let res = table.nodes[index].atomic.compare_exchange(
-                            loaded,
-                            null_mut(),
-                            Relaxed,
-                            Relaxed,
-                        );
-
-                        if res.is_ok() {
-                            let alloc =
-                                OwnedAlloc::from_raw(NonNull::new_unchecked(loaded as *mut _));
-                            // Needs to be destroyed by the incinerator as it is
-                            // shared.
-                            pause.add_to_incin(Garbage::Bucket(alloc));
-                        }
+        OwnedAlloc::new(Self {
+            bits,
+            gen,
+            bitmap,
+            nodes: children
+                .iter()
+                .map(|&(_, ptr)| Node::with_ptr(ptr))
+                .collect(),
+            frozen: None,
+            freezer: AtomicPtr::new(null_mut()),
+        })
+    }
 
-                        None
-                    }
-                };
-            }
+    // Allocates a sub-table holding only the given bucket pointer, for when a
+    // bucket is pushed one level down. It is compressed if the given capacity
+    // of compressed tables allows it.
+    fn branch_alloc(
+        bits: u32,
+        capacity: usize,
+        gen: u64,
+        slot: usize,
+        bucket: *mut (),
+        tbl_cache: &mut Cache<OwnedAlloc<Self>>,
+    ) -> OwnedAlloc<Self> {
+        if capacity > 0 {
+            Self::compressed_alloc(bits, gen, &[(slot, bucket)])
+        } else {
+            let mut table = tbl_cache.take_or(|| Self::new_alloc(bits, gen));
+            table.gen = gen;
+            table.nodes[slot].atomic.store(bucket, Relaxed);
+            table
+        }
+    }
 
-            // If none of other cases have been confirmed, the only remaining
-            // case is a branching table. Let's try to look at it.
-            table = &*((loaded as usize & !1) as *mut Self);
-            // Shifting the hash so we test some other bits.
-            shifted >>= BITS;
+    // The maximum number of children a compressed table with the given bits
+    // may have. Zero means such tables are never compressed.
+    fn compressed_capacity(bits: u32) -> usize {
+        if bits > MAX_COMPRESSED_BITS {
+            0
+        } else {
+            (1 << bits) / COMPRESS_RATIO
         }
     }
 
-    // Unsafe because the incinerator needs to be paused and there are no
-    // guarantees the passed pause comes from the incinerator used with the map
-    // by other threads. Map implementation guarantees that.
-    #[inline(never)]
-    pub unsafe fn insert<I>(
-        &self,
-        mut inserter: I,
-        hash: u64,
-        pause: &Pause<Garbage<K, V>>,
-        incin: &Arc<Incinerator<Garbage<K, V>>>,
-    ) -> Insertion<K, V, I>
-    where
-        I: Inserter<K, V>,
-        K: Ord,
-    {
-        let mut table = self;
-        let mut shifted = hash;
-        let mut depth = 1;
-        let mut tbl_cache = Cache::<OwnedAlloc<Self>>::new();
+    #[inline]
+    pub fn is_compressed(&self) -> bool {
+        !self.bitmap.is_empty()
+    }
 
-        // Compute the index from the shifted hash's lower bits.
-        let mut index = shifted as usize & ((1 << BITS) - 1); // shifted & 255
-                                                              // Load what is in the index before trying to insert.
-        let mut loaded = table.nodes[index].atomic.load(Acquire);
+    // The generation up to which the nodes under this top table are shared
+    // with its frozen tree, if any.
+    #[inline]
+    fn shared_gen(&self) -> Option<u64> {
+        self.frozen.as_ref().map(|frozen| frozen.gen())
+    }
 
-        loop {
-            if loaded.is_null() {
-                // Let's test the found conditions.
-                inserter.input(None);
-                let pair = match inserter.pointer() {
-                    // The inserter accepted the conditions.
-                    Some(nnptr) => nnptr,
-                    // The inserter rejected the conditions.
-                    None => break Insertion::Failed(inserter),
-                };
+    // Computes the slot from the shifted hash's lower bits.
+    #[inline]
+    fn slot(&self, shifted: u64) -> usize {
+        shifted as usize & ((1 << self.bits) - 1)
+    }
 
-                // Allocation of a bucket containing a single entry. Our pair.
-                let bucket = Bucket::new(hash, pair);
-                let bucket_nnptr = OwnedAlloc::new(bucket).into_raw();
+    // The node of the given slot, unless the slot is absent from a compressed
+    // table.
+    #[inline]
+    fn node(&self, slot: usize) -> Option<&Node<K, V>> {
+        if !self.is_compressed() {
+            return Some(&self.nodes[slot]);
+        }
 
-                // We try to put it in the index.
-                let res = table.nodes[index].atomic.compare_exchange(
-                    loaded,
-                    bucket_nnptr.as_ptr() as *mut (),
-                    AcqRel,
-                    Acquire,
-                );
-
-                match res {
-                    Ok(_) => {
-                        // Let's not forget to prevent the inserter from
-                        // deallocating the pointer.
-                        inserter.take_pointer();
-                        break Insertion::Created;
-                    }
+        let word = slot / 64;
+        let mask = 1u64 << (slot % 64);
+        if self.bitmap[word] & mask == 0 {
+            return None;
+        }
+
+        // The index in the dense array is the number of present slots before
+        // this one.
+        let before = self.bitmap[..word]
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum::<usize>();
+        let index = before + (self.bitmap[word] & (mask - 1)).count_ones() as usize;
+        Some(&self.nodes[index])
+    }
 
-                    Err(new) => {
-                        // If we failed this try, we have to clean up.
-                        let mut bucket = OwnedAlloc::from_raw(bucket_nnptr);
-                        bucket.take_first();
-                        loaded = new;
+    // The non-null children of this table together with their slots, sorted
+    // by slot. The table must be either compressed or frozen, so that its
+    // slots hold no descriptors.
+    fn children(&self) -> Vec<(usize, *mut ())> {
+        let mut children = Vec::with_capacity(self.nodes.len());
+
+        if self.is_compressed() {
+            let mut nodes = self.nodes.iter();
+            for (index, &word) in self.bitmap.iter().enumerate() {
+                let mut word = word;
+                while word != 0 {
+                    let slot = index * 64 + word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    // The bitmap has one bit set per node. Compressed tables
+                    // are never frozen.
+                    let loaded = nodes.next().unwrap().atomic.load(Acquire);
+                    if !loaded.is_null() {
+                        children.push((slot, loaded));
                     }
                 }
-            } else if loaded as usize & 1 == 0 {
-                // We keep pointers to Buckets with the lower bit cleared.
-                let bucket = &*(loaded as *mut Bucket<K, V>);
-
-                // If the hash of the bucket is equal to ours, there is no need
-                // for us to branch. Actually, we must not do it. We must insert
-                // in the bucket.
-                if bucket.hash() == hash {
-                    match bucket.insert(inserter, pause, incin) {
-                        InsertRes::Created => break Insertion::Created,
+            }
+        } else {
+            for (slot, node) in self.nodes.iter().enumerate() {
+                let loaded = unfrozen(node.atomic.load(Acquire));
+                debug_assert!(loaded as usize & DESC == 0);
+                if !loaded.is_null() {
+                    children.push((slot, loaded));
+                }
+            }
+        }
 
-                        InsertRes::Updated(old) => {
-                            break Insertion::Updated(old);
-                        }
+        children
+    }
 
-                        InsertRes::Failed(inserter) => {
-                            break Insertion::Failed(inserter);
-                        }
+    // Copies this compressed table with the given slot holding the given
+    // pointer, returning the marked pointer which should replace this table.
+    // See `from_children`.
+    fn with_slot(
+        &self,
+        slot: usize,
+        ptr: *mut (),
+        gen: u64,
+        capacity: usize,
+        allocs: &mut Vec<NonNull<Self>>,
+    ) -> *mut () {
+        let mut children = self.children();
+
+        match children.binary_search_by_key(&slot, |&(slot, _)| slot) {
+            Ok(index) if ptr.is_null() => {
+                children.remove(index);
+            }
+            Ok(index) => children[index].1 = ptr,
+            Err(index) if !ptr.is_null() => children.insert(index, (slot, ptr)),
+            Err(_) => (),
+        }
 
-                        // This means we must delete the bucket entirely. And
-                        // try again, obviously.
-                        InsertRes::Delete(returned) => {
-                            let ptr = &table.nodes[index].atomic;
-                            let res = ptr.compare_exchange(loaded, null_mut(), AcqRel, Acquire);
-
-                            match res {
-                                Ok(_) => {
-                                    let alloc = OwnedAlloc::from_raw(NonNull::new_unchecked(
-                                        loaded as *mut _,
-                                    ));
-                                    incin.add(Garbage::Bucket(alloc));
-                                    loaded = null_mut()
-                                }
-
-                                Err(new) => {
-                                    loaded = new;
-                                }
-                            }
+        Self::from_children(self.bits, capacity, gen, &children, allocs)
+    }
 
-                            inserter = returned;
-                        }
+    // Returns the marked pointer to a table with the given bits holding the
+    // given children, which must be sorted by slot. The table is full, with
+    // the given generation, if it has more children than the given capacity
+    // of compressed tables. Besides, the pointer is null if there are no children, and it is
+    // the only child if it is a bucket. Every allocated table is pushed into
+    // `allocs`.
+    fn from_children(
+        bits: u32,
+        capacity: usize,
+        gen: u64,
+        children: &[(usize, *mut ())],
+        allocs: &mut Vec<NonNull<Self>>,
+    ) -> *mut () {
+        match children.len() {
+            0 => null_mut(),
+
+            // Cleared lower bit means this is a bucket, and it can be moved up
+            // since the hash bits consumed so far lead to it.
+            1 if children[0].1 as usize & 1 == 0 => children[0].1,
+
+            len => {
+                let table = if len > capacity {
+                    let table = Self::new_alloc(bits, gen);
+                    for &(slot, ptr) in children {
+                        table.nodes[slot].atomic.store(ptr, Relaxed);
                     }
+                    table
                 } else {
-                    // In the case hashes aren't equal, we will branch!
-                    let new_table = tbl_cache.take_or(|| Self::new_alloc());
-                    let other_shifted = bucket.hash() >> (depth * BITS);
-                    let other_index = other_shifted as usize & ((1 << BITS) - 1);
-
-                    // Placing the found bucket into the new table first.
-                    new_table.nodes[other_index].atomic.store(loaded, Relaxed);
+                    Self::compressed_alloc(bits, gen, children)
+                };
 
-                    let new_table_nnptr = new_table.into_raw();
-                    let res = table.nodes[index].atomic.compare_exchange(
-                        loaded,
-                        // Note we mark the lower bit!
-                        (new_table_nnptr.as_ptr() as usize | 1) as *mut (),
-                        AcqRel,
-                        Acquire,
-                    );
+                let nnptr = table.into_raw();
+                allocs.push(nnptr);
+                // Note we mark the lower bit!
+                (nnptr.as_ptr() as usize | 1) as *mut ()
+            }
+        }
+    }
 
-                    match res {
-                        Ok(_) => {
-                            // If we succeeded, let's act like we found another
-                            // table in this index.
-                            depth += 1;
-                            table = &*new_table_nnptr.as_ptr();
-                            shifted >>= BITS;
-                            // Compute the index from the shifted hash's lower
-                            // bits.
-                            index = shifted as usize & ((1 << BITS) - 1);
-                            // Load what is in the index before trying to
-                            // insert.
-                            loaded = table.nodes[index].atomic.load(Acquire);
-                        }
+    // Copies this full table into the given generation. Unsafe because the
+    // incinerator needs to be paused.
+    unsafe fn copy(&self, gen: u64, root: &Root<K, V>) -> OwnedAlloc<Self> {
+        let table = Self::new_alloc(self.bits, gen);
+        for (node, copy) in self.nodes.iter().zip(table.nodes.iter()) {
+            // The table might be frozen by a compaction which gave up after
+            // a snapshot was taken, but the copy is not.
+            copy.atomic.store(unfrozen(node.load(root)), Relaxed);
+        }
+        table
+    }
 
-                        Err(new) => {
-                            // If we failed -> clean up! And store the
-                            // allocation in
-                            // some cache, since allocating a table can be
-                            // really expensive due
-                            // to it's size.
-                            let new_table = OwnedAlloc::from_raw(new_table_nnptr);
-                            new_table.nodes[other_index]
-                                .atomic
-                                .store(null_mut(), Relaxed);
-                            tbl_cache.store(new_table);
-                            loaded = new;
-                        }
-                    }
+    // Tests if this full table has so few children that it should be
+    // compacted. Only half of the compressed capacity is allowed, so that
+    // a table growing and shrinking around the capacity does not keep being
+    // copied. Unsafe because the incinerator needs to be paused.
+    unsafe fn is_sparse(&self, root: &Root<K, V>) -> bool {
+        let limit = root.compressed_capacity() / 2;
+        let mut count = 0;
+        let mut bucket = false;
+
+        for node in self.nodes.iter() {
+            let loaded = unfrozen(node.load(root));
+            if !loaded.is_null() {
+                count += 1;
+                if count > limit.max(1) {
+                    return false;
                 }
-            } else {
-                // If none of other cases have been confirmed, the only
-                // remaining case is a branching table. Let's
-                // try to look at it.
-                depth += 1;
-                table = &*((loaded as usize & !1) as *mut Self);
-                shifted >>= BITS;
+                bucket = loaded as usize & 1 == 0;
+            }
+        }
+
+        count <= limit || bucket
+    }
 
-                // Compute the index from the shifted hash's lower
-                // bits.
-                index = shifted as usize & ((1 << BITS) - 1);
-                // Load what is in the index before trying to
-                // insert.
-                loaded = table.nodes[index].atomic.load(Acquire);
+    // Freezes every slot of this full table, so no one changes it anymore.
+    // Unsafe because the incinerator needs to be paused.
+    unsafe fn freeze(&self, root: &Root<K, V>) {
+        for node in self.nodes.iter() {
+            let mut loaded = node.load(root);
+            while loaded as usize & FROZEN == 0 {
+                let frozen = (loaded as usize | FROZEN) as *mut ();
+                match node
+                    .atomic
+                    .compare_exchange_weak(loaded, frozen, SeqCst, Relaxed)
+                {
+                    Ok(_) => break,
+                    // A pending write might have been placed in the slot.
+                    Err(_) => loaded = node.load(root),
+                }
             }
         }
     }
 
+    // Creates a compressed copy of this table if it fits the given capacity of
+    // compressed tables, and if it is either full or a compressed table with
+    // null nodes.
+    fn compress(&self, capacity: usize) -> Option<OwnedAlloc<Self>> {
+        let children = self.children();
+        let sparse = children.len() <= capacity;
+        let shrinks = !self.is_compressed() || children.len() < self.nodes.len();
+
+        if sparse && shrinks && !children.is_empty() {
+            Some(Self::compressed_alloc(self.bits, self.gen, &children))
+        } else {
+            None
+        }
+    }
+
+    // The memory used by this table, not counting sub-tables and buckets.
+    fn bytes(&self) -> usize {
+        mem::size_of::<Self>()
+            + self.nodes.len() * mem::size_of::<Node<K, V>>()
+            + self.bitmap.len() * mem::size_of::<u64>()
+    }
+
     // Unsafe because the incinerator needs to be paused and there are no
     // guarantees the passed pause comes from the incinerator used with the map
     // by other threads. Map implementation guarantees that.
-    pub unsafe fn remove<Q, F>(
+    pub unsafe fn get<'map, F>(
         &self,
-        key: &Q,
-        interactive: F,
+        root: &Root<K, V>,
+        is_match: F,
         hash: u64,
-        pause: &Pause<Garbage<K, V>>,
-        incin: &Arc<Incinerator<Garbage<K, V>>>,
-    ) -> Option<Removed<K, V>>
+        pause: Pause<'map, Garbage<K, V>>,
+    ) -> Option<ReadGuard<'map, K, V>>
     where
-        Q: ?Sized + Ord,
-        K: Borrow<Q>,
-        F: FnMut(&(K, V)) -> bool,
+        F: FnMut(&K) -> bool,
     {
-        let mut table = self;
         let mut shifted = hash;
+        let mut table = self;
 
         loop {
-            // Compute the index from the shifted hash's lower bits.
-            let index = shifted as usize & ((1 << BITS) - 1);
-            // Let's load to see what is in there.
-            let loaded = table.nodes[index].atomic.load(Acquire);
+            // An absent slot of a compressed table means we have nothing.
+            let node = match table.node(table.slot(shifted)) {
+                Some(node) => node,
+                None => break None,
+            };
+            // The slot may be frozen, but it still leads to the right place.
+            let loaded = unfrozen(node.load(root));
 
             // Null means we have nothing.
             if loaded.is_null() {
@@ -323,89 +391,144 @@ impl<K, V> Table<K, V> {
                     break None;
                 }
 
-                let res = bucket.remove(key, interactive, pause, incin);
-
-                // If this field is true it means the whole bucket must be
-                // removed. Regardless of failure or success.
-                if res.delete {
-                    let res = table.nodes[index].atomic.compare_exchange(
-                        loaded,
-                        null_mut(),
-                        Relaxed,
-                        Relaxed,
-                    );
-
-                    if res.is_ok() {
-                        let alloc = OwnedAlloc::from_raw(NonNull::new_unchecked(loaded as *mut _));
-                        incin.add(Garbage::Bucket(alloc));
-                    }
-                }
-                break res.pair;
+                break bucket.get(is_match, pause);
             }
 
+            // Shifting the hash so we test some other bits.
+            shifted >>= table.bits;
             // If none of other cases have been confirmed, the only remaining
             // case is a branching table. Let's try to look at it.
             table = &*((loaded as usize & !1) as *mut Self);
-            // Shifting the hash so we test some other bits.
-            shifted >>= BITS;
         }
     }
 
-    // Unsafe because calling this function and using the table again later will
-    // cause undefined behavior.
+    // Tests if there are no entries in this table nor in its sub-tables.
+    // Unsafe because the incinerator needs to be paused.
+    pub unsafe fn is_empty(&self, root: &Root<K, V>) -> bool {
+        let mut tables = Vec::new();
+        tables.push(self);
+
+        while let Some(table) = tables.pop() {
+            for node in table.nodes.iter() {
+                let loaded = unfrozen(node.load(root));
+
+                if loaded.is_null() {
+                    continue;
+                }
+
+                // Buckets are never empty.
+                if loaded as usize & 1 == 0 {
+                    return false;
+                }
+
+                tables.push(&*((loaded as usize & !1) as *mut Self));
+            }
+        }
+
+        true
+    }
+
+    // Walks this table and its sub-tables gathering statistics. Unsafe because
+    // the incinerator needs to be paused.
+    pub unsafe fn stats(&self, root: &Root<K, V>) -> MapStats {
+        let mut stats = MapStats::default();
+        let mut tables = Vec::new();
+        tables.push((self, 1));
+
+        while let Some((table, depth)) = tables.pop() {
+            stats.tables += 1;
+            stats.depth = stats.depth.max(depth);
+            stats.table_bytes += table.bytes();
+
+            if table.is_compressed() {
+                stats.compressed_tables += 1;
+                // Absent slots are empty as well.
+                stats.empty_slots += (1 << table.bits) - table.nodes.len();
+            }
+
+            for node in table.nodes.iter() {
+                let loaded = unfrozen(node.load(root));
+
+                if loaded.is_null() {
+                    stats.empty_slots += 1;
+                } else if loaded as usize & 1 == 0 {
+                    let bucket = &*(loaded as *mut Bucket<K, V>);
+                    stats.buckets += 1;
+                    stats.entries += bucket.len();
+                    stats.max_bucket_len = stats.max_bucket_len.max(bucket.len());
+                } else {
+                    let table = &*((loaded as usize & !1) as *mut Self);
+                    tables.push((table, depth + 1));
+                }
+            }
+        }
+
+        stats
+    }
+
+    // Frees what is under this table, except what is shared up to the given
+    // generation. Unsafe because calling this function and using the table
+    // again later will cause undefined behavior.
     #[inline]
-    pub unsafe fn free_nodes(&mut self, tbl_stack: &mut Vec<OwnedAlloc<Table<K, V>>>) {
-        for node in &self.nodes as &[Node<K, V>] {
-            Node::free_ptr(node.atomic.load(Relaxed), tbl_stack);
+    unsafe fn free_nodes(
+        &mut self,
+        shared: Option<u64>,
+        tbl_stack: &mut Vec<OwnedAlloc<Table<K, V>>>,
+    ) {
+        for node in self.nodes.iter() {
+            Node::free_ptr(node.atomic.load(Relaxed), shared, tbl_stack);
         }
     }
 
     #[inline]
-    pub fn clear(&mut self, tbl_stack: &mut Vec<OwnedAlloc<Table<K, V>>>) {
-        for node in &self.nodes as &[Node<K, V>] {
+    fn clear(&mut self, shared: Option<u64>, tbl_stack: &mut Vec<OwnedAlloc<Table<K, V>>>) {
+        for node in self.nodes.iter() {
             // This should be safe because we store only proper pointers.
             unsafe {
-                Node::free_ptr(node.atomic.swap(null_mut(), Relaxed), tbl_stack);
+                Node::free_ptr(node.atomic.swap(null_mut(), Relaxed), shared, tbl_stack);
             }
         }
     }
 
-    pub fn optimize_space(&mut self) -> OptSpaceRes<K, V> {
+    // Frees the given detached top table and everything under it, except what
+    // it shares with its frozen tree. Unsafe because the table must not be
+    // used anymore, and nothing else may free it.
+    unsafe fn free_top(top: NonNull<Self>) {
+        // The frozen tree is released last, since telling shared nodes apart
+        // reads their generations.
+        let frozen = (*top.as_ptr()).frozen.take();
+        let shared = frozen.as_ref().map(|frozen| frozen.gen());
+        let mut tables = Vec::new();
+        Node::<K, V>::free_ptr((top.as_ptr() as usize | 1) as *mut (), shared, &mut tables);
+
+        while let Some(mut table) = tables.pop() {
+            table.free_nodes(shared, &mut tables);
+        }
+    }
+
+    // Optimizes the space of this table and of its sub-tables, except those
+    // shared up to the given generation, which are left as they are. Sparse
+    // sub-tables are compressed if the given capacity allows it.
+    fn optimize_space(&mut self, shared: Option<u64>, capacity: usize) -> OptSpaceRes<K, V> {
         let mut removed = 0usize;
         let mut last_bucket = None;
 
-        for node in &self.nodes as &[Node<K, V>] {
+        for node in self.nodes.iter() {
             let loaded = node.atomic.load(Relaxed);
 
             if loaded.is_null() {
                 removed += 1;
             } else if loaded as usize & 1 == 0 {
-                let bucket_ptr = loaded as *mut Bucket<K, V>;
-                // This is safe because:
-                //
-                // 1. We have exclusive reference to the table.
-                //
-                // 2. We proper allocate pointers stored in the table.
-                //
-                // 3. Bucket pointers are not marked and we checked for it.
-                if unsafe { (*bucket_ptr).is_empty() } {
-                    node.atomic.store(null_mut(), Release);
-                    removed += 1;
-
-                    // This is safe because we have exclusive reference to the
-                    // map. Also, we remove the bucket from the table so no one
-                    // else will find it.
-                    unsafe {
-                        OwnedAlloc::from_raw(NonNull::new_unchecked(bucket_ptr));
-                    }
-                } else {
-                    // Safe because of the same things in the list above. Also,
-                    // we checked for null already, we can by-pass this check.
-                    let nnptr = unsafe { NonNull::new_unchecked(bucket_ptr) };
-                    last_bucket = Some(nnptr);
-                }
+                // This is safe because we checked for null already, we can
+                // by-pass this check. Buckets are never empty.
+                let nnptr = unsafe { NonNull::new_unchecked(loaded as *mut Bucket<K, V>) };
+                last_bucket = Some(nnptr);
             } else {
                 let table_ptr = (loaded as usize & !1) as *mut Table<K, V>;
+                // Safe because we only store properly allocated tables.
+                if is_shared(unsafe { (*table_ptr).gen }, shared) {
+                    continue;
+                }
 
                 // This is safe because:
                 //
@@ -416,8 +539,22 @@ impl<K, V> Table<K, V> {
                 // 3. Table pointers are marked and we checked for it.
                 //
                 // 4. We cleared the marked bit.
-                match unsafe { &mut *table_ptr }.optimize_space() {
-                    OptSpaceRes::NoOpt => (),
+                match unsafe { &mut *table_ptr }.optimize_space(shared, capacity) {
+                    OptSpaceRes::NoOpt => {
+                        // Safe because of the same things in the list above.
+                        if let Some(compressed) = unsafe { &*table_ptr }.compress(capacity) {
+                            let marked = (compressed.into_raw().as_ptr() as usize | 1) as *mut ();
+                            node.atomic.store(marked, Relaxed);
+                            // This is safe because we have exclusive reference
+                            // to the map. Also, the inner table was replaced by
+                            // its compressed copy so no one else will find it.
+                            // Dropping a table does not drop its children.
+                            unsafe {
+                                let nnptr = NonNull::new_unchecked(table_ptr);
+                                OwnedAlloc::from_raw(nnptr);
+                            }
+                        }
+                    }
 
                     OptSpaceRes::Remove => {
                         node.atomic.store(null_mut(), Relaxed);
@@ -454,8 +591,16 @@ impl<K, V> Table<K, V> {
         }
     }
 
-    pub fn load_index(&self, index: usize, ordering: Ordering) -> Option<*mut ()> {
-        self.nodes.get(index).map(|node| node.atomic.load(ordering))
+    // Loads the pointer at the given index of the nodes, without the frozen
+    // bit, unless the index is out of bounds. Unsafe because the incinerator
+    // needs to be paused.
+    pub unsafe fn load_index(&self, index: usize, root: &Root<K, V>) -> Option<*mut ()> {
+        self.nodes.get(index).map(|node| unfrozen(node.load(root)))
+    }
+
+    // Just like `load_index`, but with exclusive access to the table.
+    pub fn load_index_mut(&mut self, index: usize) -> Option<*mut ()> {
+        self.nodes.get_mut(index).map(|node| *node.atomic.get_mut())
     }
 }
 
@@ -463,41 +608,1219 @@ impl<K, V> fmt::Debug for Table<K, V> {
     fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
         write!(
             fmtr,
-            "Table {{ nodes: {:?} }}",
-            &self.nodes as &[Node<K, V>]
+            "Table {{ bits: {}, gen: {}, bitmap: {:?}, nodes: {:?} }}",
+            self.bits, self.gen, &self.bitmap, &self.nodes
         )
     }
 }
 
-struct Node<K, V> {
-    // First lower bit is 0 for leaf and 1 for branch
-    atomic: AtomicPtr<()>,
-    _marker: PhantomData<(K, V)>,
+// The atomic pointer to the top table of a map. Taking a snapshot replaces the
+// top table by a copy of the next generation, and the old one is kept for the
+// snapshot.
+pub struct Root<K, V> {
+    // The bits of every table.
+    bits: u32,
+    // Whether sparse sub-tables are compressed. See `MapBuilder::compressed`.
+    compressed: bool,
+    atomic: AtomicPtr<Table<K, V>>,
+    // Notified of every successful insertion and removal.
+    watchers: Watchers<K, V>,
 }
 
-impl<K, V> Node<K, V> {
-    // Unsafe because it is *pretty easy* to make undefined behavior out of this
-    // because the pointer does not have even a fixed type.
-    unsafe fn free_ptr(ptr: *mut (), tbl_stack: &mut Vec<OwnedAlloc<Table<K, V>>>) {
-        if ptr.is_null() {
-            return;
+impl<K, V> Root<K, V> {
+    pub fn new(bits: u32, compressed: bool) -> Self {
+        Self {
+            bits,
+            compressed,
+            atomic: AtomicPtr::new(Table::new_alloc(bits, 0).into_raw().as_ptr()),
+            watchers: Watchers::new(),
         }
+    }
 
-        if ptr as usize & 1 == 0 {
-            OwnedAlloc::from_raw(NonNull::new_unchecked(ptr as *mut Bucket<K, V>));
+    #[inline]
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    #[inline]
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    // The maximum number of children of the compressed tables of this map.
+    // Zero means tables are never compressed.
+    #[inline]
+    fn compressed_capacity(&self) -> usize {
+        if self.compressed {
+            Table::<K, V>::compressed_capacity(self.bits)
         } else {
-            let table_ptr = (ptr as usize & !1) as *mut Table<K, V>;
+            0
+        }
+    }
 
-            debug_assert!(!table_ptr.is_null());
-            tbl_stack.push(OwnedAlloc::from_raw(NonNull::new_unchecked(table_ptr)));
+    // Loads the current top table. Unsafe because the incinerator needs to
+    // be paused.
+    #[inline]
+    pub unsafe fn load(&self) -> &Table<K, V> {
+        &*self.atomic.load(SeqCst)
+    }
+
+    pub fn get_mut(&mut self) -> &mut Table<K, V> {
+        // This is safe because we have exclusive reference to the root, and we
+        // only store properly allocated tables in it.
+        unsafe { &mut **self.atomic.get_mut() }
+    }
+
+    pub fn into_table(mut self) -> OwnedAlloc<Table<K, V>> {
+        let ptr = *self.atomic.get_mut();
+        drop(mem::take(&mut self.watchers));
+        mem::forget(self);
+        // This is safe because we have ownership over the root, and we only
+        // store properly allocated tables in it.
+        unsafe { OwnedAlloc::from_raw(NonNull::new_unchecked(ptr)) }
+    }
+
+    // Creates a root whose top table shares everything under the top table of
+    // the given frozen tree, for `Map::snapshot_mut`.
+    pub fn with_frozen(frozen: Arc<Frozen<K, V>>, compressed: bool) -> Self {
+        // This is safe because the frozen tree keeps its top table alive, and
+        // the table is frozen, so its slots hold no descriptors.
+        let top = unsafe { frozen.top.as_ref() };
+        let mut new = Table::new_alloc(top.bits, top.gen + 1);
+        for (slot, ptr) in top.children() {
+            new.nodes[slot].atomic.store(ptr, Relaxed);
+        }
+        let bits = top.bits;
+        new.frozen = Some(frozen);
+
+        Self {
+            bits,
+            compressed,
+            atomic: AtomicPtr::new(new.into_raw().as_ptr()),
+            watchers: Watchers::new(),
         }
     }
-}
 
-impl<K, V> Node<K, V> {
-    fn new() -> Self {
+    // Takes a snapshot: freezes the top table, replaces it by a copy of the
+    // next generation, and returns it. The frozen table is never changed
+    // again, and neither is anything under it, since operations copy any
+    // table of an older generation before modifying it. Unsafe because the
+    // incinerator needs to be paused, and the snapshot must only be read while
+    // the pause is kept.
+    pub unsafe fn snapshot(&self, incin: &Arc<Incinerator<Garbage<K, V>>>) -> &Table<K, V> {
+        let top = self.load();
+        // Whatever else is freezing the table, its replacement does for us.
+        let _ = top
+            .freezer
+            .compare_exchange(null_mut(), plain(), SeqCst, SeqCst);
+        self.renew(top, incin);
+        top
+    }
+
+    // Just like a snapshot, but the frozen top table is taken over by the
+    // returned frozen tree, which shares it, and everything under it, with
+    // the copy of the next generation replacing it. Unsafe because the
+    // incinerator needs to be paused.
+    pub unsafe fn share(
+        &self,
+        clone: CloneFn<K, V>,
+        incin: &Arc<Incinerator<Garbage<K, V>>>,
+    ) -> Arc<Frozen<K, V>> {
+        loop {
+            let top = self.load();
+            let frozen = Arc::into_raw(Arc::new(Frozen {
+                top: NonNull::from(top),
+                clone,
+            })) as *mut Frozen<K, V>;
+
+            let res = top
+                .freezer
+                .compare_exchange(null_mut(), frozen, SeqCst, SeqCst);
+            let freezer = match res {
+                Ok(_) => frozen,
+                Err(found) => {
+                    // Our frozen tree never got the table, so it must not
+                    // destroy it.
+                    mem::forget(Arc::try_unwrap(Arc::from_raw(frozen)));
+                    found
+                }
+            };
+
+            self.renew(top, incin);
+            if freezer != plain() {
+                // This is safe because the reference of the freezer is only
+                // released through the incinerator.
+                Arc::increment_strong_count(freezer);
+                break Arc::from_raw(freezer);
+            }
+            // A snapshot or `clear_shared` froze the table first.
+        }
+    }
+
+    // Replaces the given top table by a copy of the next generation, unless
+    // someone else did it already. The table must have its freezer set, and
+    // it is frozen first, since whoever set it might not be done yet. Unsafe
+    // because the incinerator needs to be paused.
+    unsafe fn renew(&self, top: &Table<K, V>, incin: &Arc<Incinerator<Garbage<K, V>>>) {
+        top.freeze(self);
+        let freezer = top.freezer.load(SeqCst);
+        debug_assert!(!freezer.is_null());
+
+        let mut new = Table::new_alloc(top.bits, top.gen + 1);
+        for (slot, ptr) in top.children() {
+            new.nodes[slot].atomic.store(ptr, Relaxed);
+        }
+        new.frozen = if freezer == plain() {
+            top.frozen.clone()
+        } else {
+            // This is safe because the reference of the freezer is only
+            // released through the incinerator.
+            Arc::increment_strong_count(freezer);
+            Some(Arc::from_raw(freezer))
+        };
+        let new_nnptr = new.into_raw();
+
+        let res = self.atomic.compare_exchange(
+            top as *const Table<K, V> as *mut _,
+            new_nnptr.as_ptr(),
+            SeqCst,
+            Relaxed,
+        );
+
+        if res.is_err() {
+            // Safe because this table was never shared.
+            OwnedAlloc::from_raw(new_nnptr);
+        } else if freezer == plain() {
+            // The old top table may still be read by other threads and by the
+            // snapshot, and so it needs to be destroyed by the incinerator.
+            // Dropping a table does not drop its children, which now belong
+            // to the copy.
+            let alloc = OwnedAlloc::from_raw(NonNull::from(top));
+            incin.add(Garbage::Table(alloc));
+        } else {
+            // The old top table belongs to the frozen tree now. Other threads
+            // may still be taking the reference of the freezer, and so it
+            // needs to be released by the incinerator.
+            top.freezer.store(plain(), SeqCst);
+            incin.add(Garbage::Frozen(Arc::from_raw(freezer)));
+        }
+    }
+
+    // Gives the top table its own copies of whatever it shares with its
+    // frozen tree, cloning the entries, and lets the frozen tree go, so that
+    // the tables can be modified in place. Unsafe because the incinerator
+    // needs to be paused: maps sharing the frozen tree might still be
+    // finishing pending writes in it.
+    pub unsafe fn unshare(&mut self) {
+        let top = self.load();
+        let (shared, clone) = match &top.frozen {
+            Some(frozen) => (frozen.gen(), frozen.clone),
+            None => return,
+        };
+
+        let mut tables = vec![top];
+        while let Some(table) = tables.pop() {
+            for node in table.nodes.iter() {
+                // We have exclusive access to the tables not shared.
+                let loaded = node.atomic.load(Relaxed);
+                if loaded.is_null() {
+                    continue;
+                }
+
+                if Node::<K, V>::gen(loaded) <= shared {
+                    node.atomic
+                        .store(self.deep_copy(loaded, top.gen, clone), Relaxed);
+                } else if loaded as usize & 1 == 1 {
+                    tables.push(&*((loaded as usize & !1) as *mut Table<K, V>));
+                }
+            }
+        }
+
+        self.get_mut().frozen = None;
+    }
+
+    // Copies the given node and everything under it into the given
+    // generation, cloning the entries. Unsafe because the incinerator needs to
+    // be paused.
+    unsafe fn deep_copy(&self, ptr: *mut (), gen: u64, clone: CloneFn<K, V>) -> *mut () {
+        // Cleared lower bit means this is a bucket.
+        if ptr as usize & 1 == 0 {
+            let bucket = (*(ptr as *mut Bucket<K, V>)).cloned(gen, clone);
+            return OwnedAlloc::new(bucket).into_raw().as_ptr() as *mut ();
+        }
+
+        let table = &*((ptr as usize & !1) as *mut Table<K, V>);
+        let copy = if table.is_compressed() {
+            let children = table
+                .children()
+                .into_iter()
+                .map(|(slot, child)| (slot, self.deep_copy(child, gen, clone)))
+                .collect::<Vec<_>>();
+            Table::<K, V>::compressed_alloc(table.bits, gen, &children)
+        } else {
+            let copy = Table::new_alloc(table.bits, gen);
+            for (node, copied) in table.nodes.iter().zip(copy.nodes.iter()) {
+                // A pending write found here is aborted, since the generation
+                // of the table is not the one of any top table anymore.
+                let loaded = unfrozen(node.load(self));
+                if !loaded.is_null() {
+                    copied
+                        .atomic
+                        .store(self.deep_copy(loaded, gen, clone), Relaxed);
+                }
+            }
+            copy
+        };
+
+        // Note we mark the lower bit!
+        (copy.into_raw().as_ptr() as usize | 1) as *mut ()
+    }
+
+    // Removes every entry, leaving those shared with the frozen tree to it.
+    pub fn clear(&mut self) {
+        let top = self.get_mut();
+        let shared = top.shared_gen();
+        let mut tables = Vec::new();
+        top.clear(shared, &mut tables);
+
+        while let Some(mut table) = tables.pop() {
+            // This is safe because we won't be using these tables anymore. We
+            // won't load its nodes' contents.
+            unsafe { table.free_nodes(shared, &mut tables) }
+        }
+        // Released last, since telling shared nodes apart reads their
+        // generations.
+        top.frozen = None;
+    }
+
+    // Tries to optimize space by removing unnecessary tables, except those
+    // shared with the frozen tree.
+    pub fn optimize_space(&mut self) {
+        let capacity = self.compressed_capacity();
+        let top = self.get_mut();
+        let shared = top.shared_gen();
+        top.optimize_space(shared, capacity);
+    }
+
+    // Unsafe because the incinerator needs to be paused and there are no
+    // guarantees the incinerator is the one used with the map by other
+    // threads. Map implementation guarantees that.
+    #[inline(never)]
+    pub unsafe fn insert<I>(
+        &self,
+        mut inserter: I,
+        hash: u64,
+        incin: &Arc<Incinerator<Garbage<K, V>>>,
+    ) -> Insertion<K, V, I>
+    where
+        I: Inserter<K, V>,
+        K: Eq,
+    {
+        let mut cursor = Cursor::new(self, hash);
+        let mut tbl_cache = Cache::<OwnedAlloc<Table<K, V>>>::new();
+
+        loop {
+            let loaded = cursor.loaded;
+
+            if loaded as usize & FROZEN != 0 {
+                // The table is being compacted or the top table is being
+                // replaced. Let's help and start over.
+                cursor.help(incin);
+            } else if loaded.is_null() {
+                // Let's test the found conditions.
+                inserter.input(None);
+                let pair = match inserter.pointer() {
+                    // The inserter accepted the conditions.
+                    Some(nnptr) => nnptr,
+                    // The inserter rejected the conditions.
+                    None => break Insertion::Failed(inserter),
+                };
+
+                // Allocation of a bucket containing a single entry. Our pair.
+                let bucket = Bucket::new(hash, cursor.top.gen, pair);
+                let bucket_nnptr = OwnedAlloc::new(bucket).into_raw();
+
+                // We try to put it in the slot.
+                if cursor.replace(bucket_nnptr.as_ptr() as *mut (), incin) {
+                    // Let's not forget to prevent the inserter from
+                    // deallocating the pointer.
+                    inserter.take_pointer();
+                    self.watchers
+                        .notify(hash, Change::Inserted(pair.as_ref()), incin);
+                    break Insertion::Created;
+                }
+
+                // If we failed this try, we have to clean up. Dropping a
+                // bucket does not drop its pairs.
+                OwnedAlloc::from_raw(bucket_nnptr);
+            } else if loaded as usize & 1 == 0 {
+                // We keep pointers to Buckets with the lower bit cleared.
+                let bucket = &*(loaded as *mut Bucket<K, V>);
+
+                // If the hash of the bucket is equal to ours, there is no need
+                // for us to branch. Actually, we must not do it. We must insert
+                // in the bucket.
+                if bucket.hash() == hash {
+                    // Let's test the found conditions.
+                    let index = bucket.find(inserter.key());
+                    inserter.input(index.map(|index| &*bucket.pair(index).as_ptr()));
+                    let pair = match inserter.pointer() {
+                        // The inserter accepted the conditions.
+                        Some(nnptr) => nnptr,
+                        // The inserter rejected the conditions.
+                        None => break Insertion::Failed(inserter),
+                    };
+
+                    // A bucket shared with a frozen tree keeps its pairs, so
+                    // we work on a copy with clones of them.
+                    let owned = cursor
+                        .shared_clone(bucket)
+                        .map(|clone| bucket.cloned(cursor.top.gen, clone));
+                    let bucket = owned.as_ref().unwrap_or(bucket);
+
+                    // The bucket is copied with our pair, replacing the one
+                    // with the same key, if any.
+                    let old = index.map(|index| bucket.pair(index));
+                    let new = bucket.with_pair(index, pair, cursor.top.gen);
+                    let new_nnptr = OwnedAlloc::new(new).into_raw();
+
+                    if cursor.replace_bucket(new_nnptr.as_ptr() as *mut (), incin) {
+                        inserter.take_pointer();
+                        break match old {
+                            Some(nnptr) => {
+                                self.watchers
+                                    .notify(hash, Change::Updated(pair.as_ref()), incin);
+                                let alloc = OwnedAlloc::from_raw(nnptr);
+                                Insertion::Updated(Removed::new(alloc, incin))
+                            }
+                            None => {
+                                self.watchers
+                                    .notify(hash, Change::Inserted(pair.as_ref()), incin);
+                                Insertion::Created
+                            }
+                        };
+                    }
+
+                    // If we failed this try, we have to clean up, clones
+                    // included.
+                    OwnedAlloc::from_raw(new_nnptr);
+                    if let Some(owned) = &owned {
+                        owned.free_pairs();
+                    }
+                } else {
+                    // In the case hashes aren't equal, we will branch! Both
+                    // hashes share the bits consumed so far, and they differ,
+                    // so the shift never reaches the hash's width.
+                    let other_shifted = bucket.hash() >> (cursor.depth * self.bits);
+                    let other_slot = other_shifted as usize & ((1 << self.bits) - 1);
+
+                    // Placing the found bucket into the new table first.
+                    let new_table = Table::branch_alloc(
+                        self.bits,
+                        self.compressed_capacity(),
+                        cursor.top.gen,
+                        other_slot,
+                        loaded,
+                        &mut tbl_cache,
+                    );
+                    let new_table_nnptr = new_table.into_raw();
+
+                    // Note we mark the lower bit! If we succeed, the cursor
+                    // will find the new table and enter it.
+                    let marked = (new_table_nnptr.as_ptr() as usize | 1) as *mut ();
+                    if !cursor.replace(marked, incin) {
+                        // If we failed -> clean up! And store the allocation
+                        // of a full table in some cache, since allocating it
+                        // can be really expensive due to its size.
+                        let new_table = OwnedAlloc::from_raw(new_table_nnptr);
+                        if !new_table.is_compressed() {
+                            new_table.nodes[other_slot]
+                                .atomic
+                                .store(null_mut(), Relaxed);
+                            tbl_cache.store(new_table);
+                        }
+                    }
+                }
+            } else {
+                // If none of other cases have been confirmed, the only
+                // remaining case is a branching table. Let's
+                // try to look at it.
+                cursor.descend(incin);
+            }
+        }
+    }
+
+    // Unsafe because the incinerator needs to be paused and there are no
+    // guarantees the incinerator is the one used with the map by other
+    // threads. Map implementation guarantees that.
+    pub unsafe fn remove<Q, F>(
+        &self,
+        key: &Q,
+        mut interactive: F,
+        hash: u64,
+        incin: &Arc<Incinerator<Garbage<K, V>>>,
+    ) -> Option<Removed<K, V>>
+    where
+        Q: ?Sized + Eq,
+        K: Borrow<Q>,
+        F: FnMut(&(K, V)) -> bool,
+    {
+        let mut cursor = Cursor::new(self, hash);
+
+        loop {
+            let loaded = cursor.loaded;
+
+            // The table is being compacted or the top table is being replaced.
+            // Let's help and start over.
+            if loaded as usize & FROZEN != 0 {
+                cursor.help(incin);
+                continue;
+            }
+
+            // Null means we have nothing.
+            if loaded.is_null() {
+                break None;
+            }
+
+            // Cleared lower bit means this is a bucket.
+            if loaded as usize & 1 == 0 {
+                let bucket = &*(loaded as *mut Bucket<K, V>);
+
+                // This bucket only matters if it has the same hash we do.
+                if bucket.hash() != hash {
+                    break None;
+                }
+
+                let index = match bucket.find(key) {
+                    Some(index) => index,
+                    None => break None,
+                };
+
+                // Let's test if the met conditions are ok!
+                if !interactive(bucket.pair(index).as_ref()) {
+                    break None;
+                }
+
+                // A bucket shared with a frozen tree keeps its pairs, so we
+                // work on a copy with clones of them.
+                let owned = cursor
+                    .shared_clone(bucket)
+                    .map(|clone| bucket.cloned(cursor.top.gen, clone));
+                let bucket = owned.as_ref().unwrap_or(bucket);
+                let pair = bucket.pair(index);
+
+                // The bucket is copied without our pair. If it was the only
+                // one, the bucket is deleted instead.
+                let new = match bucket.without(index, cursor.top.gen) {
+                    Some(new) => OwnedAlloc::new(new).into_raw().as_ptr() as *mut (),
+                    None => null_mut(),
+                };
+
+                if cursor.replace_bucket(new, incin) {
+                    self.watchers
+                        .notify(hash, Change::Removed(pair.as_ref()), incin);
+                    break Some(Removed::new(OwnedAlloc::from_raw(pair), incin));
+                }
+
+                // If we failed this try, we have to clean up, clones included.
+                if let Some(nnptr) = NonNull::new(new as *mut Bucket<K, V>) {
+                    OwnedAlloc::from_raw(nnptr);
+                }
+                if let Some(owned) = &owned {
+                    owned.free_pairs();
+                }
+                continue;
+            }
+
+            // If none of other cases have been confirmed, the only remaining
+            // case is a branching table. Let's try to look at it.
+            cursor.descend(incin);
+        }
+    }
+
+    // Replaces the top table by an empty one of the next generation, and
+    // sends the old one to the incinerator along with everything under it.
+    // Returns how many entries it had. Just like with a snapshot, the old top
+    // table is frozen first, and pending writes below it abort once the
+    // generation changes, so operations racing with this either take effect
+    // before it or start over from the new top table. Unsafe because the
+    // incinerator needs to be paused and there are no guarantees the
+    // incinerator is the one used with the map by other threads. Map
+    // implementation guarantees that.
+    pub unsafe fn clear_shared(&self, incin: &Arc<Incinerator<Garbage<K, V>>>) -> usize {
+        loop {
+            let top = self.load();
+            let _ = top
+                .freezer
+                .compare_exchange(null_mut(), plain(), SeqCst, SeqCst);
+            top.freeze(self);
+
+            let new = Table::new_alloc(top.bits, top.gen + 1).into_raw();
+            let res = self.atomic.compare_exchange(
+                top as *const Table<K, V> as *mut _,
+                new.as_ptr(),
+                SeqCst,
+                Relaxed,
+            );
+
+            if res.is_err() {
+                // Someone finishing a snapshot, or helping one, copied the
+                // frozen table first. Let's clear the copy.
+                OwnedAlloc::from_raw(new);
+                continue;
+            }
+
+            // Note we mark the lower bit!
+            let marked = (top as *const Table<K, V> as usize | 1) as *mut ();
+            let entries = top.stats(self).entries;
+            if self.watchers.is_active() {
+                self.notify_removed(marked, incin);
+            }
+
+            let freezer = top.freezer.load(SeqCst);
+            if freezer == plain() {
+                // The old top table may still be read by other threads and by
+                // snapshots, and so it needs to be destroyed by the
+                // incinerator.
+                incin.add(Garbage::SubTree(SubTree::new(NonNull::from(top))));
+            } else {
+                // `Map::snapshot_mut` froze the table first, and it belongs
+                // to the frozen tree. See `renew`.
+                top.freezer.store(plain(), SeqCst);
+                incin.add(Garbage::Frozen(Arc::from_raw(freezer)));
+            }
+            break entries;
+        }
+    }
+}
+
+impl<K, V> Root<K, V> {
+    // Registers the given watcher. Unsafe because the incinerator needs to be
+    // paused.
+    pub unsafe fn watch(&self, watcher: Watcher<K, V>, incin: &Arc<Incinerator<Garbage<K, V>>>) {
+        self.watchers.add(watcher, incin);
+    }
+
+    // Tests if somebody might be watching the changes.
+    pub fn is_watched(&self) -> bool {
+        self.watchers.is_active()
+    }
+
+    // Notifies the watchers of the removal of every entry under the given
+    // node, which is either detached or about to be. Unsafe because the
+    // incinerator needs to be paused.
+    pub unsafe fn notify_removed(&self, node: *mut (), incin: &Arc<Incinerator<Garbage<K, V>>>) {
+        let mut nodes = vec![node];
+
+        while let Some(node) = nodes.pop() {
+            if node.is_null() {
+                continue;
+            }
+
+            if node as usize & 1 == 0 {
+                let bucket = &*(node as *mut Bucket<K, V>);
+                for index in 0..bucket.len() {
+                    let pair = bucket.pair(index).as_ref();
+                    self.watchers
+                        .notify(bucket.hash(), Change::Removed(pair), incin);
+                }
+            } else {
+                let table = &*((node as usize & !1) as *mut Table<K, V>);
+                let mut index = 0;
+                while let Some(loaded) = table.load_index(index, self) {
+                    nodes.push(loaded);
+                    index += 1;
+                }
+            }
+        }
+    }
+}
+
+impl<K, V> Drop for Root<K, V> {
+    fn drop(&mut self) {
+        // Safe because we won't use these nodes anymore. We are in the
+        // destructor, and we only store properly allocated tables.
+        unsafe { Table::free_top(NonNull::new_unchecked(*self.atomic.get_mut())) }
+    }
+}
+
+impl<K, V> fmt::Debug for Root<K, V> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Root {{ bits: {}, compressed: {}, atomic: {:?}, watchers: {:?} }}",
+            self.bits, self.compressed, self.atomic, self.watchers
+        )
+    }
+}
+
+// A top table detached from its map. Unlike dropping a table alone, dropping
+// it also drops every sub-table, bucket and entry under it, except what it
+// shares with its frozen tree.
+pub struct SubTree<K, V> {
+    top: NonNull<Table<K, V>>,
+}
+
+impl<K, V> SubTree<K, V> {
+    // Unsafe because the table must have been detached from the map, and must
+    // not be shared with anything else that frees it.
+    unsafe fn new(top: NonNull<Table<K, V>>) -> Self {
+        Self { top }
+    }
+}
+
+impl<K, V> Drop for SubTree<K, V> {
+    fn drop(&mut self) {
+        // Safe because the sub-tree was detached from the map and it is only
+        // dropped when no one can be using it anymore.
+        unsafe { Table::free_top(self.top) }
+    }
+}
+
+impl<K, V> fmt::Debug for SubTree<K, V> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "SubTree {{ top: {:?} }}", self.top)
+    }
+}
+
+// A top table frozen by `Map::snapshot_mut`, together with everything under
+// it that it does not share with a frozen tree of its own. The maps created
+// from it share all of this with each other, through their top tables and
+// their snapshots. None of them modifies nor destroys it: they copy it
+// before modifying it, cloning the entries, and the last one to release it
+// destroys it.
+pub struct Frozen<K, V> {
+    top: NonNull<Table<K, V>>,
+    // The write path has no `Clone` bounds, so cloning is taken when the
+    // frozen tree is created.
+    clone: CloneFn<K, V>,
+}
+
+impl<K, V> Frozen<K, V> {
+    // The generation up to which nodes belong to this frozen tree, or to the
+    // ones it shares with.
+    #[inline]
+    fn gen(&self) -> u64 {
+        // Safe because we keep the top table alive.
+        unsafe { self.top.as_ref().gen }
+    }
+}
+
+impl<K, V> Drop for Frozen<K, V> {
+    fn drop(&mut self) {
+        // Safe because the maps only release the frozen tree once they no
+        // longer use it, and no one else frees its top table.
+        unsafe { Table::free_top(self.top) }
+    }
+}
+
+impl<K, V> fmt::Debug for Frozen<K, V> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "Frozen {{ top: {:?} }}", self.top)
+    }
+}
+
+// Clones an entry shared with a frozen tree.
+type CloneFn<K, V> = fn(&(K, V)) -> (K, V);
+
+// What the freezer of a top table is set to when no frozen tree takes the
+// table over. It is never the address of a frozen tree.
+#[inline]
+fn plain<K, V>() -> *mut Frozen<K, V> {
+    NonNull::dangling().as_ptr()
+}
+
+// Tests if a node of the given generation is shared with a frozen tree,
+// given the generation up to which nodes are shared.
+#[inline]
+fn is_shared(gen: u64, shared: Option<u64>) -> bool {
+    shared.map_or(false, |shared| gen <= shared)
+}
+
+// A pending write to a slot of a full sub-table. Like a restricted
+// double-compare single-swap, it only takes effect if the top table still has
+// the generation of the sub-table, that is, if no snapshot was taken since.
+// Anyone finding it in the slot decides whether it takes effect and finishes
+// it.
+#[repr(align(/* at least */ 8))]
+pub struct Desc<K, V> {
+    old: *mut (),
+    new: *mut (),
+    gen: u64,
+    state: AtomicUsize,
+    _marker: PhantomData<(K, V)>,
+}
+
+impl<K, V> Desc<K, V> {
+    fn new(old: *mut (), new: *mut (), gen: u64) -> Self {
+        Self {
+            old,
+            new,
+            gen,
+            state: AtomicUsize::new(UNDECIDED),
+            _marker: PhantomData,
+        }
+    }
+
+    // Decides whether this descriptor, found marked in the given node, takes
+    // effect, and replaces it by either the new or the old pointer. Returns
+    // whether it took effect. Unsafe because the incinerator needs to be
+    // paused.
+    unsafe fn finish(&self, node: &Node<K, V>, marked: *mut (), root: &Root<K, V>) -> bool {
+        let mut state = self.state.load(Acquire);
+
+        if state == UNDECIDED {
+            let decided = if root.load().gen == self.gen {
+                COMMITTED
+            } else {
+                ABORTED
+            };
+            state = match self
+                .state
+                .compare_exchange(UNDECIDED, decided, AcqRel, Acquire)
+            {
+                Ok(_) => decided,
+                Err(found) => found,
+            };
+        }
+
+        let committed = state == COMMITTED;
+        let value = if committed { self.new } else { self.old };
+        // Whoever succeeds, the node ends up with the same pointer.
+        let _ = node.atomic.compare_exchange(marked, value, SeqCst, Relaxed);
+        committed
+    }
+}
+
+impl<K, V> fmt::Debug for Desc<K, V> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Desc {{ old: {:?}, new: {:?}, gen: {}, state: {:?} }}",
+            self.old, self.new, self.gen, self.state
+        )
+    }
+}
+
+// A position in the tables, used by operations which modify them. Full tables
+// are modified in place, while compressed tables are copied up to the closest
+// full table, the anchor, whose node is then replaced.
+struct Cursor<'map, K, V> {
+    root: &'map Root<K, V>,
+    // The top table the operation started from.
+    top: &'map Table<K, V>,
+    hash: u64,
+    shifted: u64,
+    // The depth of the current table.
+    depth: u32,
+    // The closest full table and its node.
+    table: &'map Table<K, V>,
+    anchor: &'map Node<K, V>,
+    // What was loaded from the anchor when entering the compressed tables.
+    anchor_loaded: *mut (),
+    // The compressed tables below the anchor, and the slots taken in them.
+    compressed: Vec<(&'map Table<K, V>, usize)>,
+    // What was loaded from the current slot.
+    loaded: *mut (),
+}
+
+impl<'map, K, V> Cursor<'map, K, V> {
+    // Unsafe because the incinerator needs to be paused.
+    unsafe fn new(root: &'map Root<K, V>, hash: u64) -> Self {
+        let top = root.load();
+        let anchor = &top.nodes[top.slot(hash)];
+        Self {
+            root,
+            top,
+            hash,
+            shifted: hash,
+            depth: 1,
+            table: top,
+            anchor,
+            anchor_loaded: null_mut(),
+            compressed: Vec::new(),
+            loaded: anchor.load(root),
+        }
+    }
+
+    // Goes back to the current top table. Unsafe because the incinerator needs
+    // to be paused.
+    unsafe fn restart(&mut self) {
+        self.top = self.root.load();
+        self.shifted = self.hash;
+        self.depth = 1;
+        self.table = self.top;
+        self.anchor = &self.top.nodes[self.top.slot(self.hash)];
+        self.compressed.clear();
+        self.loaded = self.anchor.load(self.root);
+    }
+
+    // Enters the sub-table loaded from the current slot. A full sub-table of
+    // an older generation than the top table might be shared with a
+    // snapshot, so it is first replaced by a copy, and the cursor goes on
+    // from wherever `replace` leaves it. Unsafe because the incinerator needs
+    // to be paused and the loaded pointer must be a marked table pointer.
+    unsafe fn descend(&mut self, incin: &Arc<Incinerator<Garbage<K, V>>>) {
+        let table = &*((self.loaded as usize & !1) as *mut Table<K, V>);
+
+        if !table.is_compressed() && table.gen != self.top.gen {
+            let copy = table.copy(self.top.gen, self.root).into_raw();
+            // Note we mark the lower bit!
+            let marked = (copy.as_ptr() as usize | 1) as *mut ();
+            let shared = is_shared(table.gen, self.top.shared_gen());
+
+            if self.replace(marked, incin) {
+                if !shared {
+                    // The old table is still read by the snapshot, and so it
+                    // needs to be destroyed by the incinerator. Dropping a
+                    // table does not drop its children, which now belong to
+                    // the copy.
+                    let alloc = OwnedAlloc::from_raw(NonNull::from(table));
+                    incin.add(Garbage::Table(alloc));
+                }
+            } else {
+                // Safe because this table was never shared.
+                OwnedAlloc::from_raw(copy);
+            }
+            return;
+        }
+
+        self.depth += 1;
+        self.shifted >>= table.bits;
+        let slot = table.slot(self.shifted);
+
+        if table.is_compressed() {
+            if self.compressed.is_empty() {
+                self.anchor_loaded = self.loaded;
+            }
+            self.compressed.push((table, slot));
+            self.loaded = table
+                .node(slot)
+                .map_or(null_mut(), |node| node.atomic.load(Acquire));
+        } else {
+            self.compressed.clear();
+            self.table = table;
+            self.anchor = &table.nodes[slot];
+            self.loaded = self.anchor.load(self.root);
+        }
+    }
+
+    // Writes the given pointer to the anchor, if it still holds the expected
+    // one. Below the top table, the write goes through a descriptor, so that
+    // it does not take effect if a snapshot was taken since the generation of
+    // the anchor's table. The top table needs none of this, since a snapshot
+    // freezes it first. In case of success, the cursor has the pointer loaded.
+    // In case of failure, the cursor either reloads the anchor or, if a
+    // snapshot was taken, goes back to the new top table. Unsafe because the
+    // incinerator needs to be paused and the pointer must be properly marked.
+    unsafe fn write(
+        &mut self,
+        expected: *mut (),
+        ptr: *mut (),
+        incin: &Arc<Incinerator<Garbage<K, V>>>,
+    ) -> bool {
+        let res = if ptr::eq(self.table, self.top) {
+            self.anchor
+                .atomic
+                .compare_exchange(expected, ptr, SeqCst, Relaxed)
+                .is_ok()
+        } else {
+            let desc = OwnedAlloc::new(Desc::new(expected, ptr, self.table.gen)).into_raw();
+            // Note we mark the third bit!
+            let marked = (desc.as_ptr() as usize | DESC) as *mut ();
+
+            let res = self
+                .anchor
+                .atomic
+                .compare_exchange(expected, marked, SeqCst, Relaxed);
+
+            if res.is_err() {
+                // Safe because this descriptor was never shared.
+                OwnedAlloc::from_raw(desc);
+                false
+            } else {
+                let committed = desc.as_ref().finish(self.anchor, marked, self.root);
+                // Other threads may still be finishing the descriptor, and so
+                // it needs to be destroyed by the incinerator. It is no longer
+                // in the node, though.
+                incin.add(Garbage::Desc(OwnedAlloc::from_raw(desc)));
+
+                if !committed {
+                    // A snapshot was taken. Let's start over from the new top
+                    // table.
+                    self.restart();
+                    return false;
+                }
+                true
+            }
+        };
+
+        self.loaded = if res {
+            ptr
+        } else {
+            self.anchor.load(self.root)
+        };
+        res
+    }
+
+    // Tries to replace what was loaded from the current slot by the given
+    // pointer. In case of success, the cursor has the pointer loaded, unless
+    // compressed tables were copied: then it goes back to the top table. In
+    // case of failure, the cursor either reloads the current slot or goes back
+    // to the top table, and the pointer is left for the caller to clean up.
+    // Unsafe because the incinerator needs to be paused and the pointer must
+    // be properly marked.
+    unsafe fn replace(&mut self, ptr: *mut (), incin: &Arc<Incinerator<Garbage<K, V>>>) -> bool {
+        if self.compressed.is_empty() {
+            return self.write(self.loaded, ptr, incin);
+        }
+
+        // Copying the compressed tables from the bottom up.
+        let mut allocs = Vec::new();
+        let mut new = ptr;
+        let capacity = self.root.compressed_capacity();
+        for &(table, slot) in self.compressed.iter().rev() {
+            new = table.with_slot(slot, new, self.top.gen, capacity, &mut allocs);
+        }
+
+        // Since compressed tables are only destroyed by the incinerator, the
+        // anchor cannot hold the same pointer again while we are paused.
+        let compressed = mem::take(&mut self.compressed);
+        let shared = self.top.shared_gen();
+        let res = self.write(self.anchor_loaded, new, incin);
+
+        if res {
+            for (table, _) in compressed {
+                if is_shared(table.gen, shared) {
+                    continue;
+                }
+                // The old copies may still be read by other threads, and so
+                // they need to be destroyed by the incinerator. Dropping a
+                // table does not drop its children, which now belong to the
+                // new copies.
+                let alloc = OwnedAlloc::from_raw(NonNull::from(table));
+                incin.add(Garbage::Table(alloc));
+            }
+        } else {
+            for nnptr in allocs {
+                // Safe because these tables were never shared.
+                OwnedAlloc::from_raw(nnptr);
+            }
+        }
+
+        // The copied tables are gone from the path, so we start over.
+        self.restart();
+        res
+    }
+
+    // Replaces the bucket loaded from the current slot by the given pointer,
+    // which is either a copy of the bucket or null. In case of success, the
+    // old bucket is sent to the incinerator, and if the bucket was deleted,
+    // the table it was in is compacted if this left the table sparse. Unsafe
+    // because the incinerator needs to be paused and the pointer must be
+    // properly marked.
+    unsafe fn replace_bucket(
+        &mut self,
+        ptr: *mut (),
+        incin: &Arc<Incinerator<Garbage<K, V>>>,
+    ) -> bool {
+        let bucket = self.loaded;
+        let top = self.top;
+        let table = self.table;
+        let shared = self.shared_clone(&*(bucket as *mut Bucket<K, V>)).is_some();
+
+        if !self.replace(ptr, incin) {
+            return false;
+        }
+
+        if !shared {
+            let alloc = OwnedAlloc::from_raw(NonNull::new_unchecked(bucket as *mut Bucket<K, V>));
+            // Needs to be destroyed by the incinerator as it is shared.
+            // Dropping a bucket does not drop its pairs, which belong to the
+            // copy.
+            incin.add(Garbage::Bucket(alloc));
+        }
+
+        // The top table is never compacted.
+        if ptr.is_null() && !ptr::eq(table, top) && table.is_sparse(self.root) {
+            self.compact(table, incin);
+        }
+        true
+    }
+
+    // The function cloning entries, if the given bucket of the path is shared
+    // with the frozen tree of the top table, which keeps its pairs.
+    fn shared_clone(&self, bucket: &Bucket<K, V>) -> Option<CloneFn<K, V>> {
+        let frozen = self.top.frozen.as_ref()?;
+        if bucket.gen() <= frozen.gen() {
+            Some(frozen.clone)
+        } else {
+            None
+        }
+    }
+
+    // Goes down the path until the current slot holds the given pointer,
+    // helping compactions on the way. Returns false if the path does not lead
+    // to the pointer anymore. Unsafe because the incinerator needs to be
+    // paused.
+    unsafe fn seek(&mut self, ptr: *mut (), incin: &Arc<Incinerator<Garbage<K, V>>>) -> bool {
+        loop {
+            let loaded = self.loaded;
+
+            if loaded == ptr {
+                break true;
+            }
+
+            if loaded as usize & FROZEN != 0 {
+                self.help(incin);
+            } else if loaded as usize & 1 == 1 {
+                self.descend(incin);
+            } else {
+                break false;
+            }
+        }
+    }
+
+    // Finishes what froze the closest full table, whose slot was found frozen,
+    // and goes back to the top table. That is either the replacement of the
+    // top table by a snapshot, or the compaction of a sub-table. Unsafe
+    // because the incinerator needs to be paused.
+    unsafe fn help(&mut self, incin: &Arc<Incinerator<Garbage<K, V>>>) {
+        let table = self.table;
+
+        if ptr::eq(table, self.top) {
+            self.root.renew(table, incin);
+            self.restart();
+        } else {
+            self.compact(table, incin);
+        }
+    }
+
+    // Freezes the given full sub-table, which must be in the path, and
+    // replaces it with its children in a compressed table, or with nothing,
+    // or with its only bucket. Just like `optimize_space`, but concurrent.
+    // Everyone finding a frozen slot helps, and only one succeeds in replacing
+    // the table. Then, goes back to the top table. Unsafe because the
+    // incinerator needs to be paused.
+    unsafe fn compact(
+        &mut self,
+        table: &'map Table<K, V>,
+        incin: &Arc<Incinerator<Garbage<K, V>>>,
+    ) {
+        table.freeze(self.root);
+
+        let mut allocs = Vec::new();
+        let new = Table::<K, V>::from_children(
+            table.bits,
+            self.root.compressed_capacity(),
+            self.top.gen,
+            &table.children(),
+            &mut allocs,
+        );
+        // Note we mark the lower bit!
+        let marked = (table as *const Table<K, V> as usize | 1) as *mut ();
+        self.restart();
+
+        loop {
+            if !self.seek(marked, incin) {
+                // Someone else replaced the table already.
+                for nnptr in allocs {
+                    // Safe because these tables were never shared.
+                    OwnedAlloc::from_raw(nnptr);
+                }
+                break;
+            }
+
+            if self.replace(new, incin) {
+                // The table may still be read by other threads, and so it
+                // needs to be destroyed by the incinerator. Dropping a table
+                // does not drop its children, which now belong to the
+                // replacement.
+                let alloc = OwnedAlloc::from_raw(NonNull::from(table));
+                incin.add(Garbage::Table(alloc));
+                break;
+            }
+        }
+
+        self.restart();
+    }
+}
+
+// Clears the frozen bit of a pointer loaded from a slot.
+#[inline]
+fn unfrozen(ptr: *mut ()) -> *mut () {
+    (ptr as usize & !FROZEN) as *mut ()
+}
+
+struct Node<K, V> {
+    // First lower bit is 0 for leaf and 1 for branch
+    atomic: AtomicPtr<()>,
+    _marker: PhantomData<(K, V)>,
+}
+
+impl<K, V> Node<K, V> {
+    // Loads the pointer of this node, finishing any pending write found in it
+    // first. The pointer might be frozen. Unsafe because the incinerator needs
+    // to be paused.
+    #[inline]
+    unsafe fn load(&self, root: &Root<K, V>) -> *mut () {
+        loop {
+            let loaded = self.atomic.load(SeqCst);
+            if loaded as usize & DESC == 0 {
+                break loaded;
+            }
+
+            let desc = &*((loaded as usize & !DESC) as *const Desc<K, V>);
+            desc.finish(self, loaded, root);
+        }
+    }
+
+    // The generation of the bucket or table the given pointer, neither frozen
+    // nor a descriptor, points to. Unsafe because the pointer must be valid.
+    unsafe fn gen(ptr: *mut ()) -> u64 {
+        if ptr as usize & 1 == 0 {
+            (*(ptr as *mut Bucket<K, V>)).gen()
+        } else {
+            (*((ptr as usize & !1) as *mut Table<K, V>)).gen
+        }
+    }
+
+    // Frees the given node, unless it is shared up to the given generation.
+    // Unsafe because it is *pretty easy* to make undefined behavior out of
+    // this because the pointer does not have even a fixed type.
+    unsafe fn free_ptr(
+        ptr: *mut (),
+        shared: Option<u64>,
+        tbl_stack: &mut Vec<OwnedAlloc<Table<K, V>>>,
+    ) {
+        // A detached sub-tree may have a frozen table whose compaction gave
+        // up. Descriptors, though, are always finished by their writers.
+        let ptr = unfrozen(ptr);
+        debug_assert!(ptr as usize & DESC == 0);
+        if ptr.is_null() || is_shared(Self::gen(ptr), shared) {
+            return;
+        }
+
+        if ptr as usize & 1 == 0 {
+            let bucket = OwnedAlloc::from_raw(NonNull::new_unchecked(ptr as *mut Bucket<K, V>));
+            bucket.free_pairs();
+        } else {
+            let table_ptr = (ptr as usize & !1) as *mut Table<K, V>;
+
+            debug_assert!(!table_ptr.is_null());
+            tbl_stack.push(OwnedAlloc::from_raw(NonNull::new_unchecked(table_ptr)));
+        }
+    }
+}
+
+impl<K, V> Node<K, V> {
+    fn new() -> Self {
+        Self::with_ptr(null_mut())
+    }
+
+    fn with_ptr(ptr: *mut ()) -> Self {
         Self {
-            atomic: AtomicPtr::new(null_mut()),
+            atomic: AtomicPtr::new(ptr),
             _marker: PhantomData,
         }
     }
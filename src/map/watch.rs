@@ -0,0 +1,279 @@
+use super::bucket::Garbage;
+use crate::channel::mpsc;
+use crate::incin::Incinerator;
+use crate::owned_alloc::OwnedAlloc;
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    fmt,
+    ptr::{null_mut, NonNull},
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering::*},
+};
+
+/// A change to an entry of a [`Map`](super::Map), received from the channels
+/// created by [`Map::watch`](super::Map::watch) and
+/// [`Map::watch_all`](super::Map::watch_all).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Event<K, V> {
+    /// An entry was inserted, and no entry had its key. Carries the new entry.
+    Inserted(K, V),
+    /// An entry replaced another one with the same key. Carries the new entry.
+    Updated(K, V),
+    /// An entry was removed. Carries the removed entry.
+    Removed(K, V),
+}
+
+impl<K, V> Event<K, V> {
+    /// The key of the entry which changed.
+    pub fn key(&self) -> &K {
+        match self {
+            Event::Inserted(key, _) | Event::Updated(key, _) | Event::Removed(key, _) => key,
+        }
+    }
+
+    /// The value carried by this event.
+    pub fn val(&self) -> &V {
+        match self {
+            Event::Inserted(_, val) | Event::Updated(_, val) | Event::Removed(_, val) => val,
+        }
+    }
+}
+
+// A change as seen by the write path, which borrows the entry instead of
+// cloning it, since nobody might be interested in it.
+pub enum Change<'pair, K, V> {
+    Inserted(&'pair (K, V)),
+    Updated(&'pair (K, V)),
+    Removed(&'pair (K, V)),
+}
+
+impl<'pair, K, V> Clone for Change<'pair, K, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'pair, K, V> Copy for Change<'pair, K, V> {}
+
+impl<'pair, K, V> Change<'pair, K, V> {
+    fn pair(self) -> &'pair (K, V) {
+        match self {
+            Change::Inserted(pair) | Change::Updated(pair) | Change::Removed(pair) => pair,
+        }
+    }
+}
+
+pub struct Watcher<K, V> {
+    // The hash and the key of the only entry watched, if any.
+    key: Option<(u64, K)>,
+    // The write path has no `Eq` nor `Clone` bounds, so the functions which
+    // need them are taken when the watcher is created.
+    eq: fn(&K, &K) -> bool,
+    to_event: fn(Change<K, V>) -> Event<K, V>,
+    sender: mpsc::Sender<Event<K, V>>,
+    // Set once the receiver is found disconnected.
+    dead: AtomicBool,
+}
+
+impl<K, V> Watcher<K, V> {
+    pub fn new(key: Option<(u64, K)>, sender: mpsc::Sender<Event<K, V>>) -> Self
+    where
+        K: Eq + Clone,
+        V: Clone,
+    {
+        Self {
+            key,
+            eq: K::eq,
+            to_event: to_event::<K, V>,
+            sender,
+            dead: AtomicBool::new(false),
+        }
+    }
+
+    // Sends the change if this watcher is interested in it. Returns whether
+    // the receiver is still connected.
+    fn notify(&self, hash: u64, change: Change<K, V>) -> bool {
+        if let Some((watched_hash, watched)) = &self.key {
+            if *watched_hash != hash || !(self.eq)(watched, &change.pair().0) {
+                // Changes of other keys still find a dropped receiver, or a
+                // key left alone would keep its watcher forever.
+                return self.sender.is_connected();
+            }
+        }
+
+        self.sender.send((self.to_event)(change)).is_ok()
+    }
+}
+
+impl<K, V> fmt::Debug for Watcher<K, V> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Watcher {{ all: {:?}, sender: {:?}, dead: {:?} }}",
+            self.key.is_none(),
+            self.sender,
+            self.dead
+        )
+    }
+}
+
+fn to_event<K, V>(change: Change<K, V>) -> Event<K, V>
+where
+    K: Clone,
+    V: Clone,
+{
+    match change {
+        Change::Inserted((key, val)) => Event::Inserted(key.clone(), val.clone()),
+        Change::Updated((key, val)) => Event::Updated(key.clone(), val.clone()),
+        Change::Removed((key, val)) => Event::Removed(key.clone(), val.clone()),
+    }
+}
+
+// The list of watchers is never modified in place. Adding or dropping a
+// watcher replaces the list by a copy, and the old one is destroyed through
+// the incinerator. The watchers themselves are shared between the copies.
+pub struct WatcherList<K, V> {
+    watchers: Vec<Arc<Watcher<K, V>>>,
+}
+
+impl<K, V> fmt::Debug for WatcherList<K, V> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "WatcherList {{ watchers: {:?} }}", self.watchers)
+    }
+}
+
+// The watchers of a map. The pointer is null while nobody watches, so then
+// the write path only pays for loading it.
+pub struct Watchers<K, V> {
+    list: AtomicPtr<WatcherList<K, V>>,
+}
+
+impl<K, V> Watchers<K, V> {
+    pub fn new() -> Self {
+        Self {
+            list: AtomicPtr::new(null_mut()),
+        }
+    }
+
+    // Tests if somebody might be watching.
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        !self.list.load(Acquire).is_null()
+    }
+
+    // Unsafe because the incinerator needs to be paused.
+    pub unsafe fn add(&self, watcher: Watcher<K, V>, incin: &Arc<Incinerator<Garbage<K, V>>>) {
+        let watcher = Arc::new(watcher);
+        self.replace(
+            |watchers| {
+                let mut watchers = watchers.to_vec();
+                watchers.push(watcher.clone());
+                watchers
+            },
+            incin,
+        );
+    }
+
+    // Sends the change to the interested watchers, and forgets the watchers
+    // whose receivers disconnected. Unsafe because the incinerator needs to be
+    // paused.
+    #[inline]
+    pub unsafe fn notify(
+        &self,
+        hash: u64,
+        change: Change<K, V>,
+        incin: &Arc<Incinerator<Garbage<K, V>>>,
+    ) {
+        let list = self.list.load(Acquire);
+        if list.is_null() {
+            return;
+        }
+
+        let mut found_dead = false;
+        // This is safe because the incinerator is paused and we checked for
+        // null already.
+        for watcher in &(*list).watchers {
+            if !watcher.notify(hash, change) {
+                watcher.dead.store(true, Relaxed);
+                found_dead = true;
+            }
+        }
+
+        if found_dead {
+            self.replace(
+                |watchers| {
+                    watchers
+                        .iter()
+                        .filter(|watcher| !watcher.dead.load(Relaxed))
+                        .cloned()
+                        .collect()
+                },
+                incin,
+            );
+        }
+    }
+
+    // Replaces the list by the one computed from the current watchers. An
+    // empty list is replaced by null. Unsafe because the incinerator needs to
+    // be paused.
+    unsafe fn replace<F>(&self, mut update: F, incin: &Arc<Incinerator<Garbage<K, V>>>)
+    where
+        F: FnMut(&[Arc<Watcher<K, V>>]) -> Vec<Arc<Watcher<K, V>>>,
+    {
+        let mut loaded = self.list.load(Acquire);
+
+        loop {
+            // This is safe because the incinerator is paused.
+            let watchers = match loaded.as_ref() {
+                Some(list) => update(&list.watchers),
+                None => update(&[]),
+            };
+
+            let new = if watchers.is_empty() {
+                null_mut()
+            } else {
+                OwnedAlloc::new(WatcherList { watchers })
+                    .into_raw()
+                    .as_ptr()
+            };
+
+            match self.list.compare_exchange(loaded, new, AcqRel, Acquire) {
+                Ok(_) => {
+                    if let Some(nnptr) = NonNull::new(loaded) {
+                        incin.add(Garbage::Watchers(OwnedAlloc::from_raw(nnptr)));
+                    }
+                    break;
+                }
+
+                Err(actual) => {
+                    // This is safe because the list was never shared.
+                    if let Some(nnptr) = NonNull::new(new) {
+                        OwnedAlloc::from_raw(nnptr);
+                    }
+                    loaded = actual;
+                }
+            }
+        }
+    }
+}
+
+impl<K, V> Default for Watchers<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Drop for Watchers<K, V> {
+    fn drop(&mut self) {
+        if let Some(nnptr) = NonNull::new(*self.list.get_mut()) {
+            // This is safe because we have exclusive reference to the
+            // watchers, and we only store properly allocated lists.
+            unsafe { OwnedAlloc::from_raw(nnptr) };
+        }
+    }
+}
+
+impl<K, V> fmt::Debug for Watchers<K, V> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "Watchers {{ list: {:?} }}", self.list)
+    }
+}
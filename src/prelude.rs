@@ -1,6 +1,7 @@
 #[cfg(feature = "std")]
 pub use crate::{
     channel::{mpmc, mpsc, spmc, spsc},
+    deque::Deque,
     map::Map,
     queue::Queue,
     set::Set,
@@ -1,5 +1,6 @@
 use crate::owned_alloc::OwnedAlloc;
 use crate::{
+    count::Counter,
     incin::Pause,
     ptr::{bypass_null, check_null_align},
     removable::Removable,
@@ -17,6 +18,7 @@ pub struct Queue<T> {
     front: AtomicPtr<Node<T>>,
     back: AtomicPtr<Node<T>>,
     incin: SharedIncin<T>,
+    len: Counter,
 }
 
 impl<T> Queue<T> {
@@ -34,6 +36,7 @@ impl<T> Queue<T> {
             front: AtomicPtr::new(sentinel),
             back: AtomicPtr::new(sentinel),
             incin,
+            len: Counter::new(),
         }
     }
 
@@ -55,6 +58,7 @@ impl<T> Queue<T> {
         let node = Node::new(Removable::new(item));
         let alloc = OwnedAlloc::new(node);
         let node_ptr = alloc.into_raw().as_ptr();
+        self.len.add(1);
         // Swap with the previously stored back.
         let prev_back = self.back.swap(node_ptr, AcqRel);
         unsafe {
@@ -82,6 +86,7 @@ impl<T> Queue<T> {
             // We first remove the node logically.
             match unsafe { front_nnptr.as_ref().item.take(AcqRel) } {
                 Some(val) => {
+                    self.len.sub(1);
                     // Safe to call because we passed a pointer from the front
                     // which was loaded during the very same pause we are
                     // passing.
@@ -99,6 +104,35 @@ impl<T> Queue<T> {
         }
     }
 
+    /// The approximate number of elements in the queue. Concurrent pushes and
+    /// pops may be partially observed, so this is only a hint. Requires the
+    /// `len` feature.
+    #[cfg(feature = "len")]
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    /// Tests if the queue is empty. Unlike `len`, this is exact at the moment
+    /// it checks the front of the queue.
+    pub fn is_empty(&self) -> bool {
+        let pause = self.incin.get_unchecked().pause();
+        let mut ptr = self.front.load(Acquire);
+
+        while let Some(nnptr) = NonNull::new(ptr) {
+            // This is safe because we paused the incinerator and only delete
+            // nodes via incinerator. Nodes reachable from the front are not
+            // deleted during the pause.
+            let node = unsafe { nnptr.as_ref() };
+            if node.item.is_present(Acquire) {
+                return false;
+            }
+            ptr = node.next.load(Acquire);
+        }
+
+        pause.resume();
+        true
+    }
+
     /// Pushes elements from the given iterable. Acts just like
     /// [`Extend::extend`] but does not require mutability.
     pub fn extend<I>(&self, iterable: I)
@@ -201,6 +235,7 @@ impl<T> Iterator for Queue<T> {
 
             match (item, NonNull::new(next)) {
                 (Some(item), maybe_next) => {
+                    self.len.sub(1);
                     if let Some(next) = maybe_next {
                         // Ok to drop it like this because we have exclusive
                         // reference to the queue.
@@ -229,8 +264,8 @@ impl<T> fmt::Debug for Queue<T> {
     fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
         write!(
             fmtr,
-            "Queue {{ front: {:?}, back: {:?}, incin: {:?} }}",
-            self.front, self.back, self.incin
+            "Queue {{ front: {:?}, back: {:?}, incin: {:?}, len: {:?} }}",
+            self.front, self.back, self.incin, self.len
         )
     }
 }
@@ -377,4 +412,20 @@ mod test {
 
         assert_eq!(res, expected);
     }
+
+    #[test]
+    fn len_and_is_empty() {
+        let queue = Queue::new();
+        assert!(queue.is_empty());
+        queue.push(3);
+        queue.push(5);
+        assert!(!queue.is_empty());
+        #[cfg(feature = "len")]
+        assert_eq!(queue.len(), 2);
+        queue.pop();
+        queue.pop();
+        assert!(queue.is_empty());
+        #[cfg(feature = "len")]
+        assert_eq!(queue.len(), 0);
+    }
 }
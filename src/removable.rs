@@ -118,7 +118,7 @@ impl<T> Drop for Removable<T> {
         if *self.present.get_mut() {
             // Safe because present will only be true when the memory is
             // initialized. And now we are at drop.
-            unsafe { ManuallyDrop::drop(&mut self.item) }
+            unsafe { self.item.assume_init_drop() }
         }
     }
 }
@@ -1,6 +1,6 @@
 use crate::map::{
-    Insertion as MapInsertion, IntoIter as MapIntoIter, Iter as MapIter, Map, Preview,
-    ReadGuard as MapGuard, Removed as MapRemoved, SharedIncin as MapIncin,
+    Insertion as MapInsertion, IntoIter as MapIntoIter, Iter as MapIter, Map, MapBuilder, MapStats,
+    Preview, ReadGuard as MapGuard, Removed as MapRemoved, SharedIncin as MapIncin,
 };
 use core::{
     borrow::Borrow,
@@ -37,6 +37,30 @@ impl<T, H> Set<T, H> {
     pub fn iter(&self) -> Iter<'_, T> {
         self.into_iter()
     }
+
+    /// The approximate number of elements in the set. Just like
+    /// [`Map::len`], concurrent modifications may be partially observed.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Tests if the set has no elements. Unlike `len`, this does not rely on
+    /// a counter: it scans the tables, stopping at the first element found.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Walks the tables of the underlying [`Map`] and gathers structural
+    /// statistics. See [`Map::stats`].
+    pub fn stats(&self) -> MapStats {
+        self.inner.stats()
+    }
+
+    /// The number of hash bits consumed by each table of the underlying
+    /// [`Map`]. See [`MapBuilder::bits`].
+    pub fn bits(&self) -> u32 {
+        self.inner.bits()
+    }
 }
 
 impl<T, H> Set<T, H>
@@ -58,6 +82,21 @@ where
         }
     }
 
+    /// Creates a [`Set`] using the configuration of the given [`MapBuilder`].
+    pub fn with_builder(builder: MapBuilder<H>) -> Self {
+        Self {
+            inner: Map::with_builder(builder),
+        }
+    }
+
+    /// Creates the [`Set`] using the configuration of the given
+    /// [`MapBuilder`] and the given shared incinerator.
+    pub fn with_builder_and_incin(builder: MapBuilder<H>, incin: SharedIncin<T>) -> Self {
+        Self {
+            inner: Map::with_builder_and_incin(builder, incin.inner),
+        }
+    }
+
     /// The shared incinerator used by this `Map`.
     pub fn incin(&self) -> SharedIncin<T> {
         SharedIncin {
@@ -82,13 +121,18 @@ where
         self.inner.clear();
     }
 
+    /// Removes all elements in a shared context. See [`Map::clear_shared`].
+    pub fn clear_shared(&self) {
+        self.inner.clear_shared();
+    }
+
     /// Tests if the given element is present on the [`Set`]. The method accepts
     /// a type resulted from borrowing the stored element. This method will
-    /// only work correctly if [`Hash`] and [`Ord`] are implemented in the same
+    /// only work correctly if [`Hash`] and [`Eq`] are implemented in the same
     /// way for the borrowed type and the stored type.
     pub fn contains<U>(&self, elem: &U) -> bool
     where
-        U: Hash + Ord,
+        U: Hash + Eq,
         T: Borrow<U>,
     {
         self.inner.get(elem).is_some()
@@ -97,12 +141,12 @@ where
     /// Returns a guarded reference to the given element in the [`Set`]. This
     /// may be useful for types with additional metadata. The method accepts
     /// a type resulted from borrowing the stored element. This method will
-    /// only work correctly if [`Hash`] and [`Ord`] are implemented in the same
+    /// only work correctly if [`Hash`] and [`Eq`] are implemented in the same
     /// way for the borrowed type and the stored type. If the element is not
     /// found, [`None`] is obviously returned.
     pub fn get<'set, U>(&'set self, elem: &U) -> Option<ReadGuard<'set, T>>
     where
-        U: Hash + Ord,
+        U: Hash + Eq,
         T: Borrow<U>,
     {
         self.inner.get(elem).map(ReadGuard::new)
@@ -112,7 +156,7 @@ where
     /// present, [`Err`]`(the_passed_value)` is returned.
     pub fn insert(&self, elem: T) -> Result<(), T>
     where
-        T: Hash + Ord,
+        T: Hash + Eq,
     {
         let result = self.inner.insert_with(elem, |_, _, stored| {
             if stored.is_some() {
@@ -136,7 +180,7 @@ where
     pub fn insert_with<F>(&self, elem: T, mut interactive: F) -> Insertion<T, T>
     where
         F: FnMut(&T, Option<&T>) -> bool,
-        T: Hash + Ord,
+        T: Hash + Eq,
     {
         let result = self.inner.insert_with(elem, |elem, _, stored| {
             if interactive(elem, stored.map(|(elem, _)| elem)) {
@@ -164,7 +208,7 @@ where
     /// fail. Otherwise, insertion cannot fail.
     pub fn reinsert(&self, elem: Removed<T>) -> Result<(), Removed<T>>
     where
-        T: Hash + Ord,
+        T: Hash + Eq,
     {
         let result = self
             .inner
@@ -192,7 +236,7 @@ where
     pub fn reinsert_with<F>(&self, elem: Removed<T>, mut interactive: F) -> Insertion<T, Removed<T>>
     where
         F: FnMut(&T, Option<&T>) -> bool,
-        T: Hash + Ord,
+        T: Hash + Eq,
     {
         let result = self.inner.reinsert_with(elem.inner, |(elem, _), stored| {
             interactive(elem, stored.map(|(elem, _)| elem))
@@ -207,11 +251,11 @@ where
 
     /// Removes the given element inconditionally. The method accepts a
     /// type resulted from borrowing the stored element. This method will only
-    /// work correctly if [`Hash`] and [`Ord`] are implemented in the same way
+    /// work correctly if [`Hash`] and [`Eq`] are implemented in the same way
     /// for the borrowed type and the stored type.
     pub fn remove<U>(&self, elem: &U) -> Option<Removed<T>>
     where
-        U: Hash + Ord,
+        U: Hash + Eq,
         T: Borrow<U>,
     {
         self.inner.remove(elem).map(Removed::new)
@@ -222,11 +266,11 @@ where
     /// reference to the found stored element. The return value is whether the
     /// removal should happen or not. The method accepts a type resulted from
     /// borrowing the stored element. This method will only work correctly
-    /// if [`Hash`] and [`Ord`] are implemented in the same way for the borrowed
+    /// if [`Hash`] and [`Eq`] are implemented in the same way for the borrowed
     /// type and the stored type.
     pub fn remove_with<U, F>(&self, elem: &U, mut interactive: F) -> Option<Removed<T>>
     where
-        U: Hash + Ord,
+        U: Hash + Eq,
         T: Borrow<U>,
         F: FnMut(&T) -> bool,
     {
@@ -235,12 +279,91 @@ where
             .map(Removed::new)
     }
 
+    /// Computes the hash of the given element with the hasher builder of this
+    /// [`Set`]. See [`Map::hash_of`].
+    pub fn hash_of<U>(&self, elem: &U) -> u64
+    where
+        U: ?Sized + Hash,
+    {
+        self.inner.hash_of(elem)
+    }
+
+    /// Returns a guarded reference to the given element, just like
+    /// [`get`](Set::get), but using the given hash instead of hashing the
+    /// element again. See [`Map::get_hashed`].
+    pub fn get_hashed<'set, U>(&'set self, hash: u64, elem: &U) -> Option<ReadGuard<'set, T>>
+    where
+        U: ?Sized + Hash + Eq,
+        T: Borrow<U>,
+    {
+        self.inner.get_hashed(hash, elem).map(ReadGuard::new)
+    }
+
+    /// Returns a guarded reference to an element with the given hash which
+    /// matches the given closure. See [`Map::get_hashed_with`].
+    pub fn get_hashed_with<'set, F>(
+        &'set self,
+        hash: u64,
+        is_match: F,
+    ) -> Option<ReadGuard<'set, T>>
+    where
+        T: Hash,
+        F: FnMut(&T) -> bool,
+    {
+        self.inner
+            .get_hashed_with(hash, is_match)
+            .map(ReadGuard::new)
+    }
+
+    /// Inserts the element into the [`Set`], just like [`insert`](Set::insert),
+    /// but using the given hash instead of hashing the element. See
+    /// [`Map::insert_hashed`].
+    pub fn insert_hashed(&self, hash: u64, elem: T) -> Result<(), T>
+    where
+        T: Hash + Eq,
+    {
+        debug_assert_eq!(hash, self.hash_of(&elem), "hash does not match the element");
+        let result = self.inner.raw_insert_with(hash, elem, |_, _, stored| {
+            if stored.is_some() {
+                Preview::Discard
+            } else {
+                Preview::New(())
+            }
+        });
+        match result {
+            MapInsertion::Created => Ok(()),
+            MapInsertion::Failed((elem, _)) => Err(elem),
+            MapInsertion::Updated(_) => unreachable!(),
+        }
+    }
+
+    /// Removes the given element inconditionally, just like
+    /// [`remove`](Set::remove), but using the given hash instead of hashing
+    /// the element again. See [`Map::remove_hashed`].
+    pub fn remove_hashed<U>(&self, hash: u64, elem: &U) -> Option<Removed<T>>
+    where
+        U: ?Sized + Hash + Eq,
+        T: Borrow<U>,
+    {
+        self.inner.remove_hashed(hash, elem).map(Removed::new)
+    }
+
+    /// Removes every element for which the given closure returns `false`,
+    /// without requiring exclusive access. See [`Map::retain`].
+    pub fn retain<F>(&self, mut keep: F)
+    where
+        T: Hash + Eq,
+        F: FnMut(&T) -> bool,
+    {
+        self.inner.retain(|elem, _| keep(elem))
+    }
+
     /// Acts just like [`Extend::extend`] but does not require mutability.
     #[allow(unused_must_use)]
     pub fn extend<I>(&self, iterable: I)
     where
         I: IntoIterator<Item = T>,
-        T: Hash + Ord,
+        T: Hash + Eq,
     {
         for val in iterable {
             self.insert(val);
@@ -295,7 +418,7 @@ impl<'set, T, H> IntoIterator for &'set Set<T, H> {
 impl<T, H> FromIterator<T> for Set<T, H>
 where
     H: BuildHasher + Default,
-    T: Hash + Ord,
+    T: Hash + Eq,
 {
     fn from_iter<I>(iterable: I) -> Self
     where
@@ -310,7 +433,7 @@ where
 impl<T, H> Extend<T> for Set<T, H>
 where
     H: BuildHasher,
-    T: Hash + Ord,
+    T: Hash + Eq,
 {
     fn extend<I>(&mut self, iterable: I)
     where
@@ -653,6 +776,62 @@ mod test {
         }
     }
 
+    #[test]
+    fn len_and_is_empty() {
+        let set = Set::new();
+        assert!(set.is_empty());
+        set.insert(3).unwrap();
+        set.insert(3).unwrap_err();
+        set.insert(5).unwrap();
+        assert!(!set.is_empty());
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.stats().entries, 2);
+        set.remove(&3);
+        set.remove(&5);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn retain_and_clear_shared() {
+        let set = (0..100).collect::<Set<_>>();
+        set.retain(|&elem| elem % 3 == 0);
+        for i in 0..100 {
+            assert_eq!(set.contains(&i), i % 3 == 0);
+        }
+        assert_eq!(set.stats().entries, 34);
+        set.clear_shared();
+        assert!(set.is_empty());
+        set.insert(5).unwrap();
+        assert!(set.contains(&5));
+    }
+
+    #[test]
+    fn with_builder() {
+        let set = Set::with_builder(MapBuilder::new().bits(3));
+        assert_eq!(set.bits(), 3);
+        for i in 0..100 {
+            set.insert(i).unwrap();
+        }
+        for i in 0..100 {
+            assert!(set.contains(&i));
+        }
+        assert!(!set.contains(&100));
+        assert_eq!(set.stats().entries, 100);
+    }
+
+    #[test]
+    fn hashed() {
+        let set = Set::new();
+        let hash = set.hash_of(&7);
+        set.insert_hashed(hash, 7).unwrap();
+        set.insert_hashed(hash, 7).unwrap_err();
+        assert_eq!(*set.get_hashed(hash, &7).unwrap(), 7);
+        assert_eq!(*set.get_hashed_with(hash, |&elem| elem == 7).unwrap(), 7);
+        assert!(set.get_hashed_with(hash, |&elem| elem == 8).is_none());
+        assert_eq!(*set.remove_hashed(hash, &7).unwrap(), 7);
+        assert!(!set.contains(&7));
+    }
+
     #[test]
     fn inserts_and_contains_checks() {
         let set = Set::new();
@@ -669,6 +848,24 @@ mod test {
         assert!(set.contains(&5));
     }
 
+    #[test]
+    fn eq_only_elements() {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        enum Token {
+            Word(&'static str),
+            Number(u64),
+        }
+
+        let set = Set::new();
+        set.insert(Token::Word("lock")).unwrap();
+        set.insert(Token::Number(7)).unwrap();
+        set.insert(Token::Word("lock")).unwrap_err();
+        assert!(set.contains(&Token::Word("lock")));
+        assert!(!set.contains(&Token::Word("free")));
+        assert_eq!(set.remove(&Token::Number(7)).unwrap(), Token::Number(7));
+        assert_eq!(set.iter().count(), 1);
+    }
+
     #[test]
     fn inserts_and_removes() {
         let set = Set::new();
@@ -0,0 +1,219 @@
+use super::{
+    node::{Garbage, Node},
+    ReadGuard,
+};
+use crate::incin::Pause;
+use crate::owned_alloc::OwnedAlloc;
+use core::{
+    borrow::Borrow,
+    fmt,
+    marker::PhantomData,
+    ops::{Bound, RangeBounds, RangeFull},
+    ptr::NonNull,
+};
+
+/// An iterator over the key-value entries of a
+/// [`SkipMap`](super::SkipMap) within a range of keys, in ascending order
+/// (or descending order from the back). The `Item` of this iterator is a
+/// [`ReadGuard`]. This iterator may be inconsistent, but still it is
+/// memory-safe. It is guaranteed to yield, in order, only items that were in
+/// the `SkipMap` at some point between the iterator creation and the current
+/// call to [`next`](Iterator::next) or
+/// [`next_back`](DoubleEndedIterator::next_back), and to yield every item
+/// which was in the range during the whole iteration.
+pub struct Range<'map, K, V, Q, R>
+where
+    Q: ?Sized,
+{
+    pause: Pause<'map, Garbage<K, V>>,
+    head: &'map Node<K, V>,
+    range: R,
+    front: Option<(&'map Node<K, V>, &'map K)>,
+    back: Option<&'map K>,
+    done: bool,
+    _marker: PhantomData<fn(&Q)>,
+}
+
+/// An iterator over all key-value entries of a [`SkipMap`](super::SkipMap),
+/// in ascending order. See [`Range`].
+pub type Iter<'map, K, V> = Range<'map, K, V, K, RangeFull>;
+
+impl<'map, K, V, Q, R> Range<'map, K, V, Q, R>
+where
+    Q: ?Sized,
+{
+    pub(super) fn new(pause: Pause<'map, Garbage<K, V>>, head: &'map Node<K, V>, range: R) -> Self {
+        Self {
+            pause,
+            head,
+            range,
+            front: None,
+            back: None,
+            done: false,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'map, K, V, Q, R> Iterator for Range<'map, K, V, Q, R>
+where
+    K: Borrow<Q>,
+    Q: ?Sized + Ord,
+    R: RangeBounds<Q>,
+{
+    type Item = ReadGuard<'map, K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        // This is safe because the incinerator is paused during the whole
+        // lifetime of the iterator. Even if the last node was removed, its
+        // links still lead to greater keys.
+        let found = unsafe {
+            match self.front {
+                Some((node, _)) => node.next_present(),
+                None => self.head.lower_bound(self.range.start_bound()),
+            }
+        };
+
+        let in_range = found.map_or(false, |(_, (key, _))| {
+            let key = key.borrow();
+            let before_end = match self.range.end_bound() {
+                Bound::Included(end) => key <= end,
+                Bound::Excluded(end) => key < end,
+                Bound::Unbounded => true,
+            };
+            before_end && self.back.map_or(true, |back| key < back.borrow())
+        });
+
+        match found {
+            Some((node, pair)) if in_range => {
+                self.front = Some((node, &pair.0));
+                Some(ReadGuard::new(pair, self.pause.clone()))
+            }
+
+            _ => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+impl<'map, K, V, Q, R> DoubleEndedIterator for Range<'map, K, V, Q, R>
+where
+    K: Borrow<Q>,
+    Q: ?Sized + Ord,
+    R: RangeBounds<Q>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let bound = match self.back {
+            Some(back) => Bound::Excluded(back.borrow()),
+            None => self.range.end_bound(),
+        };
+        // This is safe because the incinerator is paused during the whole
+        // lifetime of the iterator.
+        let found = unsafe { self.head.upper_bound(bound) };
+
+        let in_range = found.map_or(false, |(_, (key, _))| {
+            let key = key.borrow();
+            let after_start = match self.range.start_bound() {
+                Bound::Included(start) => key >= start,
+                Bound::Excluded(start) => key > start,
+                Bound::Unbounded => true,
+            };
+            after_start && self.front.map_or(true, |(_, front)| key > front.borrow())
+        });
+
+        match found {
+            Some((_, pair)) if in_range => {
+                self.back = Some(&pair.0);
+                Some(ReadGuard::new(pair, self.pause.clone()))
+            }
+
+            _ => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+impl<'map, K, V, Q, R> fmt::Debug for Range<'map, K, V, Q, R>
+where
+    Q: ?Sized,
+    R: fmt::Debug,
+{
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Range {{ range: {:?}, done: {:?} }}",
+            self.range, self.done
+        )
+    }
+}
+
+/// An iterator over the owned key-value entries of a
+/// [`SkipMap`](super::SkipMap), in ascending order.
+pub struct IntoIter<K, V> {
+    curr: Option<NonNull<Node<K, V>>>,
+}
+
+impl<K, V> IntoIter<K, V> {
+    pub(super) fn new(first: *mut Node<K, V>) -> Self {
+        Self {
+            curr: NonNull::new(first),
+        }
+    }
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // This is safe because we own the detached nodes, and we advance
+            // before freeing each one.
+            let node = unsafe { OwnedAlloc::from_raw(self.curr?) };
+            // This is safe because we own the nodes, no one else reads them.
+            self.curr = unsafe { node.next() }.map(NonNull::from);
+            if let Some(pair) = node.take_pair() {
+                // This is safe because the pair belonged to the node.
+                let (pair, _) = unsafe { OwnedAlloc::from_raw(pair) }.move_inner();
+                break Some(pair);
+            }
+        }
+    }
+}
+
+impl<K, V> Drop for IntoIter<K, V> {
+    fn drop(&mut self) {
+        while self.next().is_some() {}
+    }
+}
+
+impl<K, V> fmt::Debug for IntoIter<K, V> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "IntoIter {{ curr: {:?} }}", self.curr)
+    }
+}
+
+unsafe impl<K, V> Send for IntoIter<K, V>
+where
+    K: Send,
+    V: Send,
+{
+}
+
+unsafe impl<K, V> Sync for IntoIter<K, V>
+where
+    K: Sync,
+    V: Sync,
+{
+}
@@ -0,0 +1,705 @@
+mod iter;
+mod node;
+/// A lock-free ordered set based on [`SkipMap`].
+pub mod set;
+
+pub use self::{
+    iter::{IntoIter, Iter, Range},
+    set::SkipSet,
+};
+
+/// A read-operation guard over an entry of a [`SkipMap`]. This ensures no
+/// entry allocation is mutated or freed while potential reads are performed.
+pub type ReadGuard<'map, K, V> = crate::map::ReadGuard<'map, K, V, Garbage<K, V>>;
+
+/// A removed entry of a [`SkipMap`]. Its contents can be moved out only if
+/// either the [`SkipMap`] is dropped or there are no sensitive reads running on
+/// that [`SkipMap`].
+pub type Removed<K, V> = crate::map::Removed<K, V, Garbage<K, V>>;
+
+use self::node::{Garbage, Insert, Node};
+use crate::count::AtomicCounter;
+use crate::owned_alloc::OwnedAlloc;
+use crate::ptr::check_null_align;
+use core::{
+    borrow::Borrow,
+    fmt,
+    iter::FromIterator,
+    ops::{Bound, RangeBounds},
+};
+
+/// A lock-free ordered map. Implemented using a skip list, whose links are
+/// marked in order to remove nodes.
+///
+/// # Design
+/// A skip list is a stack of sorted linked lists. The bottom level has every
+/// entry, and each upper level has a random subset of the entries of the
+/// level below it (one fourth of them, on average). Searching starts at the
+/// top level and goes down a level whenever the next node of the current
+/// level is already past the searched key, so only a few nodes of each level
+/// are visited.
+///
+/// Inserting a new key links the new node at the bottom level first, which
+/// makes the entry present, and then links the upper levels one by one.
+/// Inserting a present key atomically replaces the stored entry instead.
+///
+/// Removing an entry atomically takes it out of its node, which makes the
+/// entry absent, and then marks each link going out of the node, from the top
+/// to the bottom. A marked link cannot be changed anymore, and searches
+/// unlink the nodes with marked links they find on their way. Nodes and
+/// entries are only destroyed through the incinerator, after being unlinked
+/// from every level.
+///
+/// Because of limitation of sharing in concurrent contexts, we do return plain
+/// references to the entries, neither allow the user to move out removed
+/// values, as they must be deinitialized correctly. Instead, we return guarded
+/// references to the entries and wrappers over removed entries.
+pub struct SkipMap<K, V> {
+    head: OwnedAlloc<Node<K, V>>,
+    incin: SharedIncin<K, V>,
+    len: AtomicCounter,
+}
+
+impl<K, V> SkipMap<K, V> {
+    /// Creates a new empty [`SkipMap`].
+    pub fn new() -> Self {
+        Self::with_incin(SharedIncin::new())
+    }
+
+    /// Creates the [`SkipMap`] using the given shared incinerator.
+    pub fn with_incin(incin: SharedIncin<K, V>) -> Self {
+        check_null_align::<Node<K, V>>();
+        Self {
+            head: Node::new_head(),
+            incin,
+            len: AtomicCounter::new(),
+        }
+    }
+
+    /// The shared incinerator used by this [`SkipMap`].
+    pub fn incin(&self) -> SharedIncin<K, V> {
+        self.incin.clone()
+    }
+
+    /// Creates a double-ended iterator over guarded references to the
+    /// key-value entries whose keys are within the given range, in ascending
+    /// order of keys. The method accepts ranges of a type resulted from
+    /// borrowing the stored key. This method will only work correctly if
+    /// [`Ord`] is implemented in the same way for the borrowed type and the
+    /// stored type.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, V, Q, R>
+    where
+        Q: ?Sized + Ord,
+        K: Borrow<Q>,
+        R: RangeBounds<Q>,
+    {
+        Range::new(self.incin.get_unchecked().pause(), &self.head, range)
+    }
+
+    /// The approximate number of entries in the map. It is maintained by
+    /// insertions and removals, so concurrent modifications may be partially
+    /// observed.
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    /// Tests if the map has no entries. Unlike `len`, this does not rely on a
+    /// counter: it looks for the first entry.
+    pub fn is_empty(&self) -> bool {
+        let pause = self.incin.get_unchecked().pause();
+        // Safe because we paused properly.
+        let first = unsafe { self.head.next_present() };
+        pause.resume();
+        first.is_none()
+    }
+
+    /// Returns a guarded reference to the entry with the smallest key. If the
+    /// map is empty, [`None`] is returned.
+    pub fn first(&self) -> Option<ReadGuard<'_, K, V>> {
+        let pause = self.incin.get_unchecked().pause();
+        // Safe because we paused properly, and the guard keeps the pause.
+        let (_, pair) = unsafe { self.head.next_present() }?;
+        Some(ReadGuard::new(pair, pause))
+    }
+
+    /// Returns a guarded reference to the entry with the greatest key. If the
+    /// map is empty, [`None`] is returned.
+    pub fn last(&self) -> Option<ReadGuard<'_, K, V>> {
+        let pause = self.incin.get_unchecked().pause();
+        // Safe because we paused properly, and the guard keeps the pause.
+        let (pred, _) = unsafe { self.head.search(|_| true) };
+        let (_, pair) = pred?;
+        Some(ReadGuard::new(pair, pause))
+    }
+
+    /// Removes all entries. This method might also clear delayed resource
+    /// destruction. This method cannot be performed in a shared context.
+    pub fn clear(&mut self) {
+        self.incin.clear();
+        self.len.reset();
+        // Safe because we have exclusive access to the list.
+        unsafe { self.head.free_nodes() }
+    }
+}
+
+impl<K, V> SkipMap<K, V>
+where
+    K: Ord,
+{
+    /// Creates an iterator over guarded references to the key-value entries,
+    /// in ascending order of keys.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.range(..)
+    }
+
+    /// Searches for the entry identified by the given key. The returned value
+    /// is a guarded reference. Guarded to ensure no thread deallocates the
+    /// allocation for the entry while it is being used. The method accepts
+    /// a type resulted from borrowing the stored key. This method will only
+    /// work correctly if [`Ord`] is implemented in the same way for the
+    /// borrowed type and the stored type. If the entry was not found,
+    /// [`None`] is returned.
+    pub fn get<'map, Q>(&'map self, key: &Q) -> Option<ReadGuard<'map, K, V>>
+    where
+        Q: ?Sized + Ord,
+        K: Borrow<Q>,
+    {
+        let pause = self.incin.get_unchecked().pause();
+        // Safe because we paused properly, and the guard keeps the pause.
+        let (_, pair) = unsafe { self.head.lower_bound(Bound::Included(key)) }?;
+        if pair.0.borrow() == key {
+            Some(ReadGuard::new(pair, pause))
+        } else {
+            None
+        }
+    }
+
+    /// Tests if an entry identified by the given key is present. The method
+    /// accepts a type resulted from borrowing the stored key, just like
+    /// [`SkipMap::get`].
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Ord,
+        K: Borrow<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Inserts unconditionally the given key and value. If there was a
+    /// previously stored value, it is returned.
+    pub fn insert(&self, key: K, val: V) -> Option<Removed<K, V>> {
+        let pause = self.incin.get_unchecked().pause();
+        let nnptr = OwnedAlloc::new((key, val)).into_raw();
+        self.len.add(1);
+
+        // Safe because we paused properly and we own the pair allocation.
+        match unsafe { self.head.insert(nnptr, true, &pause) } {
+            Insert::Created => None,
+
+            Insert::Replaced(old) => {
+                self.len.sub(1);
+                // Safe because the old pair is no longer in the list, and
+                // only we got it.
+                let old = unsafe { OwnedAlloc::from_raw(old) };
+                Some(Removed::new(old, self.incin.get_unchecked()))
+            }
+
+            Insert::Present => unreachable!(),
+        }
+    }
+
+    /// Inserts the given key and value only if the key is not present. If it
+    /// is, the key and value are given back in [`Err`].
+    pub fn try_insert(&self, key: K, val: V) -> Result<(), (K, V)> {
+        let pause = self.incin.get_unchecked().pause();
+        let nnptr = OwnedAlloc::new((key, val)).into_raw();
+        self.len.add(1);
+
+        // Safe because we paused properly and we own the pair allocation.
+        match unsafe { self.head.insert(nnptr, false, &pause) } {
+            Insert::Created => Ok(()),
+
+            Insert::Present => {
+                self.len.sub(1);
+                // Safe because the pair was not inserted, so we still own it.
+                let (pair, _) = unsafe { OwnedAlloc::from_raw(nnptr) }.move_inner();
+                Err(pair)
+            }
+
+            Insert::Replaced(_) => unreachable!(),
+        }
+    }
+
+    /// Removes unconditionally the entry identified by the given key. This
+    /// method will only work correctly if [`Ord`] is implemented in the same
+    /// way for the borrowed type and the stored type. If the entry was not
+    /// found, [`None`] is returned.
+    pub fn remove<Q>(&self, key: &Q) -> Option<Removed<K, V>>
+    where
+        Q: ?Sized + Ord,
+        K: Borrow<Q>,
+    {
+        self.remove_with(key, |_| true)
+    }
+
+    /// Removes _interactively_ the entry identified by the given key. A closure
+    /// is passed to validate the removal. The only argument passed to the
+    /// closure is a reference to the found entry. The closure returns if the
+    /// removal should go on. This method will only work correctly if [`Ord`]
+    /// is implemented in the same way for the borrowed type and the stored
+    /// type. If the entry was not found, [`None`] is returned.
+    pub fn remove_with<Q, F>(&self, key: &Q, interactive: F) -> Option<Removed<K, V>>
+    where
+        Q: ?Sized + Ord,
+        K: Borrow<Q>,
+        F: FnMut(&(K, V)) -> bool,
+    {
+        let pause = self.incin.get_unchecked().pause();
+        // Safe because we paused properly.
+        let removed = unsafe { self.head.remove(key, interactive, &pause) }?;
+        self.len.sub(1);
+        // Safe because the pair is no longer in the list, and only we got it.
+        let removed = unsafe { OwnedAlloc::from_raw(removed) };
+        Some(Removed::new(removed, self.incin.get_unchecked()))
+    }
+
+    /// Removes the entry with the smallest key. If the map is empty, [`None`]
+    /// is returned.
+    pub fn pop_first(&self) -> Option<Removed<K, V>> {
+        let pause = self.incin.get_unchecked().pause();
+        loop {
+            // Safe because we paused properly.
+            let (node, _) = unsafe { self.head.next_present() }?;
+            // Safe because we paused properly and the node was found in this
+            // list.
+            if let Some(removed) = unsafe { self.head.remove_node(node, &pause) } {
+                self.len.sub(1);
+                // Safe because the pair is no longer in the list, and only we
+                // got it.
+                let removed = unsafe { OwnedAlloc::from_raw(removed) };
+                break Some(Removed::new(removed, self.incin.get_unchecked()));
+            }
+        }
+    }
+
+    /// Removes the entry with the greatest key. If the map is empty, [`None`]
+    /// is returned.
+    pub fn pop_last(&self) -> Option<Removed<K, V>> {
+        let pause = self.incin.get_unchecked().pause();
+        loop {
+            // Safe because we paused properly.
+            let (pred, _) = unsafe { self.head.search(|_| true) };
+            let (node, _) = pred?;
+            // Safe because we paused properly and the node was found in this
+            // list.
+            if let Some(removed) = unsafe { self.head.remove_node(node, &pause) } {
+                self.len.sub(1);
+                // Safe because the pair is no longer in the list, and only we
+                // got it.
+                let removed = unsafe { OwnedAlloc::from_raw(removed) };
+                break Some(Removed::new(removed, self.incin.get_unchecked()));
+            }
+        }
+    }
+
+    /// Acts just like [`Extend::extend`] but does not require mutability.
+    pub fn extend<I>(&self, iterable: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        for (key, val) in iterable {
+            self.insert(key, val);
+        }
+    }
+}
+
+impl<K, V> Default for SkipMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> fmt::Debug for SkipMap<K, V> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "SkipMap {{ head: {:?}, incin: {:?}, len: {:?} }}",
+            self.head,
+            self.incin.get_unchecked(),
+            self.len
+        )
+    }
+}
+
+impl<K, V> Drop for SkipMap<K, V> {
+    fn drop(&mut self) {
+        // Safe because we won't use these nodes anymore. We are in the
+        // destructor.
+        unsafe { self.head.free_nodes() }
+    }
+}
+
+impl<'map, K, V> IntoIterator for &'map SkipMap<K, V>
+where
+    K: Ord,
+{
+    type Item = ReadGuard<'map, K, V>;
+
+    type IntoIter = Iter<'map, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V> IntoIterator for SkipMap<K, V> {
+    type Item = (K, V);
+
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        let first = self.head.take_nodes();
+        IntoIter::new(first)
+    }
+}
+
+impl<K, V> Extend<(K, V)> for SkipMap<K, V>
+where
+    K: Ord,
+{
+    fn extend<I>(&mut self, iterable: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        (*self).extend(iterable)
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for SkipMap<K, V>
+where
+    K: Ord,
+{
+    fn from_iter<I>(iterable: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let this = Self::new();
+        this.extend(iterable);
+        this
+    }
+}
+
+unsafe impl<K, V> Send for SkipMap<K, V>
+where
+    K: Send,
+    V: Send,
+{
+}
+
+unsafe impl<K, V> Sync for SkipMap<K, V>
+where
+    K: Sync,
+    V: Sync,
+{
+}
+
+make_shared_incin! {
+    { "[`SkipMap`]" }
+    pub SharedIncin<K, V> of Garbage<K, V>
+}
+
+impl<K, V> fmt::Debug for SharedIncin<K, V> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "SharedIncin {{ inner: {:?} }}", self.inner)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+    use core::sync::atomic::{AtomicUsize, Ordering::*};
+    use std::thread;
+
+    #[test]
+    fn inserts_and_gets() {
+        let map = SkipMap::new();
+        assert!(map.get("five").is_none());
+        assert!(map.insert("five".to_owned(), 5).is_none());
+        assert_eq!(*map.get("five").unwrap().val(), 5);
+        assert!(map.get("four").is_none());
+        assert!(map.insert("four".to_owned(), 4).is_none());
+        assert_eq!(*map.get("five").unwrap().val(), 5);
+        assert_eq!(*map.get("four").unwrap().val(), 4);
+        assert!(map.contains_key("four"));
+        assert!(!map.contains_key("three"));
+    }
+
+    #[test]
+    fn insert_replaces() {
+        let map = SkipMap::new();
+        assert!(map.insert(5, "five").is_none());
+        let old = map.insert(5, "FIVE").unwrap();
+        assert_eq!(*old, (5, "five"));
+        assert_eq!(*map.get(&5).unwrap().val(), "FIVE");
+        assert_eq!(map.try_insert(5, "Five"), Err((5, "Five")));
+        assert_eq!(map.try_insert(4, "four"), Ok(()));
+        assert_eq!(*map.get(&4).unwrap().val(), "four");
+    }
+
+    #[test]
+    fn removes() {
+        let map = SkipMap::new();
+        map.insert(5, 'a');
+        map.insert(4, 'b');
+        assert!(map.remove(&3).is_none());
+        assert!(map.remove_with(&4, |&(_, val)| val == 'c').is_none());
+        assert_eq!(*map.remove(&4).unwrap(), (4, 'b'));
+        assert!(map.get(&4).is_none());
+        assert!(map.remove(&4).is_none());
+        assert_eq!(Removed::try_into(map.remove(&5).unwrap()), Ok((5, 'a')));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn iterates_in_order() {
+        let map = SkipMap::new();
+        for i in (0..200).rev() {
+            map.insert(i * 7 % 200, i);
+        }
+        let keys = map.iter().map(|guard| *guard.key()).collect::<Vec<_>>();
+        assert_eq!(keys, (0..200).collect::<Vec<_>>());
+        let keys = map
+            .iter()
+            .rev()
+            .map(|guard| *guard.key())
+            .collect::<Vec<_>>();
+        assert_eq!(keys, (0..200).rev().collect::<Vec<_>>());
+        assert_eq!(*map.first().unwrap().key(), 0);
+        assert_eq!(*map.last().unwrap().key(), 199);
+    }
+
+    #[test]
+    fn ranges() {
+        let map = (0..100).map(|i| (i * 2, i)).collect::<SkipMap<_, _>>();
+        fn keys<'map, I>(iter: I) -> Vec<i32>
+        where
+            I: Iterator<Item = ReadGuard<'map, i32, i32>>,
+        {
+            iter.map(|guard| *guard.key()).collect()
+        }
+
+        assert_eq!(keys(map.range(10..16)), [10, 12, 14]);
+        assert_eq!(keys(map.range(9..=16)), [10, 12, 14, 16]);
+        assert_eq!(keys(map.range(195..)), [196, 198]);
+        assert_eq!(keys(map.range(..3)), [0, 2]);
+        assert_eq!(
+            keys(map.range((Bound::Excluded(10), Bound::Excluded(16)))),
+            [12, 14]
+        );
+        assert_eq!(keys(map.range(11..12)), []);
+        assert_eq!(keys(map.range(300..)), []);
+        assert_eq!(keys(map.range(10..16).rev()), [14, 12, 10]);
+    }
+
+    #[test]
+    fn ranges_double_ended() {
+        let map = (0..10).map(|i| (i, ())).collect::<SkipMap<_, _>>();
+        let mut range = map.range(2..8);
+        assert_eq!(*range.next().unwrap().key(), 2);
+        assert_eq!(*range.next_back().unwrap().key(), 7);
+        assert_eq!(*range.next().unwrap().key(), 3);
+        assert_eq!(*range.next_back().unwrap().key(), 6);
+        assert_eq!(*range.next_back().unwrap().key(), 5);
+        assert_eq!(*range.next().unwrap().key(), 4);
+        assert!(range.next().is_none());
+        assert!(range.next_back().is_none());
+    }
+
+    #[test]
+    fn range_of_borrowed() {
+        let map = SkipMap::new();
+        for word in ["apple", "banana", "cherry", "date"] {
+            map.insert(word.to_owned(), word.len());
+        }
+        let words = map
+            .range::<str, _>((Bound::Included("b"), Bound::Excluded("d")))
+            .map(|guard| guard.key().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(words, ["banana", "cherry"]);
+    }
+
+    #[test]
+    fn pops() {
+        let map = (0..5).map(|i| (i, i * 10)).collect::<SkipMap<_, _>>();
+        assert_eq!(*map.pop_first().unwrap(), (0, 0));
+        assert_eq!(*map.pop_last().unwrap(), (4, 40));
+        assert_eq!(*map.pop_first().unwrap(), (1, 10));
+        assert_eq!(*map.pop_last().unwrap(), (3, 30));
+        assert_eq!(*map.pop_last().unwrap(), (2, 20));
+        assert!(map.pop_first().is_none());
+        assert!(map.pop_last().is_none());
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn into_iter_and_clear() {
+        let mut map = (0..50).rev().map(|i| (i, i)).collect::<SkipMap<_, _>>();
+        map.clear();
+        assert!(map.is_empty());
+        map.extend((0..50).rev().map(|i| (i, i)));
+        let pairs = map.into_iter().collect::<Vec<_>>();
+        assert_eq!(pairs, (0..50).map(|i| (i, i)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn len() {
+        let map = SkipMap::new();
+        assert_eq!(map.len(), 0);
+        map.insert(1, ());
+        map.insert(2, ());
+        map.insert(2, ());
+        assert!(map.try_insert(1, ()).is_err());
+        assert_eq!(map.len(), 2);
+        map.remove(&1);
+        map.pop_last();
+        assert_eq!(map.len(), 0);
+    }
+
+    #[test]
+    fn drops_each_value_once() {
+        let val = Arc::new(());
+        let mut map = SkipMap::new();
+        for i in 0..64 {
+            map.insert(i, val.clone());
+        }
+        map.insert(3, val.clone());
+        map.remove(&4);
+        map.pop_first();
+        map.pop_last();
+        assert_eq!(Arc::strong_count(&val), 62);
+        map.clear();
+        assert_eq!(Arc::strong_count(&val), 1);
+        map.insert(0, val.clone());
+        drop(map);
+        assert_eq!(Arc::strong_count(&val), 1);
+    }
+
+    #[test]
+    fn multithreaded() {
+        const THREADS: usize = 8;
+        const ROUNDS: usize = 512;
+
+        let map = Arc::new(SkipMap::new());
+        let mut threads = Vec::with_capacity(THREADS);
+        for i in 0..THREADS {
+            let map = map.clone();
+            threads.push(thread::spawn(move || {
+                for j in 0..ROUNDS {
+                    let key = j * THREADS + i;
+                    map.insert(key, i);
+                    assert_eq!(*map.get(&key).unwrap().val(), i);
+                    if j % 2 == 1 {
+                        assert_eq!(*map.remove(&key).unwrap().val(), i);
+                    }
+                    if j % 32 == 0 {
+                        thread::yield_now();
+                    }
+                }
+            }));
+        }
+        for thread in threads {
+            thread.join().expect("thread failed");
+        }
+
+        let keys = map.iter().map(|guard| *guard.key()).collect::<Vec<_>>();
+        let expected = (0..THREADS * ROUNDS)
+            .filter(|key| key / THREADS % 2 == 0)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn contended_keys_multithreaded() {
+        const THREADS: usize = 8;
+        const KEYS: usize = 16;
+        const ROUNDS: usize = 256;
+
+        let map = Arc::new(SkipMap::new());
+        let inserted = Arc::new(AtomicUsize::new(0));
+        let removed = Arc::new(AtomicUsize::new(0));
+        let mut threads = Vec::with_capacity(THREADS);
+        for i in 0..THREADS {
+            let map = map.clone();
+            let inserted = inserted.clone();
+            let removed = removed.clone();
+            threads.push(thread::spawn(move || {
+                for j in 0..ROUNDS * KEYS {
+                    let key = (j * 7 + i) % KEYS;
+                    if (i + j) % 3 == 0 {
+                        if map.remove(&key).is_some() {
+                            removed.fetch_add(1, Relaxed);
+                        }
+                    } else if map.try_insert(key, i).is_ok() {
+                        inserted.fetch_add(1, Relaxed);
+                    }
+                    let mut prev = None;
+                    for guard in map.range(key..) {
+                        assert!(prev < Some(*guard.key()));
+                        prev = Some(*guard.key());
+                    }
+                    if j % 32 == 0 {
+                        thread::yield_now();
+                    }
+                }
+            }));
+        }
+        for thread in threads {
+            thread.join().expect("thread failed");
+        }
+
+        let remaining = map.iter().count();
+        assert_eq!(inserted.load(Relaxed) - removed.load(Relaxed), remaining);
+    }
+
+    #[test]
+    fn pops_multithreaded() {
+        const THREADS: usize = 8;
+        const ITEMS: usize = 4096;
+
+        let map = Arc::new((0..ITEMS).map(|i| (i, i)).collect::<SkipMap<_, _>>());
+        let mut threads = Vec::with_capacity(THREADS);
+        for i in 0..THREADS {
+            let map = map.clone();
+            threads.push(thread::spawn(move || {
+                let mut popped = Vec::new();
+                loop {
+                    let removed = if i % 2 == 0 {
+                        map.pop_first()
+                    } else {
+                        map.pop_last()
+                    };
+                    match removed {
+                        Some(removed) => popped.push(*removed.key()),
+                        None => break popped,
+                    }
+                }
+            }));
+        }
+
+        let mut popped = Vec::new();
+        for thread in threads {
+            let keys = thread.join().expect("thread failed");
+            // Each thread pops either in ascending or in descending order.
+            assert!(
+                keys.windows(2).all(|pair| pair[0] < pair[1])
+                    || keys.windows(2).all(|pair| pair[0] > pair[1])
+            );
+            popped.extend(keys);
+        }
+        popped.sort_unstable();
+        assert_eq!(popped, (0..ITEMS).collect::<Vec<_>>());
+        assert!(map.is_empty());
+    }
+}
@@ -0,0 +1,529 @@
+use crate::incin::Pause;
+use crate::map::PairGarbage;
+use crate::owned_alloc::OwnedAlloc;
+use alloc::boxed::Box;
+use core::{
+    borrow::Borrow,
+    cell::Cell,
+    cmp::Ordering as CmpOrdering,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    ops::Bound,
+    ptr::{null_mut, NonNull},
+    sync::atomic::{AtomicPtr, AtomicUsize, Ordering::*},
+};
+use std::collections::hash_map::RandomState;
+
+/// The maximum number of levels of a skip list. With a promotion probability
+/// of `1/4`, this is enough for around `4 ^ 16` entries.
+pub const MAX_HEIGHT: usize = 16;
+
+/// The predecessors or successors found at each level by [`Node::find`].
+pub type Tower<K, V> = [*mut Node<K, V>; MAX_HEIGHT];
+
+thread_local! {
+    static SEED: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+/// A node of the skip list. The lowest bit of each `next` pointer marks the
+/// node as removed at that level. A node whose `pair` is null has been
+/// removed, and its `next` pointers are (or will soon be) marked.
+pub struct Node<K, V> {
+    pair: AtomicPtr<(K, V)>,
+    // One reference for the inserter linking the upper levels and one for the
+    // remover unlinking them. The node is only retired after both finished.
+    refs: AtomicUsize,
+    next: Box<[AtomicPtr<Node<K, V>>]>,
+}
+
+impl<K, V> Node<K, V> {
+    /// Creates the head of a skip list, which has no pair and all levels.
+    pub fn new_head() -> OwnedAlloc<Self> {
+        OwnedAlloc::new(Self {
+            pair: AtomicPtr::new(null_mut()),
+            refs: AtomicUsize::new(0),
+            next: (0..MAX_HEIGHT)
+                .map(|_| AtomicPtr::new(null_mut()))
+                .collect(),
+        })
+    }
+
+    fn new_alloc(pair: NonNull<(K, V)>, height: usize) -> OwnedAlloc<Self> {
+        OwnedAlloc::new(Self {
+            pair: AtomicPtr::new(pair.as_ptr()),
+            refs: AtomicUsize::new(2),
+            next: (0..height).map(|_| AtomicPtr::new(null_mut())).collect(),
+        })
+    }
+
+    /// Loads the pair of this node, or [`None`] if the node was removed.
+    ///
+    /// # Safety
+    /// The incinerator must be paused for as long as the reference is used.
+    pub unsafe fn pair<'node>(&self) -> Option<&'node (K, V)> {
+        self.pair.load(Acquire).as_ref()
+    }
+
+    /// Loads the next node at the bottom level, ignoring the mark.
+    ///
+    /// # Safety
+    /// The incinerator must be paused for as long as the reference is used.
+    pub unsafe fn next<'node>(&self) -> Option<&'node Self> {
+        unmarked(self.next[0].load(Acquire)).as_ref()
+    }
+
+    /// Loads the first node with a pair after this one at the bottom level,
+    /// along with the pair.
+    ///
+    /// # Safety
+    /// The incinerator must be paused for as long as the references are used.
+    pub unsafe fn next_present<'node>(&self) -> Option<(&'node Self, &'node (K, V))> {
+        let mut curr = self.next();
+        while let Some(node) = curr {
+            if let Some(pair) = node.pair() {
+                return Some((node, pair));
+            }
+            curr = node.next();
+        }
+        None
+    }
+
+    /// Takes the pair out of this node, which logically removes it.
+    pub fn take_pair(&self) -> Option<NonNull<(K, V)>> {
+        NonNull::new(self.pair.swap(null_mut(), AcqRel))
+    }
+
+    /// Marks every level of this node, from the top to the bottom, so no node
+    /// is linked after it anymore and finds unlink it.
+    fn mark(&self) {
+        for next in self.next.iter().rev() {
+            let mut ptr = next.load(Acquire);
+            while ptr as usize & 1 == 0 {
+                match next.compare_exchange(ptr, (ptr as usize | 1) as *mut _, AcqRel, Acquire) {
+                    Ok(_) => break,
+                    Err(new) => ptr = new,
+                }
+            }
+        }
+    }
+
+    /// Drops one reference, retiring the node if it was the last one.
+    ///
+    /// # Safety
+    /// The node must have been allocated with [`Node::new_alloc`], and this
+    /// must be called once by the inserter and once by the remover, both only
+    /// after they finished touching the links of the node.
+    unsafe fn release(&self, pause: &Pause<Garbage<K, V>>) {
+        if self.refs.fetch_sub(1, AcqRel) == 1 {
+            let alloc = OwnedAlloc::from_raw(NonNull::from(self));
+            pause.add_to_incin(Garbage::Node(alloc));
+        }
+    }
+
+    /// Searches for the given key at every level, starting from this node
+    /// (which must be the head), and unlinks removed nodes on the way. The
+    /// last node before the key and the first node not before the key are
+    /// stored at each level. Returns the node with the key, or null.
+    ///
+    /// # Safety
+    /// The incinerator must be paused while this is called and while the
+    /// stored pointers are used.
+    pub unsafe fn find<Q>(
+        &self,
+        key: &Q,
+        preds: &mut Tower<K, V>,
+        succs: &mut Tower<K, V>,
+    ) -> *mut Self
+    where
+        Q: ?Sized + Ord,
+        K: Borrow<Q>,
+    {
+        'retry: loop {
+            let mut pred = self;
+            let mut found = null_mut();
+
+            for level in (0..MAX_HEIGHT).rev() {
+                let mut curr = pred.next[level].load(Acquire);
+                // If the predecessor was removed, we cannot unlink anything
+                // after it. Start over.
+                if curr as usize & 1 == 1 {
+                    continue 'retry;
+                }
+                found = null_mut();
+
+                // This is safe because we are paused, and nodes are only
+                // retired after being unlinked from every level.
+                while let Some(curr_ref) = curr.as_ref() {
+                    let succ = curr_ref.next[level].load(Acquire);
+
+                    if succ as usize & 1 == 1 {
+                        let unlinked = unmarked(succ);
+                        match pred.next[level].compare_exchange(curr, unlinked, AcqRel, Acquire) {
+                            Ok(_) => curr = unlinked,
+                            Err(_) => continue 'retry,
+                        }
+                        continue;
+                    }
+
+                    match curr_ref.pair() {
+                        // Removed, but the remover did not mark it yet. Help
+                        // it, so we can unlink it in the next iteration.
+                        None => curr_ref.mark(),
+
+                        Some((stored, _)) => match stored.borrow().cmp(key) {
+                            CmpOrdering::Less => {
+                                pred = curr_ref;
+                                curr = succ;
+                            }
+
+                            CmpOrdering::Equal => {
+                                found = curr;
+                                break;
+                            }
+
+                            CmpOrdering::Greater => break,
+                        },
+                    }
+                }
+
+                preds[level] = pred as *const _ as *mut _;
+                succs[level] = curr;
+            }
+
+            break found;
+        }
+    }
+
+    /// Searches, starting from this node (which must be the head), for the
+    /// last present node whose key is `below`, and the first present node
+    /// whose key is not, without unlinking anything.
+    ///
+    /// # Safety
+    /// The incinerator must be paused for as long as the references are used.
+    pub unsafe fn search<'node, F>(
+        &'node self,
+        mut below: F,
+    ) -> (Entry<'node, K, V>, Entry<'node, K, V>)
+    where
+        F: FnMut(&K) -> bool,
+    {
+        let mut pred = None;
+        let mut pred_node = self;
+
+        for level in (0..MAX_HEIGHT).rev() {
+            let mut curr = unmarked(pred_node.next[level].load(Acquire));
+
+            while let Some(curr_ref) = curr.as_ref() {
+                match curr_ref.pair() {
+                    // Removed nodes are skipped. Their links still lead to
+                    // greater keys.
+                    None => (),
+
+                    Some(pair) if below(&pair.0) => {
+                        pred = Some((curr_ref, pair));
+                        pred_node = curr_ref;
+                    }
+
+                    Some(pair) => {
+                        if level == 0 {
+                            return (pred, Some((curr_ref, pair)));
+                        }
+                        break;
+                    }
+                }
+
+                curr = unmarked(curr_ref.next[level].load(Acquire));
+            }
+        }
+
+        (pred, None)
+    }
+
+    /// The first present node within the given lower bound.
+    ///
+    /// # Safety
+    /// Same as [`Node::search`].
+    pub unsafe fn lower_bound<'node, Q>(&'node self, bound: Bound<&Q>) -> Entry<'node, K, V>
+    where
+        Q: ?Sized + Ord,
+        K: Borrow<Q>,
+    {
+        let (_, curr) = self.search(|stored| match bound {
+            Bound::Included(key) => stored.borrow() < key,
+            Bound::Excluded(key) => stored.borrow() <= key,
+            Bound::Unbounded => false,
+        });
+        curr
+    }
+
+    /// The last present node within the given upper bound.
+    ///
+    /// # Safety
+    /// Same as [`Node::search`].
+    pub unsafe fn upper_bound<'node, Q>(&'node self, bound: Bound<&Q>) -> Entry<'node, K, V>
+    where
+        Q: ?Sized + Ord,
+        K: Borrow<Q>,
+    {
+        let (pred, _) = self.search(|stored| match bound {
+            Bound::Included(key) => stored.borrow() <= key,
+            Bound::Excluded(key) => stored.borrow() < key,
+            Bound::Unbounded => true,
+        });
+        pred
+    }
+
+    /// Inserts the given pair, starting from this node (which must be the
+    /// head). If the key is already present, the stored pair is replaced only
+    /// if `replace` is `true`.
+    ///
+    /// # Safety
+    /// The incinerator must be paused, and the pair must be a valid
+    /// allocation owned by the caller.
+    pub unsafe fn insert(
+        &self,
+        pair: NonNull<(K, V)>,
+        replace: bool,
+        pause: &Pause<Garbage<K, V>>,
+    ) -> Insert<K, V>
+    where
+        K: Ord,
+    {
+        let key = &(*pair.as_ptr()).0;
+        let mut preds = [null_mut(); MAX_HEIGHT];
+        let mut succs = [null_mut(); MAX_HEIGHT];
+        let mut new_node = None;
+
+        let node = loop {
+            if let Some(found) = self.find(key, &mut preds, &mut succs).as_ref() {
+                if !replace {
+                    return Insert::Present;
+                }
+
+                let mut old = found.pair.load(Acquire);
+                // If the pair is null, the node was removed meanwhile and we
+                // need to search again.
+                while let Some(nnptr) = NonNull::new(old) {
+                    match found
+                        .pair
+                        .compare_exchange(old, pair.as_ptr(), AcqRel, Acquire)
+                    {
+                        Ok(_) => return Insert::Replaced(nnptr),
+                        Err(new) => old = new,
+                    }
+                }
+                continue;
+            }
+
+            // Dropping this allocation in the paths above is fine: nodes do
+            // not own their pairs.
+            let node = new_node.get_or_insert_with(|| Node::new_alloc(pair, random_height()));
+            node.next[0].store(succs[0], Relaxed);
+
+            let pred = &*preds[0];
+            let ptr = node.raw().as_ptr();
+            if pred.next[0]
+                .compare_exchange(succs[0], ptr, AcqRel, Acquire)
+                .is_ok()
+            {
+                break ptr;
+            }
+        };
+
+        // From now on, the node is owned by the list.
+        if let Some(alloc) = new_node {
+            alloc.into_raw();
+        }
+        let node_ref = &*node;
+
+        'levels: for level in 1..node_ref.next.len() {
+            loop {
+                let succ = succs[level];
+                let next = node_ref.next[level].load(Acquire);
+                // Only a remover changes the link concurrently, marking it.
+                if next as usize & 1 == 1
+                    || (next != succ
+                        && node_ref.next[level]
+                            .compare_exchange(next, succ, AcqRel, Acquire)
+                            .is_err())
+                {
+                    break 'levels;
+                }
+
+                let pred = &*preds[level];
+                if pred.next[level]
+                    .compare_exchange(succ, node, AcqRel, Acquire)
+                    .is_ok()
+                {
+                    break;
+                }
+
+                if self.find(key, &mut preds, &mut succs) != node {
+                    break 'levels;
+                }
+            }
+        }
+
+        // If the node was removed while we linked it, the remover might have
+        // not seen some of the levels we linked. Unlink them ourselves.
+        if node_ref.pair.load(Acquire).is_null() {
+            self.find(key, &mut preds, &mut succs);
+        }
+        node_ref.release(pause);
+
+        Insert::Created
+    }
+
+    /// Removes the given node, starting from this node (which must be the
+    /// head). Returns the removed pair, or [`None`] if some other thread
+    /// removed the node first.
+    ///
+    /// # Safety
+    /// The incinerator must be paused, and the node must have been found in
+    /// this list.
+    pub unsafe fn remove_node(
+        &self,
+        node: &Self,
+        pause: &Pause<Garbage<K, V>>,
+    ) -> Option<NonNull<(K, V)>>
+    where
+        K: Ord,
+    {
+        let pair = node.take_pair()?;
+        node.mark();
+        let mut preds = [null_mut(); MAX_HEIGHT];
+        let mut succs = [null_mut(); MAX_HEIGHT];
+        self.find(&(*pair.as_ptr()).0, &mut preds, &mut succs);
+        node.release(pause);
+        Some(pair)
+    }
+
+    /// Removes the node with the given key, starting from this node (which
+    /// must be the head), if the given closure accepts the pair.
+    ///
+    /// # Safety
+    /// The incinerator must be paused.
+    pub unsafe fn remove<Q, F>(
+        &self,
+        key: &Q,
+        mut interactive: F,
+        pause: &Pause<Garbage<K, V>>,
+    ) -> Option<NonNull<(K, V)>>
+    where
+        Q: ?Sized + Ord,
+        K: Borrow<Q> + Ord,
+        F: FnMut(&(K, V)) -> bool,
+    {
+        let mut preds = [null_mut(); MAX_HEIGHT];
+        let mut succs = [null_mut(); MAX_HEIGHT];
+        let node = self.find(key, &mut preds, &mut succs).as_ref()?;
+
+        loop {
+            let ptr = node.pair.load(Acquire);
+            if !interactive(ptr.as_ref()?) {
+                break None;
+            }
+
+            if node
+                .pair
+                .compare_exchange(ptr, null_mut(), AcqRel, Acquire)
+                .is_ok()
+            {
+                node.mark();
+                self.find(key, &mut preds, &mut succs);
+                node.release(pause);
+                break NonNull::new(ptr);
+            }
+        }
+    }
+
+    /// Frees every node after this one (which must be the head) along with
+    /// their pairs, leaving the list empty.
+    ///
+    /// # Safety
+    /// No other thread may be using the list or any of its nodes.
+    pub unsafe fn free_nodes(&self) {
+        let mut curr = unmarked(self.next[0].load(Relaxed));
+        for next in self.next.iter() {
+            next.store(null_mut(), Relaxed);
+        }
+
+        while let Some(nnptr) = NonNull::new(curr) {
+            let node = OwnedAlloc::from_raw(nnptr);
+            curr = unmarked(node.next[0].load(Relaxed));
+            if let Some(pair) = NonNull::new(node.pair.load(Relaxed)) {
+                drop(OwnedAlloc::from_raw(pair));
+            }
+        }
+    }
+
+    /// Detaches the nodes after this one (which must be the head), returning
+    /// the first one. The caller becomes responsible for freeing them.
+    pub fn take_nodes(&mut self) -> *mut Self {
+        let first = unmarked(*self.next[0].get_mut());
+        for next in self.next.iter_mut() {
+            *next.get_mut() = null_mut();
+        }
+        first
+    }
+}
+
+impl<K, V> fmt::Debug for Node<K, V> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Node {{ pair: {:?}, height: {} }}",
+            self.pair.load(Relaxed),
+            self.next.len()
+        )
+    }
+}
+
+/// A present node along with its pair, as loaded by a search.
+pub type Entry<'node, K, V> = Option<(&'node Node<K, V>, &'node (K, V))>;
+
+/// The outcome of [`Node::insert`].
+pub enum Insert<K, V> {
+    Created,
+    Replaced(NonNull<(K, V)>),
+    Present,
+}
+
+/// Garbage of a skip list: removed pairs and unlinked nodes.
+pub enum Garbage<K, V> {
+    Pair(OwnedAlloc<(K, V)>),
+    Node(OwnedAlloc<Node<K, V>>),
+}
+
+impl<K, V> PairGarbage<K, V> for Garbage<K, V> {
+    fn pair(alloc: OwnedAlloc<(K, V)>) -> Self {
+        Garbage::Pair(alloc)
+    }
+}
+
+impl<K, V> fmt::Debug for Garbage<K, V> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Garbage::Pair(ptr) => write!(fmtr, "Garbage::Pair({:?})", ptr),
+            Garbage::Node(ptr) => write!(fmtr, "Garbage::Node({:?})", ptr),
+        }
+    }
+}
+
+fn unmarked<K, V>(ptr: *mut Node<K, V>) -> *mut Node<K, V> {
+    (ptr as usize & !1) as *mut _
+}
+
+/// Picks a height with probability `1/4` of each extra level, using a
+/// per-thread xorshift generator.
+fn random_height() -> usize {
+    let bits = SEED.with(|seed| {
+        let mut bits = seed.get();
+        bits ^= bits << 13;
+        bits ^= bits >> 7;
+        bits ^= bits << 17;
+        seed.set(bits);
+        bits
+    });
+    (1 + bits.trailing_zeros() as usize / 2).min(MAX_HEIGHT)
+}
@@ -0,0 +1,540 @@
+use super::{
+    IntoIter as MapIntoIter, Range as MapRange, ReadGuard as MapGuard, Removed as MapRemoved,
+    SharedIncin as MapIncin, SkipMap,
+};
+use core::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt,
+    iter::FromIterator,
+    ops::{Deref, RangeBounds, RangeFull},
+};
+
+/// A lock-free ordered set. This is currently implemented on top of
+/// [`SkipMap`]. To check more details about it, please see `SkipMap` docs.
+pub struct SkipSet<T> {
+    inner: SkipMap<T, ()>,
+}
+
+impl<T> SkipSet<T> {
+    /// Creates a new empty [`SkipSet`].
+    pub fn new() -> Self {
+        Self {
+            inner: SkipMap::new(),
+        }
+    }
+
+    /// Creates the [`SkipSet`] using the given shared incinerator.
+    pub fn with_incin(incin: SharedIncin<T>) -> Self {
+        Self {
+            inner: SkipMap::with_incin(incin.inner),
+        }
+    }
+
+    /// The shared incinerator used by this [`SkipSet`].
+    pub fn incin(&self) -> SharedIncin<T> {
+        SharedIncin {
+            inner: self.inner.incin(),
+        }
+    }
+
+    /// Creates a double-ended iterator over guarded references to the
+    /// elements within the given range, in ascending order. The method accepts
+    /// ranges of a type resulted from borrowing the stored element. This
+    /// method will only work correctly if [`Ord`] is implemented in the same
+    /// way for the borrowed type and the stored type.
+    pub fn range<U, R>(&self, range: R) -> Range<'_, T, U, R>
+    where
+        U: ?Sized + Ord,
+        T: Borrow<U>,
+        R: RangeBounds<U>,
+    {
+        Range {
+            inner: self.inner.range(range),
+        }
+    }
+
+    /// The approximate number of elements in the set. Just like
+    /// [`SkipMap::len`], concurrent modifications may be partially observed.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Tests if the set has no elements. Unlike `len`, this does not rely on
+    /// a counter: it looks for the first element.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Returns a guarded reference to the smallest element. If the set is
+    /// empty, [`None`] is returned.
+    pub fn first(&self) -> Option<ReadGuard<'_, T>> {
+        self.inner.first().map(ReadGuard::new)
+    }
+
+    /// Returns a guarded reference to the greatest element. If the set is
+    /// empty, [`None`] is returned.
+    pub fn last(&self) -> Option<ReadGuard<'_, T>> {
+        self.inner.last().map(ReadGuard::new)
+    }
+
+    /// Removes all elements. This method cannot be performed in a shared
+    /// context.
+    pub fn clear(&mut self) {
+        self.inner.clear();
+    }
+}
+
+impl<T> SkipSet<T>
+where
+    T: Ord,
+{
+    /// Creates an iterator over guarded references to the elements, in
+    /// ascending order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.range(..)
+    }
+
+    /// Tests if the given element is present on the [`SkipSet`]. The method
+    /// accepts a type resulted from borrowing the stored element. This method
+    /// will only work correctly if [`Ord`] is implemented in the same way for
+    /// the borrowed type and the stored type.
+    pub fn contains<U>(&self, elem: &U) -> bool
+    where
+        U: ?Sized + Ord,
+        T: Borrow<U>,
+    {
+        self.inner.contains_key(elem)
+    }
+
+    /// Returns a guarded reference to the given element in the [`SkipSet`].
+    /// This may be useful for types with additional metadata. The method
+    /// accepts a type resulted from borrowing the stored element. This method
+    /// will only work correctly if [`Ord`] is implemented in the same way for
+    /// the borrowed type and the stored type. If the element is not found,
+    /// [`None`] is obviously returned.
+    pub fn get<'set, U>(&'set self, elem: &U) -> Option<ReadGuard<'set, T>>
+    where
+        U: ?Sized + Ord,
+        T: Borrow<U>,
+    {
+        self.inner.get(elem).map(ReadGuard::new)
+    }
+
+    /// Inserts the element into the [`SkipSet`]. If the element was already
+    /// present, [`Err`]`(the_passed_value)` is returned.
+    pub fn insert(&self, elem: T) -> Result<(), T> {
+        self.inner.try_insert(elem, ()).map_err(|(elem, _)| elem)
+    }
+
+    /// Removes the given element from the [`SkipSet`]. The method accepts a
+    /// type resulted from borrowing the stored element. This method will only
+    /// work correctly if [`Ord`] is implemented in the same way for the
+    /// borrowed type and the stored type. If the element was not found,
+    /// [`None`] is returned.
+    pub fn remove<U>(&self, elem: &U) -> Option<Removed<T>>
+    where
+        U: ?Sized + Ord,
+        T: Borrow<U>,
+    {
+        self.inner.remove(elem).map(Removed::new)
+    }
+
+    /// Removes _interactively_ the given element. A closure is passed to
+    /// validate the removal. The only argument passed to the closure is a
+    /// reference to the found element. The closure returns if the removal
+    /// should go on. If the element was not found, [`None`] is returned.
+    pub fn remove_with<U, F>(&self, elem: &U, mut interactive: F) -> Option<Removed<T>>
+    where
+        U: ?Sized + Ord,
+        T: Borrow<U>,
+        F: FnMut(&T) -> bool,
+    {
+        self.inner
+            .remove_with(elem, |(elem, _)| interactive(elem))
+            .map(Removed::new)
+    }
+
+    /// Removes the smallest element. If the set is empty, [`None`] is
+    /// returned.
+    pub fn pop_first(&self) -> Option<Removed<T>> {
+        self.inner.pop_first().map(Removed::new)
+    }
+
+    /// Removes the greatest element. If the set is empty, [`None`] is
+    /// returned.
+    pub fn pop_last(&self) -> Option<Removed<T>> {
+        self.inner.pop_last().map(Removed::new)
+    }
+
+    /// Acts just like [`Extend::extend`] but does not require mutability.
+    pub fn extend<I>(&self, iterable: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        for elem in iterable {
+            let _ = self.insert(elem);
+        }
+    }
+}
+
+impl<T> Default for SkipSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for SkipSet<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "SkipSet {{ inner: {:?} }}", self.inner)
+    }
+}
+
+impl<T> IntoIterator for SkipSet<T> {
+    type Item = T;
+
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            inner: self.inner.into_iter(),
+        }
+    }
+}
+
+impl<'set, T> IntoIterator for &'set SkipSet<T>
+where
+    T: Ord,
+{
+    type Item = ReadGuard<'set, T>;
+
+    type IntoIter = Iter<'set, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T> FromIterator<T> for SkipSet<T>
+where
+    T: Ord,
+{
+    fn from_iter<I>(iterable: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let this = Self::new();
+        this.extend(iterable);
+        this
+    }
+}
+
+impl<T> Extend<T> for SkipSet<T>
+where
+    T: Ord,
+{
+    fn extend<I>(&mut self, iterable: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        (*self).extend(iterable)
+    }
+}
+
+/// A read-operation guard. This ensures no element allocation is
+/// mutated or freed while potential reads are performed.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ReadGuard<'set, T>
+where
+    T: 'set,
+{
+    inner: MapGuard<'set, T, ()>,
+}
+
+impl<'set, T> ReadGuard<'set, T> {
+    fn new(inner: MapGuard<'set, T, ()>) -> Self {
+        Self { inner }
+    }
+}
+
+impl<'set, T> Deref for ReadGuard<'set, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.inner.key()
+    }
+}
+
+impl<'set, T> fmt::Debug for ReadGuard<'set, T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(fmtr)
+    }
+}
+
+impl<'set, T> fmt::Display for ReadGuard<'set, T>
+where
+    T: fmt::Display,
+{
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(fmtr)
+    }
+}
+
+impl<'set, T> PartialEq<T> for ReadGuard<'set, T>
+where
+    T: PartialEq,
+{
+    fn eq(&self, other: &T) -> bool {
+        **self == *other
+    }
+}
+
+impl<'set, T> PartialOrd<T> for ReadGuard<'set, T>
+where
+    T: PartialOrd,
+{
+    fn partial_cmp(&self, other: &T) -> Option<Ordering> {
+        (**self).partial_cmp(other)
+    }
+}
+
+impl<'set, T> Borrow<T> for ReadGuard<'set, T> {
+    fn borrow(&self) -> &T {
+        self.deref()
+    }
+}
+
+impl<'set, T> AsRef<T> for ReadGuard<'set, T> {
+    fn as_ref(&self) -> &T {
+        self.deref()
+    }
+}
+
+/// A removed element. Its contents can be moved out only if either the
+/// [`SkipSet`] is dropped or there are no sensitive reads running on that
+/// [`SkipSet`].
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Removed<T> {
+    inner: MapRemoved<T, ()>,
+}
+
+impl<T> Removed<T> {
+    fn new(inner: MapRemoved<T, ()>) -> Self {
+        Self { inner }
+    }
+
+    /// Tries to acquire a mutable reference to the element. Succeeds only if
+    /// either the original [`SkipSet`] was dropped or no sensitive reads are
+    /// being performed.
+    pub fn try_as_mut(this: &mut Self) -> Option<&mut T> {
+        MapRemoved::try_as_mut(&mut this.inner).map(|(elem, _)| elem)
+    }
+
+    /// Tries to convert this wrapper into the element. Succeeds only if either
+    /// the original [`SkipSet`] was dropped or no sensitive reads are being
+    /// performed.
+    pub fn try_into(this: Self) -> Result<T, Self> {
+        match MapRemoved::try_into(this.inner) {
+            Ok((elem, _)) => Ok(elem),
+            Err(inner) => Err(Self::new(inner)),
+        }
+    }
+}
+
+impl<T> Deref for Removed<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.inner.key()
+    }
+}
+
+impl<T> fmt::Debug for Removed<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(fmtr)
+    }
+}
+
+impl<T> fmt::Display for Removed<T>
+where
+    T: fmt::Display,
+{
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(fmtr)
+    }
+}
+
+impl<T> PartialEq<T> for Removed<T>
+where
+    T: PartialEq,
+{
+    fn eq(&self, other: &T) -> bool {
+        **self == *other
+    }
+}
+
+impl<T> PartialOrd<T> for Removed<T>
+where
+    T: PartialOrd,
+{
+    fn partial_cmp(&self, other: &T) -> Option<Ordering> {
+        (**self).partial_cmp(other)
+    }
+}
+
+impl<T> Borrow<T> for Removed<T> {
+    fn borrow(&self) -> &T {
+        self.deref()
+    }
+}
+
+impl<T> AsRef<T> for Removed<T> {
+    fn as_ref(&self) -> &T {
+        self.deref()
+    }
+}
+
+/// A double-ended iterator over the elements of a [`SkipSet`] within a range,
+/// in ascending order. The `Item` of this iterator is a [`ReadGuard`].
+pub struct Range<'set, T, U, R>
+where
+    T: 'set,
+    U: ?Sized,
+{
+    inner: MapRange<'set, T, (), U, R>,
+}
+
+/// An iterator over all elements of a [`SkipSet`], in ascending order. See
+/// [`Range`].
+pub type Iter<'set, T> = Range<'set, T, T, RangeFull>;
+
+impl<'set, T, U, R> Iterator for Range<'set, T, U, R>
+where
+    T: Borrow<U>,
+    U: ?Sized + Ord,
+    R: RangeBounds<U>,
+{
+    type Item = ReadGuard<'set, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(ReadGuard::new)
+    }
+}
+
+impl<'set, T, U, R> DoubleEndedIterator for Range<'set, T, U, R>
+where
+    T: Borrow<U>,
+    U: ?Sized + Ord,
+    R: RangeBounds<U>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(ReadGuard::new)
+    }
+}
+
+impl<'set, T, U, R> fmt::Debug for Range<'set, T, U, R>
+where
+    U: ?Sized,
+    R: fmt::Debug,
+{
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "Range {{ inner: {:?} }}", self.inner)
+    }
+}
+
+/// An iterator over owned elements of a [`SkipSet`], in ascending order.
+pub struct IntoIter<T> {
+    inner: MapIntoIter<T, ()>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(elem, _)| elem)
+    }
+}
+
+impl<T> fmt::Debug for IntoIter<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "IntoIter {{ inner: {:?} }}", self.inner)
+    }
+}
+
+/// The shared incinerator used by [`SkipSet`]. You may want to use this type
+/// in order to reduce memory consumption of the minimal space required by the
+/// incinerator. However, garbage items may be hold for longer time than they
+/// would if no shared incinerator were used.
+pub struct SharedIncin<T> {
+    inner: MapIncin<T, ()>,
+}
+
+impl<T> SharedIncin<T> {
+    /// Creates a new shared incinerator for [`SkipSet`].
+    pub fn new() -> Self {
+        Self {
+            inner: MapIncin::new(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for SharedIncin<T> {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "{:?}", self.inner)
+    }
+}
+
+impl<T> Default for SharedIncin<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for SharedIncin<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn inserts_removes_and_iterates() {
+        let set = SkipSet::new();
+        assert!(set.insert(5).is_ok());
+        assert!(set.insert(3).is_ok());
+        assert!(set.insert(9).is_ok());
+        assert_eq!(set.insert(5), Err(5));
+        assert!(set.contains(&3));
+        assert!(!set.contains(&4));
+        assert_eq!(*set.first().unwrap(), 3);
+        assert_eq!(*set.last().unwrap(), 9);
+        assert_eq!(set.iter().map(|elem| *elem).collect::<Vec<_>>(), [3, 5, 9]);
+        assert_eq!(
+            set.range(4..).rev().map(|elem| *elem).collect::<Vec<_>>(),
+            [9, 5]
+        );
+        assert_eq!(*set.remove(&5).unwrap(), 5);
+        assert!(set.remove_with(&9, |&elem| elem < 9).is_none());
+        assert_eq!(*set.pop_last().unwrap(), 9);
+        assert_eq!(*set.pop_first().unwrap(), 3);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn into_iter() {
+        let set = [4, 1, 3, 1, 2].iter().copied().collect::<SkipSet<_>>();
+        assert_eq!(set.into_iter().collect::<Vec<_>>(), [1, 2, 3, 4]);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::owned_alloc::OwnedAlloc;
+use crate::{count::Counter, owned_alloc::OwnedAlloc};
 use core::{
     fmt,
     iter::FromIterator,
@@ -11,6 +11,7 @@ use core::{
 pub struct Stack<T> {
     top: AtomicPtr<Node<T>>,
     incin: SharedIncin<T>,
+    len: Counter,
 }
 
 impl<T> Stack<T> {
@@ -24,6 +25,7 @@ impl<T> Stack<T> {
         Self {
             top: AtomicPtr::new(null_mut()),
             incin,
+            len: Counter::new(),
         }
     }
 
@@ -42,6 +44,7 @@ impl<T> Stack<T> {
     pub fn push(&self, val: T) {
         // Let's first create a node.
         let mut target = OwnedAlloc::new(Node::new(val, self.top.load(Acquire)));
+        self.len.add(1);
 
         loop {
             // Let's try to publish our changes.
@@ -82,6 +85,7 @@ impl<T> Stack<T> {
                 .compare_exchange(top, unsafe { nnptr.as_ref().next }, AcqRel, Acquire)
             {
                 Ok(_) => {
+                    self.len.sub(1);
                     // Done with an element. Let's first get the "val" to be
                     // returned.
                     //
@@ -101,6 +105,20 @@ impl<T> Stack<T> {
         }
     }
 
+    /// The approximate number of elements in the stack. Concurrent pushes and
+    /// pops may be partially observed, so this is only a hint. Requires the
+    /// `len` feature.
+    #[cfg(feature = "len")]
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    /// Tests if the stack is empty. Unlike `len`, this is exact at the moment
+    /// it loads the top of the stack.
+    pub fn is_empty(&self) -> bool {
+        self.top.load(Acquire).is_null()
+    }
+
     /// Pushes elements from the given iterable. Acts just like
     /// [`Extend::extend`] but does not require mutability.
     pub fn extend<I>(&self, iterable: I)
@@ -136,6 +154,7 @@ impl<T> Iterator for Stack<T> {
             // `OwnedAlloc`. Also, we have exclusive access to this pointer.
             let mut node = unsafe { OwnedAlloc::from_raw(nnptr) };
             *top = node.next;
+            self.len.sub(1);
             // This read is we never drop the inner value when dropping the
             // node.
             unsafe { (&mut *node.val as *mut T).read() }
@@ -167,8 +186,8 @@ impl<T> fmt::Debug for Stack<T> {
     fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
         write!(
             fmtr,
-            "Stack {{ top: {:?}, incin: {:?} }}",
-            self.top, self.incin
+            "Stack {{ top: {:?}, incin: {:?}, len: {:?} }}",
+            self.top, self.incin, self.len
         )
     }
 }
@@ -299,4 +318,20 @@ mod test {
 
         assert_eq!(res, expected);
     }
+
+    #[test]
+    fn len_and_is_empty() {
+        let mut stack = Stack::new();
+        assert!(stack.is_empty());
+        stack.push(3);
+        stack.push(5);
+        assert!(!stack.is_empty());
+        #[cfg(feature = "len")]
+        assert_eq!(stack.len(), 2);
+        stack.pop();
+        assert_eq!(stack.next(), Some(3));
+        assert!(stack.is_empty());
+        #[cfg(feature = "len")]
+        assert_eq!(stack.len(), 0);
+    }
 }
@@ -1,9 +1,10 @@
 mod tid;
 
 pub use self::tid::ThreadId;
+use alloc::boxed::Box;
 use alloc::vec::Vec;
 
-use crate::owned_alloc::{Cache, OwnedAlloc, UninitAlloc};
+use crate::owned_alloc::{Cache, OwnedAlloc};
 use crate::ptr::check_null_align;
 use core::{
     fmt,
@@ -13,7 +14,9 @@ use core::{
     sync::atomic::{AtomicPtr, Ordering::*},
 };
 
-const BITS: usize = 8;
+const DEFAULT_BITS: u32 = 8;
+
+const MAX_BITS: u32 = 16;
 
 /// Per Object Thread Local Storage. The stored data is not dropped on thread
 /// exit. It is only dropped when the structure itself is dropped. After the
@@ -53,13 +56,34 @@ pub struct ThreadLocal<T> {
 impl<T> ThreadLocal<T> {
     /// Creates an empty thread local storage.
     pub fn new() -> Self {
+        Self::with_bits(DEFAULT_BITS)
+    }
+
+    /// Creates an empty thread local storage whose tables consume `bits` bits
+    /// of the thread ID each, i.e. tables have `1 << bits` slots. The default
+    /// is `8`. Fewer bits use less memory when there are few threads.
+    ///
+    /// # Panics
+    /// Panics if `bits` is `0` or greater than `16`.
+    pub fn with_bits(bits: u32) -> Self {
+        assert!(
+            bits > 0 && bits <= MAX_BITS,
+            "table bits must be in 1..={}, got {}",
+            MAX_BITS,
+            bits
+        );
         check_null_align::<Table<T>>();
         check_null_align::<Entry<T>>();
         Self {
-            top: Table::new_alloc(),
+            top: Table::new_alloc(bits),
         }
     }
 
+    /// The number of thread ID bits consumed by each table of this storage.
+    pub fn bits(&self) -> u32 {
+        self.top.bits
+    }
+
     /// Removes and drops all entries. The TLS is considered empty then. This
     /// method is only available with exclusive references. This method is
     /// merely for optimization since the TLS is cleared at drop.
@@ -118,7 +142,7 @@ impl<T> ThreadLocal<T> {
 
         loop {
             // The index of the node for our id.
-            let index = shifted & ((1 << BITS) - 1);
+            let index = table.index(shifted);
 
             // Load what is in there.
             let in_place = table.nodes[index].atomic.load(Acquire);
@@ -165,7 +189,7 @@ impl<T> ThreadLocal<T> {
             // references.
             table = unsafe { &*table_ptr };
             // Shift our "hash" for the next level.
-            shifted >>= BITS;
+            shifted >>= table.bits;
         }
     }
 
@@ -192,7 +216,7 @@ impl<T> ThreadLocal<T> {
         let mut depth = 1;
         let mut shifted = id.bits();
         // The pointer stored in place.
-        let mut index = shifted & ((1 << BITS) - 1);
+        let mut index = table.index(shifted);
         let mut in_place = table.nodes[index].atomic.load(Acquire);
         // Using `LazyInit` to make sure we only initialize if there is no
         // entry.
@@ -252,11 +276,13 @@ impl<T> ThreadLocal<T> {
                 }
 
                 // Get a table allocation from the cache.
-                let new_tbl = tbl_cache.take_or(Table::new_alloc);
+                let new_tbl = tbl_cache.take_or(|| Table::new_alloc(table.bits));
 
-                // Calculate index for the collided entry.
-                let other_shifted = entry.id.bits() >> (depth * BITS);
-                let other_index = other_shifted & ((1 << BITS) - 1);
+                // Calculate index for the collided entry. Both IDs share the
+                // bits consumed so far and they differ, so the shift never
+                // reaches the ID's width.
+                let other_shifted = entry.id.bits() >> (depth * table.bits);
+                let other_index = new_tbl.index(other_shifted);
 
                 // Pre-insert it in the table from the cache.
                 new_tbl.nodes[other_index].atomic.store(in_place, Relaxed);
@@ -286,9 +312,9 @@ impl<T> ThreadLocal<T> {
                         // We are going one depth further.
                         depth += 1;
                         // Shift our "hash" for the next level.
-                        shifted >>= BITS;
+                        shifted >>= table.bits;
                         // Load new in place pointer.
-                        index = shifted & ((1 << BITS) - 1);
+                        index = table.index(shifted);
                         in_place = table.nodes[index].atomic.load(Acquire);
                     }
 
@@ -326,9 +352,9 @@ impl<T> ThreadLocal<T> {
                 // We are going one depth further.
                 depth += 1;
                 // Shift our "hash" for the next level.
-                shifted >>= BITS;
+                shifted >>= table.bits;
                 // Load new in place pointer.
-                index = shifted & ((1 << BITS) - 1);
+                index = table.index(shifted);
                 in_place = table.nodes[index].atomic.load(Acquire);
             }
         }
@@ -664,33 +690,34 @@ impl<T> fmt::Debug for Node<T> {
 
 #[repr(align(/* at least */ 2))]
 struct Table<T> {
-    nodes: [Node<T>; 1 << BITS],
+    // Number of ID bits consumed by this table. There are `1 << bits` nodes.
+    bits: u32,
+    nodes: Box<[Node<T>]>,
 }
 
 impl<T> Table<T> {
     #[inline]
-    fn new_alloc() -> OwnedAlloc<Self> {
-        // Safe because it calls a correctly a function which correctly
-        // initializes uninitialized memory with, indeed, uninitialized memory.
-        unsafe { UninitAlloc::<Self>::new().init_in_place(|this| this.init()) }
-    }
-
-    // Unsafe because passing ininitialized memory may cause leaks.
-    #[inline]
-    unsafe fn init(&mut self) {
-        for node_ref in &mut self.nodes as &mut [_] {
-            (node_ref as *mut Node<T>).write(Node {
+    fn new_alloc(bits: u32) -> OwnedAlloc<Self> {
+        let nodes = (0..1usize << bits)
+            .map(|_| Node {
                 atomic: AtomicPtr::new(null_mut()),
                 _marker: PhantomData,
             })
-        }
+            .collect();
+        OwnedAlloc::new(Self { bits, nodes })
+    }
+
+    // The index of the node for the given shifted ID.
+    #[inline]
+    fn index(&self, shifted: usize) -> usize {
+        shifted & (self.nodes.len() - 1)
     }
 
     // Unsafe because calling this function and using the table again later will
     // cause undefined behavior.
     #[inline]
     unsafe fn free_nodes(&mut self, tbl_stack: &mut Vec<OwnedAlloc<Table<T>>>) {
-        for node in &mut self.nodes as &mut [Node<T>] {
+        for node in self.nodes.iter_mut() {
             Node::free_ptr(*node.atomic.get_mut(), tbl_stack);
         }
     }
@@ -699,7 +726,7 @@ impl<T> Table<T> {
     // undefined behavior.
     #[inline]
     unsafe fn clear(&mut self, tbl_stack: &mut Vec<OwnedAlloc<Table<T>>>) {
-        for node in &mut self.nodes as &mut [Node<T>] {
+        for node in self.nodes.iter_mut() {
             let ptr = node.atomic.get_mut();
             Node::free_ptr(*ptr, tbl_stack);
             *ptr = null_mut();
@@ -709,7 +736,11 @@ impl<T> Table<T> {
 
 impl<T> fmt::Debug for Table<T> {
     fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
-        write!(fmtr, "Table {{ nodes: {:?} }}", &self.nodes as &[Node<T>])
+        write!(
+            fmtr,
+            "Table {{ bits: {}, nodes: {:?} }}",
+            self.bits, &self.nodes
+        )
     }
 }
 
@@ -837,4 +868,38 @@ mod test {
             assert_eq!(status, 2);
         }
     }
+
+    #[test]
+    fn with_bits() {
+        const THREADS: usize = 32;
+
+        let tls = Arc::new(ThreadLocal::with_bits(1));
+        assert_eq!(tls.bits(), 1);
+        let mut threads = Vec::with_capacity(THREADS);
+        // prevent IDs from being reused.
+        let barrier = Arc::new(Barrier::new(THREADS));
+
+        for i in 0..THREADS {
+            let tls = tls.clone();
+            let barrier = barrier.clone();
+            threads.push(thread::spawn(move || {
+                assert_eq!(*tls.with_init(|| i), i);
+                barrier.wait();
+                assert_eq!(*tls.get().unwrap(), i);
+            }))
+        }
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let mut done = [0; THREADS];
+        for entry in Arc::try_unwrap(tls).unwrap() {
+            done[entry] += 1;
+        }
+
+        for &status in &done as &[_] {
+            assert_eq!(status, 1);
+        }
+    }
 }
@@ -0,0 +1,447 @@
+use crate::map::{Iter as MapIter, Map, MapBuilder, ReadGuard, Removed};
+use core::{
+    borrow::Borrow,
+    fmt,
+    hash::{BuildHasher, Hash},
+    ops::Deref,
+    ptr,
+    sync::atomic::{AtomicU64, Ordering::*},
+    time::Duration,
+};
+use std::{collections::hash_map::RandomState, time::Instant};
+
+/// A source of time for a [`TtlMap`]. The time is measured since an arbitrary
+/// epoch, fixed for each clock, and it must never go backwards.
+pub trait Clock {
+    /// The time elapsed since the epoch of this clock.
+    fn now(&self) -> Duration;
+}
+
+/// A [`Clock`] which reads the system's monotonic clock, with the time of its
+/// creation as the epoch.
+#[derive(Debug, Clone, Copy)]
+pub struct InstantClock {
+    epoch: Instant,
+}
+
+impl InstantClock {
+    /// Creates a clock whose epoch is now.
+    pub fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+        }
+    }
+}
+
+impl Default for InstantClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for InstantClock {
+    fn now(&self) -> Duration {
+        self.epoch.elapsed()
+    }
+}
+
+/// A [`Clock`] which only moves when told to, useful for tests.
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    nanos: AtomicU64,
+}
+
+impl ManualClock {
+    /// Creates a clock at its epoch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the clock forward by the given duration. The clock stops at the
+    /// latest time it can represent, about 584 years after its epoch, rather
+    /// than going backwards.
+    pub fn advance(&self, by: Duration) {
+        let by = u64::try_from(by.as_nanos()).unwrap_or(u64::MAX);
+        let _ = self
+            .nanos
+            .fetch_update(Relaxed, Relaxed, |nanos| Some(nanos.saturating_add(by)));
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Relaxed))
+    }
+}
+
+impl<C> Clock for &C
+where
+    C: ?Sized + Clock,
+{
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+}
+
+/// A lock-free map whose entries may expire. This is implemented on top of
+/// [`Map`], whose values are wrapped in [`Expiring`].
+///
+/// Entries inserted through [`insert_with_ttl`](TtlMap::insert_with_ttl)
+/// expire once the given time to live has passed according to the map's
+/// [`Clock`]. Expired entries are treated as absent. Lookups finding them
+/// remove them, and [`purge_expired`](TtlMap::purge_expired) removes all of
+/// them at once.
+///
+/// # Example
+/// ```rust
+/// use lock_freedom::ttl::{ManualClock, TtlMap};
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let sessions = TtlMap::with_clock(&clock);
+/// sessions.insert_with_ttl("alice", 1, Duration::from_secs(60));
+/// sessions.insert("bob", 2);
+///
+/// clock.advance(Duration::from_secs(30));
+/// assert_eq!(**sessions.get("alice").unwrap().val(), 1);
+///
+/// clock.advance(Duration::from_secs(30));
+/// assert!(sessions.get("alice").is_none());
+/// assert_eq!(**sessions.get("bob").unwrap().val(), 2);
+/// ```
+pub struct TtlMap<K, V, C = InstantClock, H = RandomState> {
+    map: Map<K, Expiring<V>, H>,
+    clock: C,
+}
+
+impl<K, V> TtlMap<K, V> {
+    /// Creates a [`TtlMap`] reading the system's monotonic clock, with the
+    /// default hasher builder.
+    pub fn new() -> Self {
+        Self::with_clock(InstantClock::new())
+    }
+}
+
+impl<K, V, C> TtlMap<K, V, C>
+where
+    C: Clock,
+{
+    /// Creates a [`TtlMap`] reading the given clock, with the default hasher
+    /// builder.
+    pub fn with_clock(clock: C) -> Self {
+        Self::with_builder_and_clock(MapBuilder::new(), clock)
+    }
+}
+
+impl<K, V, C, H> TtlMap<K, V, C, H> {
+    /// Creates an iterator over guarded references to the entries which had
+    /// not expired when the iterator was created.
+    pub fn iter(&self) -> Iter<'_, K, V>
+    where
+        C: Clock,
+    {
+        Iter {
+            inner: self.map.iter(),
+            now: self.clock.now(),
+        }
+    }
+
+    /// The clock of this map.
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+}
+
+impl<K, V, C, H> TtlMap<K, V, C, H>
+where
+    C: Clock,
+    H: BuildHasher,
+{
+    /// Creates a [`TtlMap`] reading the given clock, using the configuration
+    /// of the given [`MapBuilder`].
+    pub fn with_builder_and_clock(builder: MapBuilder<H>, clock: C) -> Self {
+        Self {
+            map: Map::with_builder(builder),
+            clock,
+        }
+    }
+
+    /// Searches for the entry identified by the given key. An expired entry is
+    /// treated as absent, and removed. The method accepts a type resulted from
+    /// borrowing the stored key, just like [`Map::get`].
+    pub fn get<'map, Q>(&'map self, key: &Q) -> Option<ReadGuard<'map, K, Expiring<V>>>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let guard = self.map.get(key)?;
+        if !guard.val().is_expired(self.clock.now()) {
+            return Some(guard);
+        }
+
+        // Only removes the very entry we found expired, and not another one
+        // inserted meanwhile with the same key.
+        self.map.remove_with(key, |pair| ptr::eq(pair, &*guard));
+        None
+    }
+
+    /// Inserts unconditionally the given key and value, which never expire.
+    /// If an entry with the same key was present and had not expired, it is
+    /// returned.
+    pub fn insert(&self, key: K, val: V) -> Option<Removed<K, Expiring<V>>>
+    where
+        K: Hash + Eq,
+    {
+        self.insert_expiring(key, Expiring::new(val, None))
+    }
+
+    /// Inserts unconditionally the given key and value, which expire after the
+    /// given time to live. If an entry with the same key was present and had
+    /// not expired, it is returned.
+    pub fn insert_with_ttl(&self, key: K, val: V, ttl: Duration) -> Option<Removed<K, Expiring<V>>>
+    where
+        K: Hash + Eq,
+    {
+        // A deadline too far to be represented is as good as none.
+        let deadline = self.clock.now().checked_add(ttl);
+        self.insert_expiring(key, Expiring::new(val, deadline))
+    }
+
+    fn insert_expiring(&self, key: K, expiring: Expiring<V>) -> Option<Removed<K, Expiring<V>>>
+    where
+        K: Hash + Eq,
+    {
+        let removed = self.map.insert(key, expiring)?;
+        if removed.val().is_expired(self.clock.now()) {
+            None
+        } else {
+            Some(removed)
+        }
+    }
+
+    /// Removes unconditionally the entry identified by the given key. If it
+    /// had not expired, it is returned. The method accepts a type resulted
+    /// from borrowing the stored key, just like [`Map::remove`].
+    pub fn remove<Q>(&self, key: &Q) -> Option<Removed<K, Expiring<V>>>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let removed = self.map.remove(key)?;
+        if removed.val().is_expired(self.clock.now()) {
+            None
+        } else {
+            Some(removed)
+        }
+    }
+
+    /// Removes every entry which has expired, without requiring exclusive
+    /// access. Just like [`Map::retain`], entries inserted concurrently might
+    /// not be visited.
+    pub fn purge_expired(&self)
+    where
+        K: Hash + Eq,
+    {
+        let now = self.clock.now();
+        self.map.retain(|_, expiring| !expiring.is_expired(now));
+    }
+}
+
+impl<K, V> Default for TtlMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, C, H> fmt::Debug for TtlMap<K, V, C, H>
+where
+    C: fmt::Debug,
+    H: fmt::Debug,
+{
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "TtlMap {{ map: {:?}, clock: {:?} }}",
+            self.map, self.clock
+        )
+    }
+}
+
+/// A value stored in a [`TtlMap`], together with its deadline. Dereferences to
+/// the value.
+pub struct Expiring<V> {
+    val: V,
+    deadline: Option<Duration>,
+}
+
+impl<V> Expiring<V> {
+    fn new(val: V, deadline: Option<Duration>) -> Self {
+        Self { val, deadline }
+    }
+
+    /// The stored value.
+    pub fn val(&self) -> &V {
+        &self.val
+    }
+
+    /// The time, according to the map's [`Clock`], at which the entry
+    /// expires. `None` if it never expires.
+    pub fn deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    /// Tests if the entry has expired at the given time.
+    pub fn is_expired(&self, now: Duration) -> bool {
+        self.deadline.map_or(false, |deadline| deadline <= now)
+    }
+
+    /// Takes the value out.
+    pub fn into_inner(self) -> V {
+        self.val
+    }
+}
+
+impl<V> Deref for Expiring<V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        &self.val
+    }
+}
+
+impl<V> fmt::Debug for Expiring<V>
+where
+    V: fmt::Debug,
+{
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Expiring {{ val: {:?}, deadline: {:?} }}",
+            self.val, self.deadline
+        )
+    }
+}
+
+/// An iterator over the entries of a [`TtlMap`] which had not expired when
+/// the iterator was created. Just like [`map::Iter`](crate::map::Iter), it may
+/// be inconsistent if the map is shared.
+#[derive(Debug)]
+pub struct Iter<'map, K, V>
+where
+    K: 'map,
+    V: 'map,
+{
+    inner: MapIter<'map, K, Expiring<V>>,
+    now: Duration,
+}
+
+impl<'map, K, V> Iterator for Iter<'map, K, V> {
+    type Item = ReadGuard<'map, K, Expiring<V>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let now = self.now;
+        self.inner.find(|guard| !guard.val().is_expired(now))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{sync::Arc, vec::Vec};
+    use std::thread;
+
+    #[test]
+    fn expires_lazily() {
+        let clock = ManualClock::new();
+        let map = TtlMap::with_clock(&clock);
+        assert!(map.insert_with_ttl(1, 10, Duration::from_secs(2)).is_none());
+        assert!(map.insert_with_ttl(2, 20, Duration::from_secs(1)).is_none());
+        assert!(map.insert(3, 30).is_none());
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(
+            map.get(&1).unwrap().val().deadline(),
+            Some(Duration::from_secs(2))
+        );
+        // Still stored until a lookup finds it expired.
+        assert!(map.map.get(&2).is_some());
+        assert!(map.get(&2).is_none());
+        assert!(map.map.get(&2).is_none());
+
+        clock.advance(Duration::from_secs(100));
+        assert!(map.remove(&1).is_none());
+        assert_eq!(**map.get(&3).unwrap().val(), 30);
+        assert_eq!(map.iter().count(), 1);
+    }
+
+    #[test]
+    fn replaces_and_removes() {
+        let clock = ManualClock::new();
+        let map = TtlMap::with_clock(&clock);
+        map.insert_with_ttl("a", 1, Duration::from_secs(1));
+        let removed = map.insert_with_ttl("a", 2, Duration::from_secs(1)).unwrap();
+        assert_eq!(**removed.val(), 1);
+
+        clock.advance(Duration::from_secs(1));
+        // The replaced entry had expired, so it is as if it was absent.
+        assert!(map
+            .insert_with_ttl("a", 3, Duration::from_secs(1))
+            .is_none());
+        let removed = map.remove("a").unwrap();
+        assert_eq!(Removed::try_into(removed).unwrap().1.into_inner(), 3);
+        assert!(map.remove("a").is_none());
+
+        // Too long to be represented, so it never expires.
+        map.insert_with_ttl("b", 4, Duration::MAX);
+        clock.advance(Duration::from_secs(1 << 40));
+        assert_eq!(clock.now(), Duration::from_nanos(u64::MAX));
+        assert!(map.get("b").is_some());
+    }
+
+    #[test]
+    fn purge_expired() {
+        let clock = ManualClock::new();
+        let map = TtlMap::with_clock(&clock);
+        for i in 0..100u64 {
+            map.insert_with_ttl(i, i, Duration::from_secs(i % 4));
+        }
+        clock.advance(Duration::from_secs(2));
+        map.purge_expired();
+
+        let mut keys = map.map.iter().map(|guard| *guard.key()).collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, (0..100).filter(|i| i % 4 >= 3).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn multithreaded() {
+        const THREADS: usize = 8;
+        const ROUNDS: usize = 512;
+
+        let map = Arc::new(TtlMap::with_clock(ManualClock::new()));
+        let mut threads = Vec::with_capacity(THREADS);
+        for i in 0..THREADS {
+            let map = map.clone();
+            threads.push(thread::spawn(move || {
+                for j in 0..ROUNDS {
+                    map.insert_with_ttl((i, j), j, Duration::from_millis(j as u64 % 8));
+                    map.clock().advance(Duration::from_millis(1));
+                    if let Some(guard) = map.get(&(i, j / 2)) {
+                        assert_eq!(**guard.val(), j / 2);
+                    }
+                    if j % 64 == 0 {
+                        map.purge_expired();
+                        thread::yield_now();
+                    }
+                }
+            }));
+        }
+        for thread in threads {
+            thread.join().expect("thread failed");
+        }
+
+        map.clock().advance(Duration::from_millis(8));
+        map.purge_expired();
+        assert!(map.map.is_empty());
+    }
+}